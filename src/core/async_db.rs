@@ -0,0 +1,57 @@
+use crate::core::database::Database;
+use anyhow::{Context, Result};
+
+/// Async front for [`Database`]. Every call runs on tokio's blocking pool so a
+/// slow query never parks the task that is driving the manager loop.
+#[derive(Clone)]
+pub struct AsyncDatabase {
+    db: Database,
+}
+
+impl AsyncDatabase {
+    pub fn new(db: Database) -> Self {
+        Self { db }
+    }
+
+    /// Runs `f` against the database on a blocking thread and awaits its result.
+    pub async fn call<R, F>(&self, f: F) -> Result<R>
+    where
+        F: FnOnce(&Database) -> Result<R> + Send + 'static,
+        R: Send + 'static,
+    {
+        let db = self.db.clone();
+        tokio::task::spawn_blocking(move || f(&db))
+            .await
+            .context("Database task panicked")?
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::core::types::{ClipboardItem, ClipboardType};
+
+    #[tokio::test]
+    async fn call_runs_queries_off_the_caller() {
+        let db = AsyncDatabase::new(Database::open_in_memory().unwrap());
+        let item = ClipboardItem {
+            id: None,
+            type_: ClipboardType::Text,
+            content: "hello".to_string(),
+            content_hash: "hash-hello".to_string(),
+            source_app: None,
+            created_at: 1,
+            is_pinned: false,
+            edited_at: None,
+            pin_order: None,
+            tags: Vec::new(),
+        };
+
+        let id = db.call(move |db| db.insert_history(&item)).await.unwrap();
+        let found = db.call(move |db| db.get_item_by_id(id)).await.unwrap();
+        assert_eq!(found.unwrap().content, "hello");
+
+        let err = db.call(|_| -> Result<()> { anyhow::bail!("boom") }).await;
+        assert!(err.is_err());
+    }
+}
@@ -5,23 +5,115 @@ use arboard::{Clipboard, ImageData};
 use chrono::Utc;
 use image::{ImageBuffer, RgbaImage};
 use sha2::{Digest, Sha256};
+use std::collections::VecDeque;
 use std::sync::{Arc, Mutex};
-use std::time::Duration;
+use std::time::{Duration, Instant};
 use tokio::sync::mpsc;
 
+/// How long a self-write stays recognizable. Covers a few monitor polls.
+const SELF_WRITE_TTL: Duration = Duration::from_secs(5);
+
+/// Hash used for `content_hash` and deduplication.
+pub fn content_hash(data: &[u8]) -> String {
+    let mut hasher = Sha256::new();
+    hasher.update(data);
+    format!("{:x}", hasher.finalize())
+}
+
+/// A text item holding `text` the way a capture of it is stored, so text
+/// added by hand and the same text copied later dedup. `None` if nothing is
+/// left once the text is cleaned.
+pub fn captured_text(text: &str) -> Option<ClipboardItem> {
+    let content = text::clean_text(text);
+    if content.is_empty() {
+        return None;
+    }
+    Some(ClipboardItem {
+        id: None,
+        type_: ClipboardType::Text,
+        content_hash: content_hash(content.as_bytes()),
+        content,
+        source_app: None,
+        created_at: Utc::now().timestamp(),
+        is_pinned: false,
+        edited_at: None,
+        pin_order: None,
+        tags: Vec::new(),
+    })
+}
+
+/// Clipboard contents picaclip wrote itself (pastes and restores), shared
+/// between the writer and the monitor so those changes aren't captured again.
+#[derive(Clone, Default)]
+pub struct SelfWrites {
+    marks: Arc<Mutex<Marks>>,
+}
+
+#[derive(Default)]
+struct Marks {
+    next_seq: u64,
+    /// Pending writes in the order they were made.
+    pending: VecDeque<Mark>,
+}
+
+struct Mark {
+    hash: String,
+    seq: u64,
+    expires: Instant,
+}
+
+impl SelfWrites {
+    /// Marks text about to be written, hashed the way the monitor will see it.
+    pub fn mark_text(&self, text: &str) {
+        self.mark(content_hash(text::clean_text(text).as_bytes()));
+    }
+
+    /// Marks RGBA image bytes about to be written.
+    pub fn mark_image(&self, bytes: &[u8]) {
+        self.mark(content_hash(bytes));
+    }
+
+    fn mark(&self, hash: String) {
+        let mut marks = self.marks.lock().unwrap();
+        marks.pending.retain(|mark| mark.expires > Instant::now());
+        let seq = marks.next_seq;
+        marks.next_seq += 1;
+        marks.pending.push_back(Mark {
+            hash,
+            seq,
+            expires: Instant::now() + SELF_WRITE_TTL,
+        });
+    }
+
+    /// Consumes the latest mark for `hash`, returning whether picaclip wrote
+    /// what the monitor sees. Writes marked before it were overwritten by it,
+    /// so their marks go too and can't hide a later copy by the user.
+    pub fn take(&self, hash: &str) -> bool {
+        let mut marks = self.marks.lock().unwrap();
+        marks.pending.retain(|mark| mark.expires > Instant::now());
+        let Some(seq) = marks.pending.iter().rev().find(|mark| mark.hash == hash).map(|mark| mark.seq) else {
+            return false;
+        };
+        marks.pending.retain(|mark| mark.seq > seq);
+        true
+    }
+}
+
 pub struct ClipboardMonitor {
     tx: mpsc::Sender<ClipboardItem>,
     clipboard: Arc<Mutex<Clipboard>>,
     last_hash: String,
+    self_writes: SelfWrites,
 }
 
 impl ClipboardMonitor {
-    pub fn new(tx: mpsc::Sender<ClipboardItem>) -> Result<Self> {
+    pub fn new(tx: mpsc::Sender<ClipboardItem>, self_writes: SelfWrites) -> Result<Self> {
         let clipboard = Clipboard::new().context("Failed to initialize clipboard")?;
         Ok(Self {
             tx,
             clipboard: Arc::new(Mutex::new(clipboard)),
             last_hash: String::new(),
+            self_writes,
         })
     }
 
@@ -43,28 +135,18 @@ impl ClipboardMonitor {
 
         // Try getting text
         if let Ok(text) = clipboard.get_text() {
-            let cleaned = text::clean_text(&text);
-            if cleaned.is_empty() {
+            let Some(item) = captured_text(&text) else {
                 return Ok(());
-            }
+            };
 
-            let hash = self.compute_hash(cleaned.as_bytes());
-            if hash != self.last_hash {
+            // Checked even when unchanged: a restore may put back what we last saw
+            let ours = self.self_writes.take(&item.content_hash);
+            if item.content_hash != self.last_hash {
                 // New content
-                self.last_hash = hash.clone();
-
-                let item = ClipboardItem {
-                    id: None,
-                    type_: ClipboardType::Text,
-                    content: cleaned,
-                    content_hash: hash,
-                    source_app: None,
-                    created_at: Utc::now().timestamp(),
-                    is_pinned: false,
-                    tags: Vec::new(),
-                };
-
-                let _ = self.tx.try_send(item);
+                self.last_hash = item.content_hash.clone();
+                if !ours {
+                    let _ = self.tx.try_send(item);
+                }
             }
             return Ok(());
         }
@@ -74,8 +156,12 @@ impl ClipboardMonitor {
             // Convert to hashable bytes
             let hash = self.compute_hash(&image_data.bytes);
 
+            let ours = self.self_writes.take(&hash);
             if hash != self.last_hash {
                 self.last_hash = hash.clone();
+                if ours {
+                    return Ok(());
+                }
 
                 // Save image to file
                 let path = self.save_image(&image_data)?;
@@ -88,6 +174,8 @@ impl ClipboardMonitor {
                     source_app: None,
                     created_at: Utc::now().timestamp(),
                     is_pinned: false,
+                    edited_at: None,
+                    pin_order: None,
                     tags: Vec::new(),
                 };
 
@@ -100,9 +188,7 @@ impl ClipboardMonitor {
     }
 
     fn compute_hash(&self, data: &[u8]) -> String {
-        let mut hasher = Sha256::new();
-        hasher.update(data);
-        format!("{:x}", hasher.finalize())
+        content_hash(data)
     }
 
     fn save_image(&self, image_data: &ImageData) -> Result<String> {
@@ -120,3 +206,43 @@ impl ClipboardMonitor {
         Ok(path.to_string_lossy().to_string())
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_captured_text_is_cleaned() {
+        let piped = captured_text("foo\n").unwrap();
+        assert_eq!(piped.content, "foo");
+        assert_eq!(piped.content_hash, captured_text("foo").unwrap().content_hash);
+        assert!(captured_text(" \n\t").is_none());
+    }
+
+    #[test]
+    fn test_self_writes_are_recognized_once() {
+        let self_writes = SelfWrites::default();
+        self_writes.mark_text("  pasted\n");
+        self_writes.mark_image(&[1, 2, 3, 4]);
+
+        // The monitor hashes cleaned text
+        assert!(self_writes.take(&content_hash(b"pasted")));
+        assert!(!self_writes.take(&content_hash(b"pasted")));
+        assert!(self_writes.take(&content_hash(&[1, 2, 3, 4])));
+        assert!(!self_writes.take(&content_hash(b"copied by the user")));
+    }
+
+    #[test]
+    fn test_observed_self_write_drops_older_marks() {
+        let self_writes = SelfWrites::default();
+        // A paste, then a restore, both before the monitor looks
+        self_writes.mark_text("pasted");
+        self_writes.mark_text("restored");
+        self_writes.mark_text("pasted");
+
+        assert!(self_writes.take(&content_hash(b"restored")));
+        // The first paste was overwritten unseen; copying it again is the user's
+        assert!(self_writes.take(&content_hash(b"pasted")));
+        assert!(!self_writes.take(&content_hash(b"pasted")));
+    }
+}
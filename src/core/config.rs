@@ -0,0 +1,311 @@
+use crate::utils::paths;
+use serde::{Deserialize, Serialize};
+use std::fs;
+use std::path::Path;
+
+/// User settings, stored as JSON next to the database.
+///
+/// Every section falls back to its defaults, so an old or partial config file
+/// keeps working when new options are added.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(default)]
+pub struct Config {
+    pub search: SearchConfig,
+    pub ranking: RankingConfig,
+    pub paste: PasteConfig,
+    pub history: HistoryConfig,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct HistoryConfig {
+    /// How long a delete, clear, unpin or edit can be undone.
+    pub undo_seconds: u64,
+    /// Deleted items stay in the trash this long before they and their
+    /// image files are removed for good. Never shorter than the undo window.
+    pub trash_retention_hours: u64,
+}
+
+impl Default for HistoryConfig {
+    fn default() -> Self {
+        Self {
+            undo_seconds: 10,
+            trash_retention_hours: 24,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct SearchConfig {
+    pub tokenizer: FtsTokenizer,
+    /// Rank the search box with the typo-tolerant matcher in `core::fuzzy`
+    /// instead of plain FTS relevance.
+    pub fuzzy: bool,
+    /// How many recent history items are fuzzy-matched besides the FTS candidates.
+    pub recent_candidates: usize,
+}
+
+impl Default for SearchConfig {
+    fn default() -> Self {
+        Self {
+            tokenizer: FtsTokenizer::default(),
+            fuzzy: true,
+            recent_candidates: 500,
+        }
+    }
+}
+
+/// Weights of the quick-pick ranking. Every signal is normalized to 0..=1
+/// before weighting, so the weights compare directly.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct RankingConfig {
+    pub match_weight: f64,
+    pub recency_weight: f64,
+    /// Age at which the recency signal has dropped to one half.
+    pub recency_half_life_hours: f64,
+    pub pin_weight: f64,
+    pub usage_weight: f64,
+}
+
+impl Default for RankingConfig {
+    fn default() -> Self {
+        Self {
+            match_weight: 1.0,
+            recency_weight: 0.3,
+            recency_half_life_hours: 72.0,
+            pin_weight: 0.2,
+            usage_weight: 0.2,
+        }
+    }
+}
+
+/// How a paste is delivered to the focused application.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct PasteConfig {
+    /// Used when no rule matches the focused application.
+    pub strategy: PasteStrategy,
+    /// Checked in order; the first rule matching the focused application's
+    /// name wins.
+    pub rules: Vec<PasteRule>,
+    /// Put the previous clipboard contents back after pasting.
+    pub restore_clipboard: bool,
+    /// How long the pasted item stays on the clipboard before the restore.
+    /// Slow apps read the clipboard late after the keystroke.
+    pub restore_delay_ms: u64,
+    /// What pasting does to the pasted history item's place and ranking. The
+    /// clipboard monitor never records picaclip's own writes; this decides the
+    /// effect instead. Pastes are counted in the item's statistics either way.
+    pub self_write_policy: SelfWritePolicy,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum SelfWritePolicy {
+    /// The item keeps its place and pastes don't feed the ranking.
+    Ignore,
+    /// The item moves to the top, like a fresh copy.
+    Bump,
+    /// The item keeps its place and its pastes feed the ranking.
+    #[default]
+    CountAsUsage,
+}
+
+impl Default for PasteConfig {
+    fn default() -> Self {
+        let terminal_paste = PasteStrategy::KeyChord {
+            keys: vec!["ctrl".to_string(), "shift".to_string(), "v".to_string()],
+        };
+        // Short names like "foot" would also match unrelated apps as substrings
+        let terminals = [
+            ("terminal", false),
+            ("konsole", false),
+            ("alacritty", false),
+            ("kitty", false),
+            ("xterm", false),
+            ("wezterm", false),
+            ("foot", true),
+            ("footclient", true),
+            ("tilix", false),
+        ];
+        Self {
+            strategy: PasteStrategy::default(),
+            rules: terminals
+                .iter()
+                .map(|&(app, exact)| PasteRule {
+                    app: app.to_string(),
+                    exact,
+                    strategy: terminal_paste.clone(),
+                })
+                .collect(),
+            restore_clipboard: false,
+            restore_delay_ms: 500,
+            self_write_policy: SelfWritePolicy::default(),
+        }
+    }
+}
+
+impl PasteConfig {
+    pub fn strategy_for(&self, app: Option<&str>) -> &PasteStrategy {
+        let Some(app) = app.map(str::to_lowercase) else {
+            return &self.strategy;
+        };
+        self.rules
+            .iter()
+            .find(|rule| rule.matches(&app))
+            .map_or(&self.strategy, |rule| &rule.strategy)
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct PasteRule {
+    /// Compared to the application's name ignoring case; it only has to
+    /// occur somewhere in the name unless `exact` is set.
+    pub app: String,
+    #[serde(default)]
+    pub exact: bool,
+    pub strategy: PasteStrategy,
+}
+
+impl PasteRule {
+    /// `app` is the application's name in lower case.
+    fn matches(&self, app: &str) -> bool {
+        let name = self.app.to_lowercase();
+        if self.exact {
+            app == name
+        } else {
+            app.contains(&name)
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(tag = "kind", rename_all = "snake_case")]
+pub enum PasteStrategy {
+    /// Presses the keys together, e.g. `["ctrl", "v"]`.
+    KeyChord { keys: Vec<String> },
+    /// Shift+Insert, which X11 apps and most terminals accept.
+    ShiftInsert,
+    /// Types the text as key presses; for apps that ignore the clipboard.
+    /// Images and files fall back to the default chord.
+    TypeText,
+    /// Only sets the clipboard; the user pastes by hand.
+    ClipboardOnly,
+    /// Runs a program with the pasted text on stdin, e.g. `wtype -` on Wayland.
+    Command { program: String, args: Vec<String> },
+}
+
+impl Default for PasteStrategy {
+    fn default() -> Self {
+        let modifier = if cfg!(target_os = "macos") { "meta" } else { "ctrl" };
+        PasteStrategy::KeyChord {
+            keys: vec![modifier.to_string(), "v".to_string()],
+        }
+    }
+}
+
+/// Tokenizer used by the FTS5 search indexes.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum FtsTokenizer {
+    /// Indexes every 3-character window, so substrings match anywhere:
+    /// inside CJK runs (no spaces) and inside identifiers like `ClipboardManager`.
+    #[default]
+    Trigram,
+    /// SQLite's default word tokenizer. Smaller index, whole-word/prefix matches only.
+    Unicode61,
+}
+
+impl FtsTokenizer {
+    pub fn as_sql(&self) -> &'static str {
+        match self {
+            FtsTokenizer::Trigram => "trigram",
+            FtsTokenizer::Unicode61 => "unicode61",
+        }
+    }
+
+    /// Recovers the tokenizer from a `CREATE VIRTUAL TABLE` statement.
+    /// Tables created without a `tokenize` option use `unicode61`.
+    pub fn from_create_sql(sql: &str) -> Self {
+        if sql.contains("trigram") {
+            FtsTokenizer::Trigram
+        } else {
+            FtsTokenizer::Unicode61
+        }
+    }
+}
+
+impl Config {
+    pub fn load() -> Self {
+        Self::load_from(&paths::get_config_path())
+    }
+
+    pub fn load_from(path: &Path) -> Self {
+        let Ok(data) = fs::read_to_string(path) else {
+            return Self::default();
+        };
+
+        match serde_json::from_str(&data) {
+            Ok(config) => config,
+            Err(e) => {
+                log::warn!("Invalid config at {}, using defaults: {}", path.display(), e);
+                Self::default()
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_partial_config_uses_defaults() {
+        let config: Config = serde_json::from_str("{}").unwrap();
+        assert_eq!(config.search.tokenizer, FtsTokenizer::Trigram);
+
+        let config: Config = serde_json::from_str(r#"{"search": {"tokenizer": "unicode61"}}"#).unwrap();
+        assert_eq!(config.search.tokenizer, FtsTokenizer::Unicode61);
+    }
+
+    #[test]
+    fn test_tokenizer_from_sql() {
+        assert_eq!(
+            FtsTokenizer::from_create_sql("CREATE VIRTUAL TABLE history_fts USING fts5(content, tokenize = 'trigram')"),
+            FtsTokenizer::Trigram
+        );
+        assert_eq!(
+            FtsTokenizer::from_create_sql("CREATE VIRTUAL TABLE history_fts USING fts5(content)"),
+            FtsTokenizer::Unicode61
+        );
+    }
+
+    #[test]
+    fn test_paste_strategy_per_app() {
+        let config: Config = serde_json::from_str(
+            r#"{"paste": {"strategy": {"kind": "shift_insert"},
+                "rules": [{"app": "Firefox", "strategy": {"kind": "command", "program": "wtype", "args": ["-"]}}]}}"#,
+        )
+        .unwrap();
+        assert_eq!(config.paste.strategy_for(None), &PasteStrategy::ShiftInsert);
+        assert_eq!(config.paste.strategy_for(Some("gedit")), &PasteStrategy::ShiftInsert);
+        assert_eq!(
+            config.paste.strategy_for(Some("firefox-esr")),
+            &PasteStrategy::Command {
+                program: "wtype".to_string(),
+                args: vec!["-".to_string()]
+            }
+        );
+
+        // Terminals get Ctrl+Shift+V out of the box
+        let defaults = PasteConfig::default();
+        assert!(matches!(
+            defaults.strategy_for(Some("Alacritty")),
+            PasteStrategy::KeyChord { keys } if keys.len() == 3
+        ));
+        assert_eq!(defaults.strategy_for(Some("foot")), defaults.strategy_for(Some("kitty")));
+        assert_eq!(defaults.strategy_for(Some("Bigfootapp")), &PasteStrategy::default());
+    }
+}
@@ -1,34 +1,120 @@
-use crate::core::types::{Category, ClipboardItem, ClipboardType, Snippet};
-use anyhow::{Context, Result};
-use rusqlite::{params, Connection, OptionalExtension, Row};
+use crate::core::config::FtsTokenizer;
+use crate::core::fuzzy::Candidate;
+use crate::core::types::{
+    Category, ClearFilter, ClearReport, ClipboardItem, ClipboardType, HistoryCursor, HistoryEdit, HistoryPage,
+    HistoryStats, ItemStats, ItemUsage, MatchSpan, PinMove, QueueEntry, QueueOrder, QueueState, SearchExcerpt,
+    SearchHit, SearchResult, Snippet, StorageStats, Tag, TagTarget,
+};
+use anyhow::{bail, Context, Result};
+use rusqlite::{params, params_from_iter, Connection, OpenFlags, OptionalExtension, Row, ToSql, Transaction};
 use std::path::Path;
 use std::sync::{Arc, Mutex};
+use std::time::Duration;
+
+/// Columns copied between `history` and `history_trash`. A migration that
+/// adds a history column must add it to the trash table and here as well.
+const HISTORY_COLUMNS: &str = "id, type, content, content_hash, source_app, created_at, is_pinned, paste_count, \
+                               edited_at, original_content, pin_order, \
+                               first_seen_at, last_copied_at, copy_count, last_pasted_at";
+
+/// Schema changes applied after the base tables, in order. `PRAGMA user_version`
+/// records how many have run, so each one executes exactly once per database.
+const MIGRATIONS: &[&str] = &[
+    // 1: paste frequency, used by the quick-pick ranking
+    "ALTER TABLE history ADD COLUMN paste_count INTEGER NOT NULL DEFAULT 0;",
+    // 2: in-place edits keep the captured content until reverted
+    "ALTER TABLE history ADD COLUMN edited_at INTEGER;
+     ALTER TABLE history ADD COLUMN original_content TEXT;
+     ALTER TABLE history_trash ADD COLUMN edited_at INTEGER;
+     ALTER TABLE history_trash ADD COLUMN original_content TEXT;",
+    // 3: manual order of the pinned board, seeded from the old newest-first order
+    "ALTER TABLE history ADD COLUMN pin_order INTEGER;
+     ALTER TABLE history_trash ADD COLUMN pin_order INTEGER;
+     UPDATE history SET pin_order = (
+         SELECT COUNT(*) FROM history p
+         WHERE p.is_pinned AND (p.created_at, p.id) > (history.created_at, history.id)
+     ) WHERE is_pinned;",
+    // 4: usage statistics. `created_at` stays the display order and moves on
+    // every re-copy, so the first capture gets its own column.
+    "ALTER TABLE history ADD COLUMN first_seen_at INTEGER;
+     ALTER TABLE history ADD COLUMN last_copied_at INTEGER;
+     ALTER TABLE history ADD COLUMN copy_count INTEGER NOT NULL DEFAULT 1;
+     ALTER TABLE history ADD COLUMN last_pasted_at INTEGER;
+     ALTER TABLE history_trash ADD COLUMN first_seen_at INTEGER;
+     ALTER TABLE history_trash ADD COLUMN last_copied_at INTEGER;
+     ALTER TABLE history_trash ADD COLUMN copy_count INTEGER NOT NULL DEFAULT 1;
+     ALTER TABLE history_trash ADD COLUMN last_pasted_at INTEGER;
+     UPDATE history SET first_seen_at = created_at, last_copied_at = created_at;
+     CREATE INDEX IF NOT EXISTS idx_history_first_seen ON history(first_seen_at);",
+];
 
 #[derive(Clone)]
 pub struct Database {
+    /// Connection for writes and schema changes.
     conn: Arc<Mutex<Connection>>,
+    /// Read-only connection for queries. With WAL, a slow search here doesn't
+    /// block inserts on `conn`. In-memory databases share `conn`.
+    reader: Arc<Mutex<Connection>>,
+    /// Read-only connection for search and quick-pick queries, so a slow
+    /// full-text search doesn't hold up the lookups capture handling waits on.
+    searcher: Arc<Mutex<Connection>>,
+    tokenizer: FtsTokenizer,
+}
+
+/// A search box query split for the FTS index in use.
+struct TextQuery {
+    /// FTS5 MATCH expression, if any term can go through the index.
+    fts: Option<String>,
+    /// Terms too short for the trigram index, matched with LIKE instead.
+    like_terms: Vec<String>,
+}
+
+#[derive(Clone, Copy)]
+enum HitOrder<'a> {
+    Rank,
+    Display(Option<&'a HistoryCursor>),
 }
 
 impl Database {
     pub fn new(path: &Path) -> Result<Self> {
+        Self::with_tokenizer(path, FtsTokenizer::default())
+    }
+
+    pub fn with_tokenizer(path: &Path, tokenizer: FtsTokenizer) -> Result<Self> {
         let conn = Connection::open(path).context("Failed to open database")?;
 
+        // Only takes effect on a new database; lets `vacuum_step` work in small chunks
+        conn.pragma_update(None, "auto_vacuum", "INCREMENTAL")?;
         // Enable WAL mode for concurrency
         conn.pragma_update(None, "journal_mode", "WAL")?;
         conn.pragma_update(None, "foreign_keys", "ON")?;
+        conn.busy_timeout(Duration::from_secs(5))?;
 
-        let db = Self {
-            conn: Arc::new(Mutex::new(conn)),
+        let conn = Arc::new(Mutex::new(conn));
+        let mut db = Self {
+            conn: conn.clone(),
+            reader: conn.clone(),
+            searcher: conn,
+            tokenizer,
         };
-
         db.init_schema()?;
+
+        db.reader = Arc::new(Mutex::new(open_reader(path)?));
+        db.searcher = Arc::new(Mutex::new(open_reader(path)?));
         Ok(db)
     }
 
     pub fn open_in_memory() -> Result<Self> {
-        let conn = Connection::open_in_memory()?;
+        Self::open_in_memory_with_tokenizer(FtsTokenizer::default())
+    }
+
+    pub fn open_in_memory_with_tokenizer(tokenizer: FtsTokenizer) -> Result<Self> {
+        let conn = Arc::new(Mutex::new(Connection::open_in_memory()?));
         let db = Self {
-            conn: Arc::new(Mutex::new(conn)),
+            conn: conn.clone(),
+            reader: conn.clone(),
+            searcher: conn,
+            tokenizer,
         };
         db.init_schema()?;
         Ok(db)
@@ -86,13 +172,16 @@ impl Database {
             [],
         )?;
 
-        conn.execute_batch(
+        let rebuild_fts = self.drop_stale_fts(&conn)?;
+
+        conn.execute_batch(&format!(
             "
             CREATE VIRTUAL TABLE IF NOT EXISTS history_fts USING fts5(
                 content,
                 source_app,
                 content='history',
-                content_rowid='id'
+                content_rowid='id',
+                tokenize = '{tokenizer}'
             );
 
             CREATE TRIGGER IF NOT EXISTS history_ai AFTER INSERT ON history BEGIN
@@ -107,8 +196,115 @@ impl Database {
               INSERT INTO history_fts(history_fts, rowid, content, source_app) VALUES('delete', old.id, old.content, old.source_app);
               INSERT INTO history_fts(rowid, content, source_app) VALUES (new.id, new.content, new.source_app);
             END;
+            ",
+            tokenizer = self.tokenizer.as_sql(),
+        ))
+        .context("Failed to init FTS")?;
+
+        if rebuild_fts {
+            conn.execute("INSERT INTO history_fts(history_fts) VALUES('rebuild')", [])
+                .context("Failed to rebuild history index")?;
+        }
+
+        // Tags
+        conn.execute_batch(
+            "
+            CREATE TABLE IF NOT EXISTS tags (
+                id INTEGER PRIMARY KEY AUTOINCREMENT,
+                name TEXT NOT NULL UNIQUE,
+                color TEXT,
+                created_at INTEGER DEFAULT (strftime('%s', 'now'))
+            );
+
+            CREATE TABLE IF NOT EXISTS item_tags (
+                tag_id INTEGER NOT NULL,
+                item_id INTEGER NOT NULL,
+                item_type INTEGER NOT NULL,
+                PRIMARY KEY (tag_id, item_id, item_type),
+                FOREIGN KEY(tag_id) REFERENCES tags(id) ON DELETE CASCADE
+            );
+
+            CREATE INDEX IF NOT EXISTS idx_item_tags_target ON item_tags(item_id, item_type);
+            "
+        ).context("Failed to init tags")?;
+
+        // Paste queue and small persisted UI state
+        conn.execute_batch(
+            "
+            CREATE TABLE IF NOT EXISTS paste_queue (
+                id INTEGER PRIMARY KEY AUTOINCREMENT,
+                position INTEGER NOT NULL,
+                history_id INTEGER,
+                type INTEGER NOT NULL,
+                content TEXT NOT NULL,
+                FOREIGN KEY(history_id) REFERENCES history(id) ON DELETE SET NULL
+            );
+
+            CREATE TABLE IF NOT EXISTS settings (
+                key TEXT PRIMARY KEY,
+                value TEXT NOT NULL
+            );
+            "
+        ).context("Failed to init queue")?;
+
+        // Deleted history rows, kept until purged so deletes can be undone.
+        // Rows deleted together share a batch.
+        conn.execute_batch(
+            "
+            CREATE TABLE IF NOT EXISTS history_trash (
+                id INTEGER PRIMARY KEY,
+                batch INTEGER NOT NULL,
+                deleted_at INTEGER NOT NULL,
+                type INTEGER NOT NULL,
+                content TEXT NOT NULL,
+                content_hash TEXT NOT NULL,
+                source_app TEXT,
+                created_at INTEGER,
+                is_pinned INTEGER DEFAULT 0,
+                paste_count INTEGER NOT NULL DEFAULT 0
+            );
+
+            CREATE INDEX IF NOT EXISTS idx_history_trash_batch ON history_trash(batch);
+            "
+        ).context("Failed to init trash")?;
+
+        // Snippets FTS. Unlike history_fts this is a regular FTS table: masked
+        // content must never reach the index, so it keeps its own copy with the
+        // content column blanked for masked rows.
+        conn.execute_batch(&format!(
             "
-        ).context("Failed to init FTS")?;
+            CREATE VIRTUAL TABLE IF NOT EXISTS snippets_fts USING fts5(
+                title,
+                content,
+                tokenize = '{tokenizer}'
+            );
+
+            CREATE TRIGGER IF NOT EXISTS snippets_ai AFTER INSERT ON snippets BEGIN
+              INSERT INTO snippets_fts(rowid, title, content)
+              VALUES (new.id, new.title, CASE WHEN new.is_masked THEN '' ELSE new.content END);
+            END;
+
+            CREATE TRIGGER IF NOT EXISTS snippets_ad AFTER DELETE ON snippets BEGIN
+              DELETE FROM snippets_fts WHERE rowid = old.id;
+            END;
+
+            CREATE TRIGGER IF NOT EXISTS snippets_au AFTER UPDATE ON snippets BEGIN
+              DELETE FROM snippets_fts WHERE rowid = old.id;
+              INSERT INTO snippets_fts(rowid, title, content)
+              VALUES (new.id, new.title, CASE WHEN new.is_masked THEN '' ELSE new.content END);
+            END;
+
+            -- Backfill snippets created before the index existed or after a rebuild
+            INSERT INTO snippets_fts(rowid, title, content)
+            SELECT id, title, CASE WHEN is_masked THEN '' ELSE content END
+            FROM snippets
+            WHERE id NOT IN (SELECT rowid FROM snippets_fts);
+            ",
+            tokenizer = self.tokenizer.as_sql(),
+        ))
+        .context("Failed to init snippets FTS")?;
+
+        Self::migrate(&conn)?;
 
         // Initialize default categories if empty
         let count: i64 = conn.query_row("SELECT count(*) FROM categories", [], |row| row.get(0))?;
@@ -121,12 +317,53 @@ impl Database {
         Ok(())
     }
 
+    fn migrate(conn: &Connection) -> Result<()> {
+        let version: usize = conn.query_row("PRAGMA user_version", [], |row| row.get(0))?;
+
+        for (index, migration) in MIGRATIONS.iter().enumerate().skip(version) {
+            conn.execute_batch(&format!(
+                "BEGIN; {} PRAGMA user_version = {}; COMMIT;",
+                migration,
+                index + 1
+            ))
+            .with_context(|| format!("Failed to run migration {}", index + 1))?;
+        }
+        Ok(())
+    }
+
+    /// Drops the FTS tables if they were built with a different tokenizer than
+    /// the configured one. Returns true when the history index must be rebuilt;
+    /// the snippets index is refilled by the backfill in `init_schema`.
+    fn drop_stale_fts(&self, conn: &Connection) -> Result<bool> {
+        let existing: Option<String> = conn.query_row(
+            "SELECT sql FROM sqlite_master WHERE type = 'table' AND name = 'history_fts'",
+            [],
+            |row| row.get(0),
+        ).optional()?;
+
+        let Some(sql) = existing else {
+            return Ok(false);
+        };
+        if FtsTokenizer::from_create_sql(&sql) == self.tokenizer {
+            return Ok(false);
+        }
+
+        log::info!("Rebuilding search index with the {} tokenizer", self.tokenizer.as_sql());
+        conn.execute_batch(
+            "
+            DROP TABLE IF EXISTS history_fts;
+            DROP TABLE IF EXISTS snippets_fts;
+            "
+        ).context("Failed to drop stale FTS tables")?;
+        Ok(true)
+    }
+
     // --- History Operations ---
 
     pub fn get_item_by_id(&self, id: i64) -> Result<Option<ClipboardItem>> {
-        let conn = self.conn.lock().unwrap();
+        let conn = self.reader.lock().unwrap();
         let mut stmt = conn.prepare(
-            "SELECT id, type, content, content_hash, source_app, created_at, is_pinned
+            "SELECT id, type, content, content_hash, source_app, created_at, is_pinned, edited_at, pin_order
              FROM history
              WHERE id = ?"
         )?;
@@ -150,13 +387,21 @@ impl Database {
         ).optional()?;
 
         if let Some(id) = existing {
-            // Update timestamp
-            conn.execute("UPDATE history SET created_at = strftime('%s', 'now') WHERE id = ?", params![id])?;
+            // Copied again: move it up and count the copy
+            conn.execute(
+                "UPDATE history SET
+                    created_at = strftime('%s', 'now'),
+                    last_copied_at = strftime('%s', 'now'),
+                    copy_count = copy_count + 1
+                 WHERE id = ?",
+                params![id],
+            )?;
             return Ok(id);
         }
 
         conn.execute(
-            "INSERT INTO history (type, content, content_hash, source_app, is_pinned) VALUES (?, ?, ?, ?, ?)",
+            "INSERT INTO history (type, content, content_hash, source_app, is_pinned, first_seen_at, last_copied_at)
+             VALUES (?, ?, ?, ?, ?, strftime('%s', 'now'), strftime('%s', 'now'))",
             params![
                 item.type_ as i64,
                 item.content,
@@ -170,11 +415,11 @@ impl Database {
     }
 
     pub fn get_recent_history(&self, limit: usize, offset: usize) -> Result<Vec<ClipboardItem>> {
-        let conn = self.conn.lock().unwrap();
+        let conn = self.reader.lock().unwrap();
         let mut stmt = conn.prepare(
-            "SELECT id, type, content, content_hash, source_app, created_at, is_pinned
+            "SELECT id, type, content, content_hash, source_app, created_at, is_pinned, edited_at, pin_order
              FROM history
-             ORDER BY is_pinned DESC, created_at DESC
+             ORDER BY is_pinned DESC, COALESCE(pin_order, 0), created_at DESC
              LIMIT ? OFFSET ?"
         )?;
 
@@ -187,184 +432,2151 @@ impl Database {
         Ok(items)
     }
 
-    pub fn search_history(&self, query: &str) -> Result<Vec<ClipboardItem>> {
-        let conn = self.conn.lock().unwrap();
-        // Use FTS
+    /// One page of history in display order, starting after `after`.
+    pub fn get_history_page(&self, after: Option<&HistoryCursor>, limit: usize) -> Result<HistoryPage> {
+        self.get_history_page_of_type(after, limit, None)
+    }
+
+    /// Like `get_history_page`, keeping only items of `type_` when given.
+    pub fn get_history_page_of_type(
+        &self,
+        after: Option<&HistoryCursor>,
+        limit: usize,
+        type_: Option<ClipboardType>,
+    ) -> Result<HistoryPage> {
+        let conn = self.reader.lock().unwrap();
         let mut stmt = conn.prepare(
-            "SELECT id, type, content, content_hash, source_app, created_at, is_pinned
+            "SELECT id, type, content, content_hash, source_app, created_at, is_pinned, edited_at, pin_order
              FROM history
-             WHERE id IN (SELECT rowid FROM history_fts WHERE history_fts MATCH ? ORDER BY rank)
-             ORDER BY is_pinned DESC, created_at DESC
-             LIMIT 50"
+             WHERE (?1 IS NULL OR (is_pinned, -COALESCE(pin_order, 0), created_at, id) < (?2, ?5, ?3, ?1))
+               AND (?6 IS NULL OR type = ?6)
+             ORDER BY is_pinned DESC, -COALESCE(pin_order, 0) DESC, created_at DESC, id DESC
+             LIMIT ?4"
         )?;
 
-        let rows = stmt.query_map(params![query], |row| self.row_to_clipboard_item(row))?;
+        let rows = stmt.query_map(
+            params![
+                after.map(|c| c.id),
+                after.map(|c| c.is_pinned),
+                after.map(|c| c.created_at),
+                limit,
+                after.map(|c| -c.pin_order),
+                type_.map(|t| t as i64)
+            ],
+            |row| self.row_to_clipboard_item(row),
+        )?;
 
         let mut items = Vec::new();
         for row in rows {
             items.push(row?);
         }
-        Ok(items)
+        Ok(page(items, limit))
     }
 
-    pub fn delete_history(&self, id: i64) -> Result<()> {
-        let conn = self.conn.lock().unwrap();
-        conn.execute("DELETE FROM history WHERE id = ?", params![id])?;
-        Ok(())
+    pub fn search_history(&self, query: &str) -> Result<Vec<ClipboardItem>> {
+        Ok(self.search_history_page(query, None, 50)?.items)
     }
 
-    pub fn toggle_pin(&self, id: i64) -> Result<bool> {
-        let conn = self.conn.lock().unwrap();
-        let current: bool = conn.query_row("SELECT is_pinned FROM history WHERE id = ?", params![id], |row| row.get(0))?;
-        let new_state = !current;
-        conn.execute("UPDATE history SET is_pinned = ? WHERE id = ?", params![new_state, id])?;
-        Ok(new_state)
+    /// History matching `query` in display order, paged like `get_history_page`.
+    pub fn search_history_page(&self, query: &str, after: Option<&HistoryCursor>, limit: usize) -> Result<HistoryPage> {
+        let Some(query) = self.text_query(query) else {
+            return Ok(HistoryPage { items: Vec::new(), next: None });
+        };
+        let hits = self.search_history_hits(&query, HitOrder::Display(after), limit)?;
+        Ok(page(hits.into_iter().map(|(item, _, _)| item).collect(), limit))
     }
 
-    // --- Snippets Operations ---
+    /// History rows matching `query` with their score (negated bm25, 0.0 for
+    /// LIKE-only matches) and match excerpt. `order` is either relevance or the
+    /// display order, optionally continuing after a cursor.
+    fn search_history_hits(
+        &self,
+        query: &TextQuery,
+        order: HitOrder,
+        limit: usize,
+    ) -> Result<Vec<(ClipboardItem, f64, SearchExcerpt)>> {
+        let mut sql = String::from(
+            "SELECT h.id, h.type, h.content, h.content_hash, h.source_app, h.created_at, h.is_pinned, \
+             h.edited_at, h.pin_order, "
+        );
+        let mut args = Vec::new();
+        if let Some(fts) = &query.fts {
+            sql.push_str(&format!(
+                "history_fts.rank,
+                 highlight(history_fts, 0, char(2), char(3)),
+                 snippet(history_fts, 0, char(2), char(3), '…', {})
+                 FROM history_fts JOIN history h ON h.id = history_fts.rowid
+                 WHERE history_fts MATCH ?",
+                self.excerpt_tokens()
+            ));
+            args.push(fts.clone());
+        } else {
+            sql.push_str("0.0, NULL, NULL FROM history h WHERE 1");
+        }
+        for term in &query.like_terms {
+            sql.push_str(" AND h.content LIKE ? ESCAPE '\\'");
+            args.push(like_pattern(term));
+        }
+        if let HitOrder::Display(Some(cursor)) = order {
+            sql.push_str(&format!(
+                " AND (h.is_pinned, -COALESCE(h.pin_order, 0), h.created_at, h.id) < ({}, {}, {}, {})",
+                cursor.is_pinned as i64, -cursor.pin_order, cursor.created_at, cursor.id
+            ));
+        }
+        match order {
+            HitOrder::Rank if query.fts.is_some() => sql.push_str(" ORDER BY history_fts.rank"),
+            _ => sql.push_str(
+                " ORDER BY h.is_pinned DESC, -COALESCE(h.pin_order, 0) DESC, h.created_at DESC, h.id DESC",
+            ),
+        }
+        sql.push_str(&format!(" LIMIT {}", limit));
 
-    pub fn get_categories(&self) -> Result<Vec<Category>> {
-        let conn = self.conn.lock().unwrap();
-        let mut stmt = conn.prepare("SELECT id, name, icon, sort_order, is_locked FROM categories ORDER BY sort_order ASC")?;
-        let rows = stmt.query_map([], |row| {
-            Ok(Category {
-                id: Some(row.get(0)?),
-                name: row.get(1)?,
-                icon: row.get(2)?,
-                sort_order: row.get(3)?,
-                is_locked: row.get(4)?,
-            })
+        let conn = self.searcher.lock().unwrap();
+        let mut stmt = conn.prepare(&sql)?;
+        let rows = stmt.query_map(params_from_iter(args), |row| {
+            let item = self.row_to_clipboard_item(row)?;
+            let excerpt = build_excerpt(&item.content, row.get(10)?, row.get(11)?, &query.like_terms);
+            Ok((item, -row.get::<_, f64>(9)?, excerpt))
         })?;
 
-        let mut cats = Vec::new();
+        let mut hits = Vec::new();
         for row in rows {
-            cats.push(row?);
+            hits.push(row?);
         }
-        Ok(cats)
+        Ok(hits)
     }
 
-    pub fn get_snippets(&self, category_id: i64) -> Result<Vec<Snippet>> {
-        let conn = self.conn.lock().unwrap();
-        let mut stmt = conn.prepare(
-            "SELECT id, category_id, title, content, is_masked, usage_count, updated_at
-             FROM snippets
-             WHERE category_id = ?
-             ORDER BY usage_count DESC, updated_at DESC"
-        )?;
+    /// Snippet counterpart of `search_history_hits`, always ordered by relevance.
+    /// Masked contents are not indexed, and the LIKE fallback skips them too.
+    /// The excerpt is cut from the content, so masked snippets get none.
+    fn search_snippet_hits(
+        &self,
+        query: &TextQuery,
+        limit: usize,
+    ) -> Result<Vec<(Snippet, f64, Option<SearchExcerpt>)>> {
+        let mut sql = String::from(
+            "SELECT s.id, s.category_id, s.title, s.content, s.is_masked, s.usage_count, s.updated_at, "
+        );
+        let mut args = Vec::new();
+        if let Some(fts) = &query.fts {
+            sql.push_str(&format!(
+                "snippets_fts.rank,
+                 highlight(snippets_fts, 1, char(2), char(3)),
+                 snippet(snippets_fts, 1, char(2), char(3), '…', {})
+                 FROM snippets_fts JOIN snippets s ON s.id = snippets_fts.rowid
+                 WHERE snippets_fts MATCH ?",
+                self.excerpt_tokens()
+            ));
+            args.push(fts.clone());
+        } else {
+            sql.push_str("0.0, NULL, NULL FROM snippets s WHERE 1");
+        }
+        for term in &query.like_terms {
+            sql.push_str(" AND (s.title LIKE ? ESCAPE '\\' OR (s.is_masked = 0 AND s.content LIKE ? ESCAPE '\\'))");
+            args.push(like_pattern(term));
+            args.push(like_pattern(term));
+        }
+        if query.fts.is_some() {
+            sql.push_str(" ORDER BY snippets_fts.rank");
+        } else {
+            sql.push_str(" ORDER BY s.usage_count DESC, s.updated_at DESC");
+        }
+        sql.push_str(&format!(" LIMIT {}", limit));
 
-        let rows = stmt.query_map(params![category_id], |row| {
-            Ok(Snippet {
-                id: Some(row.get(0)?),
-                category_id: row.get(1)?,
-                title: row.get(2)?,
-                content: row.get(3)?,
-                is_masked: row.get(4)?,
-                usage_count: row.get(5)?,
-                updated_at: row.get(6)?,
-            })
+        let conn = self.searcher.lock().unwrap();
+        let mut stmt = conn.prepare(&sql)?;
+        let rows = stmt.query_map(params_from_iter(args), |row| {
+            let snippet = self.row_to_snippet(row)?;
+            let excerpt = match snippet.is_masked {
+                true => None,
+                // A title-only match marks nothing, leaving the head of the content
+                false => Some(build_excerpt(&snippet.content, row.get(8)?, row.get(9)?, &query.like_terms)),
+            };
+            Ok((snippet, -row.get::<_, f64>(7)?, excerpt))
         })?;
 
-        let mut snippets = Vec::new();
+        let mut hits = Vec::new();
         for row in rows {
-            snippets.push(row?);
+            hits.push(row?);
         }
-        Ok(snippets)
+        Ok(hits)
     }
 
-    pub fn add_snippet(&self, snippet: &Snippet) -> Result<i64> {
+    pub fn record_paste(&self, id: i64) -> Result<()> {
         let conn = self.conn.lock().unwrap();
         conn.execute(
-            "INSERT INTO snippets (category_id, title, content, is_masked, usage_count) VALUES (?, ?, ?, ?, ?)",
-            params![
-                snippet.category_id,
-                snippet.title,
-                snippet.content,
-                snippet.is_masked,
-                snippet.usage_count
-            ],
+            "UPDATE history SET paste_count = paste_count + 1, last_pasted_at = strftime('%s', 'now') WHERE id = ?",
+            params![id],
         )?;
-        Ok(conn.last_insert_rowid())
+        Ok(())
     }
 
-    // --- Helper ---
+    /// Copy and paste counters of one history item.
+    pub fn item_stats(&self, id: i64) -> Result<Option<ItemStats>> {
+        let conn = self.reader.lock().unwrap();
+        let stats = conn
+            .query_row(
+                "SELECT first_seen_at, last_copied_at, copy_count, paste_count, last_pasted_at
+                 FROM history WHERE id = ?",
+                params![id],
+                row_to_stats,
+            )
+            .optional()?;
+        Ok(stats)
+    }
 
-    fn row_to_clipboard_item(&self, row: &Row) -> rusqlite::Result<ClipboardItem> {
-        Ok(ClipboardItem {
-            id: Some(row.get(0)?),
-            type_: ClipboardType::from(row.get::<_, i64>(1)?),
-            content: row.get(2)?,
-            content_hash: row.get(3)?,
-            source_app: row.get(4)?,
-            created_at: row.get(5)?,
-            is_pinned: row.get(6)?,
-            tags: Vec::new(),
+    /// The items copied and pasted most, each re-copy and paste counting once;
+    /// ties go to the most recently used.
+    pub fn most_used(&self, limit: usize) -> Result<Vec<ItemUsage>> {
+        let conn = self.reader.lock().unwrap();
+        let mut stmt = conn.prepare(
+            "SELECT id, type, content, content_hash, source_app, created_at, is_pinned, edited_at, pin_order,
+                    first_seen_at, last_copied_at, copy_count, paste_count, last_pasted_at
+             FROM history
+             ORDER BY copy_count + paste_count DESC,
+                      MAX(COALESCE(last_copied_at, 0), COALESCE(last_pasted_at, 0)) DESC,
+                      id DESC
+             LIMIT ?"
+        )?;
+        let rows = stmt.query_map(params![limit], |row| {
+            Ok(ItemUsage {
+                item: self.row_to_clipboard_item(row)?,
+                stats: ItemStats {
+                    first_seen_at: row.get(9)?,
+                    last_copied_at: row.get(10)?,
+                    copy_count: row.get(11)?,
+                    paste_count: row.get(12)?,
+                    last_pasted_at: row.get(13)?,
+                },
+            })
+        })?;
+
+        let mut items = Vec::new();
+        for row in rows {
+            items.push(row?);
+        }
+        Ok(items)
+    }
+
+    /// Summary for the stats view: items first seen per local day over the
+    /// last `days` days (days without items are left out), the `top_apps`
+    /// source apps by item count, and disk usage.
+    pub fn stats(&self, days: u32, top_apps: usize) -> Result<HistoryStats> {
+        let conn = self.reader.lock().unwrap();
+        let (total_items, pinned_items, copies, pastes): (usize, usize, i64, i64) = conn.query_row(
+            "SELECT COUNT(*), COALESCE(SUM(is_pinned), 0), COALESCE(SUM(copy_count), 0), COALESCE(SUM(paste_count), 0)
+             FROM history",
+            [],
+            |row| Ok((row.get(0)?, row.get(1)?, row.get(2)?, row.get(3)?)),
+        )?;
+
+        let items_per_day = {
+            let mut stmt = conn.prepare(
+                "SELECT date(first_seen_at, 'unixepoch', 'localtime') AS day, COUNT(*)
+                 FROM history
+                 WHERE first_seen_at >= strftime('%s', 'now', 'localtime', 'start of day', ?, 'utc')
+                 GROUP BY day
+                 ORDER BY day",
+            )?;
+            let rows = stmt.query_map(params![format!("-{} days", days.saturating_sub(1))], |row| {
+                Ok((row.get(0)?, row.get(1)?))
+            })?;
+            rows.collect::<rusqlite::Result<_>>()?
+        };
+
+        let top_apps = {
+            let mut stmt = conn.prepare(
+                "SELECT source_app, COUNT(*) AS items
+                 FROM history
+                 WHERE source_app IS NOT NULL AND source_app != ''
+                 GROUP BY source_app
+                 ORDER BY items DESC, source_app
+                 LIMIT ?",
+            )?;
+            let rows = stmt.query_map(params![top_apps], |row| Ok((row.get(0)?, row.get(1)?)))?;
+            rows.collect::<rusqlite::Result<_>>()?
+        };
+
+        let database_bytes: i64 = conn.query_row(
+            "SELECT page_count * page_size FROM pragma_page_count(), pragma_page_size()",
+            [],
+            |row| row.get(0),
+        )?;
+        let images: Vec<String> = {
+            let mut stmt = conn.prepare("SELECT content FROM history WHERE type = ?")?;
+            let rows = stmt.query_map(params![ClipboardType::Image as i64], |row| row.get(0))?;
+            rows.collect::<rusqlite::Result<_>>()?
+        };
+        drop(conn);
+
+        // Missing files count as nothing rather than failing the summary
+        let image_bytes = images.iter().filter_map(|path| std::fs::metadata(path).ok()).map(|meta| meta.len()).sum();
+
+        Ok(HistoryStats {
+            total_items,
+            pinned_items,
+            copies,
+            pastes,
+            items_per_day,
+            top_apps,
+            storage: StorageStats {
+                database_bytes: database_bytes as u64,
+                image_files: images.len(),
+                image_bytes,
+            },
         })
     }
-}
 
-#[cfg(test)]
-mod tests {
-    use super::*;
+    /// Moves an item to the top of its section, as if it had just been copied again.
+    pub fn bump_history(&self, id: i64) -> Result<()> {
+        let conn = self.conn.lock().unwrap();
+        conn.execute("UPDATE history SET created_at = strftime('%s', 'now') WHERE id = ?", params![id])?;
+        Ok(())
+    }
 
-    #[test]
-    fn test_database_init() {
-        let db = Database::open_in_memory().unwrap();
-        let cats = db.get_categories().unwrap();
-        assert_eq!(cats.len(), 3);
+    /// Moves an item's capture time back, for tests that need an older item.
+    #[cfg(test)]
+    pub fn age_history(&self, id: i64, seconds: i64) -> Result<()> {
+        let conn = self.conn.lock().unwrap();
+        conn.execute("UPDATE history SET created_at = created_at - ? WHERE id = ?", params![seconds, id])?;
+        Ok(())
     }
 
-    #[test]
-    fn test_history_crud() {
-        let db = Database::open_in_memory().unwrap();
-        let item = ClipboardItem {
-            id: None,
-            type_: ClipboardType::Text,
-            content: "Hello".to_string(),
-            content_hash: "hash123".to_string(),
-            source_app: Some("test".to_string()),
-            created_at: 0,
-            is_pinned: false,
-            tags: vec![],
+    /// Candidates for the fuzzy quick-pick: the `recent` newest history items,
+    /// FTS hits for fragments of the query (so typos still reach older items),
+    /// and all snippets and tags. History use counts are re-copies, plus
+    /// pastes if `count_pastes` is set.
+    pub fn quick_pick_candidates(&self, query: &str, recent: usize, count_pastes: bool) -> Result<Vec<Candidate>> {
+        let conn = self.searcher.lock().unwrap();
+        let mut candidates = Vec::new();
+        let mut seen = std::collections::HashSet::new();
+
+        let history_candidate = |row: &Row| -> rusqlite::Result<Candidate> {
+            let item = self.row_to_clipboard_item(row)?;
+            Ok(Candidate {
+                text: item.content.clone(),
+                last_used: item.created_at,
+                is_pinned: item.is_pinned,
+                use_count: row.get(9)?,
+                hit: SearchHit::History(item),
+            })
         };
 
-        let id = db.insert_history(&item).unwrap();
-        assert!(id > 0);
+        let mut stmt = conn.prepare(
+            "SELECT id, type, content, content_hash, source_app, created_at, is_pinned, edited_at, pin_order,
+                    paste_count * ?2 + copy_count - 1
+             FROM history
+             ORDER BY is_pinned DESC, created_at DESC, id DESC
+             LIMIT ?1"
+        )?;
+        for candidate in stmt.query_map(params![recent, count_pastes], history_candidate)? {
+            let candidate = candidate?;
+            if let SearchHit::History(item) = &candidate.hit {
+                seen.insert(item.id);
+            }
+            candidates.push(candidate);
+        }
 
-        let recent = db.get_recent_history(10, 0).unwrap();
-        assert_eq!(recent.len(), 1);
-        assert_eq!(recent[0].content, "Hello");
+        if let Some(fragments) = self.fragment_query(query) {
+            let mut stmt = conn.prepare(
+                "SELECT h.id, h.type, h.content, h.content_hash, h.source_app, h.created_at, h.is_pinned,
+                        h.edited_at, h.pin_order, h.paste_count * ?3 + h.copy_count - 1
+                 FROM history_fts
+                 JOIN history h ON h.id = history_fts.rowid
+                 WHERE history_fts MATCH ?1
+                 ORDER BY history_fts.rank
+                 LIMIT ?2"
+            )?;
+            for candidate in stmt.query_map(params![fragments, recent, count_pastes], history_candidate)? {
+                let candidate = candidate?;
+                if let SearchHit::History(item) = &candidate.hit {
+                    if !seen.insert(item.id) {
+                        continue;
+                    }
+                }
+                candidates.push(candidate);
+            }
+        }
 
-        let id2 = db.insert_history(&item).unwrap();
-        assert_eq!(id, id2);
+        let mut stmt = conn.prepare(
+            "SELECT id, category_id, title, content, is_masked, usage_count, updated_at FROM snippets"
+        )?;
+        let rows = stmt.query_map([], |row| self.row_to_snippet(row))?;
+        for snippet in rows {
+            let snippet = snippet?;
+            candidates.push(Candidate {
+                text: if snippet.is_masked {
+                    snippet.title.clone()
+                } else {
+                    format!("{} {}", snippet.title, snippet.content)
+                },
+                last_used: snippet.updated_at,
+                is_pinned: false,
+                use_count: snippet.usage_count,
+                hit: SearchHit::Snippet(snippet),
+            });
+        }
 
-        let search = db.search_history("Hello").unwrap();
-        assert_eq!(search.len(), 1);
+        let mut stmt = conn.prepare("SELECT id, name, color, created_at FROM tags")?;
+        let rows = stmt.query_map([], |row| {
+            Ok(Candidate {
+                text: row.get(1)?,
+                last_used: row.get(3)?,
+                is_pinned: false,
+                use_count: 0,
+                hit: SearchHit::Tag(Tag {
+                    id: Some(row.get(0)?),
+                    name: row.get(1)?,
+                    color: row.get(2)?,
+                }),
+            })
+        })?;
+        for candidate in rows {
+            candidates.push(candidate?);
+        }
 
-        db.delete_history(id).unwrap();
-        let recent_after = db.get_recent_history(10, 0).unwrap();
-        assert_eq!(recent_after.len(), 0);
+        Ok(candidates)
     }
 
-    #[test]
-    fn test_snippet_crud() {
-        let db = Database::open_in_memory().unwrap();
-        let cats = db.get_categories().unwrap();
-        let cat_id = cats[0].id.unwrap();
+    pub fn delete_history(&self, id: i64) -> Result<()> {
+        let conn = self.conn.lock().unwrap();
+        conn.execute("DELETE FROM history WHERE id = ?", params![id])?;
+        Ok(())
+    }
 
-        let snip = Snippet {
-            id: None,
-            category_id: cat_id,
-            title: "My Snippet".to_string(),
-            content: "Secret".to_string(),
-            is_masked: true,
-            usage_count: 0,
-            updated_at: 0,
+    /// Moves history rows to the trash as one batch. Returns the batch id and
+    /// how many rows were moved; `restore_trash` brings them back.
+    pub fn trash_history(&self, ids: &[i64]) -> Result<(i64, usize)> {
+        let ids = serde_json::to_string(ids)?;
+        let mut conn = self.conn.lock().unwrap();
+        let tx = conn.transaction()?;
+        let report = trash_where(&tx, "id IN (SELECT value FROM json_each(?))", &[&ids])?;
+        tx.commit()?;
+        Ok((report.batch, report.items))
+    }
+
+    /// Bulk-clears history into a single trash batch, all or nothing. The
+    /// report counts the rows and image items cleared; their files are
+    /// deleted once the batch is purged.
+    pub fn clear_history(&self, filter: &ClearFilter) -> Result<ClearReport> {
+        let ids = match filter {
+            ClearFilter::Items(ids) => serde_json::to_string(ids)?,
+            _ => String::new(),
+        };
+        let type_ = match filter {
+            ClearFilter::Type(type_) => *type_ as i64,
+            _ => 0,
         };
 
-        let _id = db.add_snippet(&snip).unwrap();
+        let (condition, args): (&str, Vec<&dyn ToSql>) = match filter {
+            ClearFilter::All => ("1", vec![]),
+            ClearFilter::Unpinned => ("is_pinned = 0", vec![]),
+            ClearFilter::DateRange { from, to } => {
+                ("is_pinned = 0 AND created_at >= ? AND created_at < ?", vec![from, to])
+            }
+            ClearFilter::SourceApp(app) => ("is_pinned = 0 AND source_app = ?", vec![app]),
+            ClearFilter::Type(_) => ("is_pinned = 0 AND type = ?", vec![&type_]),
+            ClearFilter::Items(_) => ("is_pinned = 0 AND id IN (SELECT value FROM json_each(?))", vec![&ids]),
+        };
+
+        let mut conn = self.conn.lock().unwrap();
+        let tx = conn.transaction()?;
+        let report = trash_where(&tx, condition, &args)?;
+        tx.commit()?;
+        Ok(report)
+    }
+
+    /// Puts a trashed batch back under the original ids. Rows whose content
+    /// was copied again in the meantime stay deleted, since the newer copy
+    /// already holds their place. Returns the ids that came back.
+    pub fn restore_trash(&self, batch: i64) -> Result<Vec<i64>> {
+        let mut conn = self.conn.lock().unwrap();
+        let tx = conn.transaction()?;
+        let restored = restore_batch(&tx, batch)?;
+        tx.commit()?;
+        Ok(restored)
+    }
+
+    /// Replaces the content of a text item. The hash and search index follow
+    /// the new text, and the captured content is kept until an edit restores
+    /// it exactly. If another item already holds the new content, that item
+    /// is moved to the trash and its pin and paste count fold into this one.
+    ///
+    /// Returns `None` when the content is unchanged; otherwise the state
+    /// `revert_edit` needs to undo the edit.
+    pub fn edit_history(&self, id: i64, content: &str, content_hash: &str) -> Result<Option<HistoryEdit>> {
+        let mut conn = self.conn.lock().unwrap();
+        let tx = conn.transaction()?;
+        let (type_, previous) = tx
+            .query_row(
+                "SELECT type, content, content_hash, edited_at, is_pinned, paste_count, pin_order, copy_count,
+                        original_content
+                 FROM history WHERE id = ?",
+                params![id],
+                |row| {
+                    Ok((
+                        ClipboardType::from(row.get::<_, i64>(0)?),
+                        HistoryEdit {
+                            id,
+                            content: row.get(1)?,
+                            content_hash: row.get(2)?,
+                            edited_at: row.get(3)?,
+                            is_pinned: row.get(4)?,
+                            paste_count: row.get(5)?,
+                            pin_order: row.get(6)?,
+                            copy_count: row.get(7)?,
+                            original_content: row.get(8)?,
+                            replaced_batch: None,
+                            replaced_id: None,
+                        },
+                    ))
+                },
+            )
+            .optional()?
+            .with_context(|| format!("history item {} not found", id))?;
+        if type_ != ClipboardType::Text {
+            bail!("item {} is not text; only text items can be edited", id);
+        }
+        if previous.content == content {
+            return Ok(None);
+        }
+
+        let mut edit = previous;
+        let duplicate: Option<(i64, bool, i64, Option<i64>, i64)> = tx
+            .query_row(
+                "SELECT id, is_pinned, paste_count, pin_order, copy_count
+                 FROM history WHERE content_hash = ? AND id != ?",
+                params![content_hash, id],
+                |row| Ok((row.get(0)?, row.get(1)?, row.get(2)?, row.get(3)?, row.get(4)?)),
+            )
+            .optional()?;
+        if let Some((duplicate, is_pinned, paste_count, pin_order, copy_count)) = duplicate {
+            edit.replaced_batch = Some(trash_where(&tx, "id = ?", &[&duplicate])?.batch);
+            edit.replaced_id = Some(duplicate);
+            tx.execute(
+                "UPDATE history SET
+                    is_pinned = is_pinned OR ?1,
+                    pin_order = CASE WHEN is_pinned THEN pin_order ELSE ?2 END,
+                    paste_count = paste_count + ?3,
+                    copy_count = copy_count + ?4
+                 WHERE id = ?5",
+                params![is_pinned, pin_order, paste_count, copy_count, id],
+            )?;
+        }
+
+        // Every right-hand side sees the row as it was before the update
+        tx.execute(
+            "UPDATE history SET
+                original_content = CASE WHEN ?1 = COALESCE(original_content, content) THEN NULL
+                                        ELSE COALESCE(original_content, content) END,
+                edited_at = CASE WHEN ?1 = COALESCE(original_content, content) THEN NULL
+                                 ELSE strftime('%s', 'now') END,
+                content = ?1,
+                content_hash = ?2
+             WHERE id = ?3",
+            params![content, content_hash, id],
+        )?;
+        tx.commit()?;
+        Ok(Some(edit))
+    }
+
+    /// Undoes `edit_history`, bringing back any item the edit replaced.
+    pub fn revert_edit(&self, edit: &HistoryEdit) -> Result<()> {
+        let mut conn = self.conn.lock().unwrap();
+        let tx = conn.transaction()?;
+        tx.execute(
+            "UPDATE history SET
+                original_content = ?3,
+                content = ?1,
+                content_hash = ?2,
+                edited_at = ?4,
+                is_pinned = ?5,
+                paste_count = ?6,
+                pin_order = ?7,
+                copy_count = ?8
+             WHERE id = ?9",
+            params![
+                edit.content,
+                edit.content_hash,
+                edit.original_content,
+                edit.edited_at,
+                edit.is_pinned,
+                edit.paste_count,
+                edit.pin_order,
+                edit.copy_count,
+                edit.id
+            ],
+        )?;
+        if let Some(batch) = edit.replaced_batch {
+            restore_batch(&tx, batch)?;
+        }
+        tx.commit()?;
+        Ok(())
+    }
+
+    /// The content an edited item was captured with, `None` if it was never
+    /// edited (or was edited back).
+    pub fn original_content(&self, id: i64) -> Result<Option<String>> {
+        let conn = self.reader.lock().unwrap();
+        let original = conn
+            .query_row("SELECT original_content FROM history WHERE id = ?", params![id], |row| row.get(0))
+            .optional()?;
+        Ok(original.flatten())
+    }
+
+    /// Permanently removes trash rows deleted before `before` (unix seconds),
+    /// along with their tags and image files. Returns how many rows went.
+    pub fn purge_trash(&self, before: i64) -> Result<usize> {
+        let mut conn = self.conn.lock().unwrap();
+        let tx = conn.transaction()?;
+        let images: Vec<String> = {
+            let mut stmt = tx.prepare(
+                "SELECT content FROM history_trash
+                 WHERE deleted_at < ? AND type = ?
+                   AND content NOT IN (SELECT content FROM history WHERE type = ?)",
+            )?;
+            let rows = stmt.query_map(
+                params![before, ClipboardType::Image as i64, ClipboardType::Image as i64],
+                |row| row.get(0),
+            )?;
+            rows.collect::<rusqlite::Result<_>>()?
+        };
+        tx.execute(
+            "DELETE FROM item_tags WHERE item_type = ? AND item_id IN (SELECT id FROM history_trash WHERE deleted_at < ?)",
+            params![TagTarget::History as i64, before],
+        )?;
+        let purged = tx.execute("DELETE FROM history_trash WHERE deleted_at < ?", params![before])?;
+        tx.commit()?;
+
+        remove_image_files(&images);
+        Ok(purged)
+    }
+
+    /// Pins an item at the top of the pinned board, or unpins it. Pinning an
+    /// already pinned item keeps its place.
+    pub fn set_pinned(&self, id: i64, pinned: bool) -> Result<()> {
+        let conn = self.conn.lock().unwrap();
+        conn.execute(
+            "UPDATE history SET
+                pin_order = CASE WHEN NOT ?1 THEN NULL
+                                 WHEN is_pinned THEN pin_order
+                                 ELSE (SELECT COALESCE(MIN(pin_order), 0) - 1 FROM history WHERE is_pinned) END,
+                is_pinned = ?1
+             WHERE id = ?2",
+            params![pinned, id],
+        )?;
+        Ok(())
+    }
+
+    /// Pins an item back at a board position it had before, e.g. to undo an unpin.
+    pub fn restore_pin(&self, id: i64, pin_order: Option<i64>) -> Result<()> {
+        let conn = self.conn.lock().unwrap();
+        conn.execute(
+            "UPDATE history SET is_pinned = 1, pin_order = ? WHERE id = ?",
+            params![pin_order, id],
+        )?;
+        Ok(())
+    }
+
+    pub fn toggle_pin(&self, id: i64) -> Result<bool> {
+        let current: bool = {
+            let conn = self.conn.lock().unwrap();
+            conn.query_row("SELECT is_pinned FROM history WHERE id = ?", params![id], |row| row.get(0))?
+        };
+        let new_state = !current;
+        self.set_pinned(id, new_state)?;
+        Ok(new_state)
+    }
+
+    /// Pinned items in board order. Re-copying a pinned item leaves its place alone.
+    pub fn get_pinned_board(&self) -> Result<Vec<ClipboardItem>> {
+        let conn = self.reader.lock().unwrap();
+        let mut stmt = conn.prepare(
+            "SELECT id, type, content, content_hash, source_app, created_at, is_pinned, edited_at, pin_order
+             FROM history
+             WHERE is_pinned
+             ORDER BY COALESCE(pin_order, 0), created_at DESC, id DESC"
+        )?;
+        let rows = stmt.query_map([], |row| self.row_to_clipboard_item(row))?;
+
+        let mut items = Vec::new();
+        for row in rows {
+            items.push(row?);
+        }
+        Ok(items)
+    }
+
+    /// Moves a pinned item within the board and renumbers the board from 0.
+    /// Moves past either end stop there.
+    pub fn move_pinned(&self, id: i64, to: PinMove) -> Result<()> {
+        let mut conn = self.conn.lock().unwrap();
+        let tx = conn.transaction()?;
+        let mut ids: Vec<i64> = {
+            let mut stmt = tx.prepare(
+                "SELECT id FROM history WHERE is_pinned ORDER BY COALESCE(pin_order, 0), created_at DESC, id DESC",
+            )?;
+            let rows = stmt.query_map([], |row| row.get(0))?;
+            rows.collect::<rusqlite::Result<_>>()?
+        };
+        let Some(from) = ids.iter().position(|&pinned| pinned == id) else {
+            bail!("item {} is not pinned", id);
+        };
+
+        let last = ids.len() - 1;
+        let target = match to {
+            PinMove::Up => from.saturating_sub(1),
+            PinMove::Down => (from + 1).min(last),
+            PinMove::To(index) => index.min(last),
+        };
+        ids.remove(from);
+        ids.insert(target, id);
+        for (order, id) in ids.iter().enumerate() {
+            tx.execute("UPDATE history SET pin_order = ? WHERE id = ?", params![order as i64, id])?;
+        }
+        tx.commit()?;
+        Ok(())
+    }
+
+    /// Unified search over history content, snippet titles/contents and tag names.
+    ///
+    /// A query starting with `#` is a tag filter: it returns the matching tags
+    /// followed by the history items and snippets carrying them.
+    pub fn search_all(&self, query: &str, limit: usize) -> Result<Vec<SearchResult>> {
+        let query = query.trim();
+        if let Some(tag) = query.strip_prefix('#') {
+            return self.search_by_tag(tag.trim(), limit);
+        }
+
+        let mut results = Vec::new();
+        if let Some(text_query) = self.text_query(query) {
+            for (item, score, excerpt) in self.search_history_hits(&text_query, HitOrder::Rank, limit)? {
+                results.push(SearchResult { hit: SearchHit::History(item), score, excerpt: Some(excerpt) });
+            }
+            for (snippet, score, excerpt) in self.search_snippet_hits(&text_query, limit)? {
+                results.push(SearchResult { hit: SearchHit::Snippet(snippet), score, excerpt });
+            }
+        }
+
+        if !query.is_empty() {
+            // Tag hits score by how much of the name the query covers (1.0 = exact),
+            // so a strong content match still outranks a loose tag match.
+            for tag in self.find_tags(query, limit)? {
+                let score = query.chars().count() as f64 / tag.name.chars().count().max(1) as f64;
+                results.push(SearchResult { hit: SearchHit::Tag(tag), score, excerpt: None });
+            }
+        }
+
+        results.sort_by(|a, b| b.score.total_cmp(&a.score));
+        results.truncate(limit);
+        Ok(results)
+    }
+
+    fn search_by_tag(&self, name: &str, limit: usize) -> Result<Vec<SearchResult>> {
+        let tags = self.find_tags(name, limit)?;
+        let mut results: Vec<SearchResult> = tags
+            .into_iter()
+            .map(|tag| SearchResult { hit: SearchHit::Tag(tag), score: 1.0, excerpt: None })
+            .collect();
+
+        let conn = self.searcher.lock().unwrap();
+        let pattern = like_pattern(name);
+
+        let mut stmt = conn.prepare(
+            "SELECT DISTINCT h.id, h.type, h.content, h.content_hash, h.source_app, h.created_at, h.is_pinned,
+                    h.edited_at, h.pin_order
+             FROM history h
+             JOIN item_tags it ON it.item_id = h.id AND it.item_type = ?
+             JOIN tags t ON t.id = it.tag_id
+             WHERE t.name LIKE ? ESCAPE '\\'
+             ORDER BY h.is_pinned DESC, COALESCE(h.pin_order, 0), h.created_at DESC
+             LIMIT ?"
+        )?;
+        let rows = stmt.query_map(params![TagTarget::History as i64, pattern, limit], |row| {
+            Ok(SearchResult { hit: SearchHit::History(self.row_to_clipboard_item(row)?), score: 0.5, excerpt: None })
+        })?;
+        for row in rows {
+            results.push(row?);
+        }
+
+        let mut stmt = conn.prepare(
+            "SELECT DISTINCT s.id, s.category_id, s.title, s.content, s.is_masked, s.usage_count, s.updated_at
+             FROM snippets s
+             JOIN item_tags it ON it.item_id = s.id AND it.item_type = ?
+             JOIN tags t ON t.id = it.tag_id
+             WHERE t.name LIKE ? ESCAPE '\\'
+             ORDER BY s.usage_count DESC, s.updated_at DESC
+             LIMIT ?"
+        )?;
+        let rows = stmt.query_map(params![TagTarget::Snippet as i64, pattern, limit], |row| {
+            Ok(SearchResult { hit: SearchHit::Snippet(self.row_to_snippet(row)?), score: 0.5, excerpt: None })
+        })?;
+        for row in rows {
+            results.push(row?);
+        }
+
+        results.truncate(limit);
+        Ok(results)
+    }
+
+    // --- Tag Operations ---
+
+    /// Returns the id of the tag called `name`, creating it if needed.
+    pub fn add_tag(&self, name: &str, color: Option<&str>) -> Result<i64> {
+        let conn = self.conn.lock().unwrap();
+        conn.execute(
+            "INSERT OR IGNORE INTO tags (name, color) VALUES (?, ?)",
+            params![name, color],
+        )?;
+        let id = conn.query_row("SELECT id FROM tags WHERE name = ?", params![name], |row| row.get(0))?;
+        Ok(id)
+    }
+
+    pub fn tag_item(&self, tag_id: i64, item_id: i64, target: TagTarget) -> Result<()> {
+        let conn = self.conn.lock().unwrap();
+        conn.execute(
+            "INSERT OR IGNORE INTO item_tags (tag_id, item_id, item_type) VALUES (?, ?, ?)",
+            params![tag_id, item_id, target as i64],
+        )?;
+        Ok(())
+    }
+
+    fn find_tags(&self, name: &str, limit: usize) -> Result<Vec<Tag>> {
+        let conn = self.searcher.lock().unwrap();
+        let mut stmt = conn.prepare(
+            "SELECT id, name, color FROM tags
+             WHERE name LIKE ? ESCAPE '\\'
+             ORDER BY length(name) ASC, name ASC
+             LIMIT ?"
+        )?;
+        let rows = stmt.query_map(params![like_pattern(name), limit], |row| {
+            Ok(Tag {
+                id: Some(row.get(0)?),
+                name: row.get(1)?,
+                color: row.get(2)?,
+            })
+        })?;
+
+        let mut tags = Vec::new();
+        for row in rows {
+            tags.push(row?);
+        }
+        Ok(tags)
+    }
+
+    // --- Paste Queue ---
+
+    /// Appends an item to the end of the queue and returns the entry id.
+    pub fn enqueue(&self, item: &ClipboardItem) -> Result<i64> {
+        let conn = self.conn.lock().unwrap();
+        Self::insert_queue_entry(&conn, item)?;
+        Ok(conn.last_insert_rowid())
+    }
+
+    /// Appends several items in one transaction, keeping their order.
+    pub fn enqueue_many(&self, items: &[ClipboardItem]) -> Result<usize> {
+        let mut conn = self.conn.lock().unwrap();
+        let tx = conn.transaction()?;
+        for item in items {
+            Self::insert_queue_entry(&tx, item)?;
+        }
+        tx.commit()?;
+        Ok(items.len())
+    }
+
+    fn insert_queue_entry(conn: &Connection, item: &ClipboardItem) -> Result<()> {
+        conn.execute(
+            "INSERT INTO paste_queue (position, history_id, type, content)
+             VALUES ((SELECT COALESCE(MAX(position), 0) + 1 FROM paste_queue), ?, ?, ?)",
+            params![item.id, item.type_ as i64, item.content],
+        )?;
+        Ok(())
+    }
+
+    pub fn get_queue(&self) -> Result<Vec<QueueEntry>> {
+        let conn = self.reader.lock().unwrap();
+        let mut stmt = conn.prepare(
+            "SELECT q.id, h.id, q.type, q.content
+             FROM paste_queue q
+             LEFT JOIN history h ON h.id = q.history_id
+             ORDER BY q.position",
+        )?;
+        let rows = stmt.query_map([], |row| {
+            Ok(QueueEntry {
+                id: row.get(0)?,
+                history_id: row.get(1)?,
+                type_: ClipboardType::from(row.get::<_, i64>(2)?),
+                content: row.get(3)?,
+            })
+        })?;
+
+        let mut entries = Vec::new();
+        for row in rows {
+            entries.push(row?);
+        }
+        Ok(entries)
+    }
+
+    /// The queue along with the queue mode and order the manager saved.
+    pub fn get_queue_state(&self) -> Result<QueueState> {
+        Ok(QueueState {
+            active: self.get_setting("queue.active")?.is_some_and(|v| v == "true"),
+            order: self
+                .get_setting("queue.order")?
+                .and_then(|v| serde_json::from_str(&v).ok())
+                .unwrap_or_default(),
+            entries: self.get_queue()?,
+        })
+    }
+
+    /// Removes and returns the entry that is next for `order`. Read and
+    /// removed in one writer transaction, so two pops never get the same entry.
+    pub fn pop_queue(&self, order: QueueOrder) -> Result<Option<QueueEntry>> {
+        let direction = match order {
+            QueueOrder::Fifo => "ASC",
+            QueueOrder::Lifo => "DESC",
+        };
+        let mut conn = self.conn.lock().unwrap();
+        let tx = conn.transaction()?;
+        let entry = tx
+            .query_row(
+                &format!(
+                    "SELECT q.id, h.id, q.type, q.content
+                     FROM paste_queue q
+                     LEFT JOIN history h ON h.id = q.history_id
+                     ORDER BY q.position {} LIMIT 1",
+                    direction
+                ),
+                [],
+                |row| {
+                    Ok(QueueEntry {
+                        id: row.get(0)?,
+                        history_id: row.get(1)?,
+                        type_: ClipboardType::from(row.get::<_, i64>(2)?),
+                        content: row.get(3)?,
+                    })
+                },
+            )
+            .optional()?;
+        if let Some(entry) = &entry {
+            tx.execute("DELETE FROM paste_queue WHERE id = ?", params![entry.id])?;
+        }
+        tx.commit()?;
+        Ok(entry)
+    }
+
+    pub fn remove_queue_entry(&self, id: i64) -> Result<()> {
+        let conn = self.conn.lock().unwrap();
+        conn.execute("DELETE FROM paste_queue WHERE id = ?", params![id])?;
+        Ok(())
+    }
+
+    /// Moves an entry to `index` in the queue, clamped to the end.
+    pub fn move_queue_entry(&self, id: i64, index: usize) -> Result<()> {
+        let mut conn = self.conn.lock().unwrap();
+        let tx = conn.transaction()?;
+        let mut ids: Vec<i64> = {
+            let mut stmt = tx.prepare("SELECT id FROM paste_queue ORDER BY position")?;
+            let rows = stmt.query_map([], |row| row.get(0))?;
+            rows.collect::<rusqlite::Result<_>>()?
+        };
+        let Some(from) = ids.iter().position(|&entry| entry == id) else {
+            return Ok(());
+        };
+        ids.remove(from);
+        ids.insert(index.min(ids.len()), id);
+
+        for (position, entry) in ids.iter().enumerate() {
+            tx.execute(
+                "UPDATE paste_queue SET position = ? WHERE id = ?",
+                params![position as i64 + 1, entry],
+            )?;
+        }
+        tx.commit()?;
+        Ok(())
+    }
+
+    pub fn clear_queue(&self) -> Result<()> {
+        let conn = self.conn.lock().unwrap();
+        conn.execute("DELETE FROM paste_queue", [])?;
+        Ok(())
+    }
+
+    // --- Settings ---
+
+    pub fn get_setting(&self, key: &str) -> Result<Option<String>> {
+        let conn = self.reader.lock().unwrap();
+        Ok(conn
+            .query_row("SELECT value FROM settings WHERE key = ?", params![key], |row| row.get(0))
+            .optional()?)
+    }
+
+    pub fn set_setting(&self, key: &str, value: &str) -> Result<()> {
+        let conn = self.conn.lock().unwrap();
+        conn.execute(
+            "INSERT INTO settings (key, value) VALUES (?, ?)
+             ON CONFLICT(key) DO UPDATE SET value = excluded.value",
+            params![key, value],
+        )?;
+        Ok(())
+    }
+
+    // --- Maintenance ---
+
+    /// Returns up to `pages` free pages to the filesystem. Each step holds the
+    /// write lock only briefly; returns true while free pages remain.
+    ///
+    /// Databases created before incremental auto-vacuum was enabled have
+    /// nothing to release here.
+    pub fn vacuum_step(&self, pages: u32) -> Result<bool> {
+        let conn = self.conn.lock().unwrap();
+        let mode: i64 = conn.query_row("PRAGMA auto_vacuum", [], |row| row.get(0))?;
+        if mode != 2 {
+            return Ok(false);
+        }
+
+        conn.execute_batch(&format!("PRAGMA incremental_vacuum({})", pages))?;
+        let free: i64 = conn.query_row("PRAGMA freelist_count", [], |row| row.get(0))?;
+        Ok(free > 0)
+    }
+
+    // --- Snippets Operations ---
+
+    pub fn get_categories(&self) -> Result<Vec<Category>> {
+        let conn = self.reader.lock().unwrap();
+        let mut stmt = conn.prepare("SELECT id, name, icon, sort_order, is_locked FROM categories ORDER BY sort_order ASC")?;
+        let rows = stmt.query_map([], |row| {
+            Ok(Category {
+                id: Some(row.get(0)?),
+                name: row.get(1)?,
+                icon: row.get(2)?,
+                sort_order: row.get(3)?,
+                is_locked: row.get(4)?,
+            })
+        })?;
+
+        let mut cats = Vec::new();
+        for row in rows {
+            cats.push(row?);
+        }
+        Ok(cats)
+    }
+
+    pub fn get_snippets(&self, category_id: i64) -> Result<Vec<Snippet>> {
+        let conn = self.reader.lock().unwrap();
+        let mut stmt = conn.prepare(
+            "SELECT id, category_id, title, content, is_masked, usage_count, updated_at
+             FROM snippets
+             WHERE category_id = ?
+             ORDER BY usage_count DESC, updated_at DESC"
+        )?;
+
+        let rows = stmt.query_map(params![category_id], |row| self.row_to_snippet(row))?;
+
+        let mut snippets = Vec::new();
+        for row in rows {
+            snippets.push(row?);
+        }
+        Ok(snippets)
+    }
+
+    /// Every snippet, grouped by category in their display order.
+    pub fn get_all_snippets(&self) -> Result<Vec<Snippet>> {
+        let conn = self.reader.lock().unwrap();
+        let mut stmt = conn.prepare(
+            "SELECT s.id, s.category_id, s.title, s.content, s.is_masked, s.usage_count, s.updated_at
+             FROM snippets s
+             JOIN categories c ON c.id = s.category_id
+             ORDER BY c.sort_order, c.id, s.usage_count DESC, s.updated_at DESC"
+        )?;
+
+        let rows = stmt.query_map([], |row| self.row_to_snippet(row))?;
+
+        let mut snippets = Vec::new();
+        for row in rows {
+            snippets.push(row?);
+        }
+        Ok(snippets)
+    }
+
+    pub fn add_snippet(&self, snippet: &Snippet) -> Result<i64> {
+        let conn = self.conn.lock().unwrap();
+        conn.execute(
+            "INSERT INTO snippets (category_id, title, content, is_masked, usage_count) VALUES (?, ?, ?, ?, ?)",
+            params![
+                snippet.category_id,
+                snippet.title,
+                snippet.content,
+                snippet.is_masked,
+                snippet.usage_count
+            ],
+        )?;
+        Ok(conn.last_insert_rowid())
+    }
+
+    pub fn get_snippet_by_id(&self, id: i64) -> Result<Option<Snippet>> {
+        let conn = self.reader.lock().unwrap();
+        let snippet = conn.query_row(
+            "SELECT id, category_id, title, content, is_masked, usage_count, updated_at
+             FROM snippets
+             WHERE id = ?",
+            params![id],
+            |row| self.row_to_snippet(row),
+        ).optional()?;
+        Ok(snippet)
+    }
+
+    pub fn increment_snippet_usage(&self, id: i64) -> Result<()> {
+        let conn = self.conn.lock().unwrap();
+        conn.execute("UPDATE snippets SET usage_count = usage_count + 1 WHERE id = ?", params![id])?;
+        Ok(())
+    }
+
+    // --- Helper ---
+
+    /// Turns free text from the search box into a `TextQuery`. Every word is
+    /// quoted, so punctuation like `-` or `:` can't be parsed as FTS5 syntax.
+    fn text_query(&self, input: &str) -> Option<TextQuery> {
+        let mut fts_terms = Vec::new();
+        let mut like_terms = Vec::new();
+
+        for word in input.split_whitespace() {
+            let quoted = word.replace('"', "\"\"");
+            match self.tokenizer {
+                FtsTokenizer::Unicode61 => fts_terms.push(format!("\"{}\"*", quoted)),
+                FtsTokenizer::Trigram if word.chars().count() >= 3 => {
+                    fts_terms.push(format!("\"{}\"", quoted))
+                }
+                // Trigram indexes can't answer 1-2 character terms (e.g. most Chinese words)
+                FtsTokenizer::Trigram => like_terms.push(word.to_string()),
+            }
+        }
+
+        if fts_terms.is_empty() && like_terms.is_empty() {
+            return None;
+        }
+        Some(TextQuery {
+            fts: (!fts_terms.is_empty()).then(|| fts_terms.join(" ")),
+            like_terms,
+        })
+    }
+
+    /// Loose FTS query for typo-tolerant candidates: any trigram of any term
+    /// (or any two-letter word prefix with `unicode61`) is enough to match.
+    fn fragment_query(&self, input: &str) -> Option<String> {
+        let mut fragments = Vec::new();
+        for word in input.split_whitespace() {
+            let chars: Vec<char> = word.chars().filter(|c| *c != '"').collect();
+            match self.tokenizer {
+                FtsTokenizer::Trigram => {
+                    for window in chars.windows(3) {
+                        fragments.push(format!("\"{}\"", window.iter().collect::<String>()));
+                    }
+                }
+                FtsTokenizer::Unicode61 if chars.len() >= 2 => {
+                    fragments.push(format!("\"{}\"*", chars[..2].iter().collect::<String>()));
+                }
+                FtsTokenizer::Unicode61 => {}
+            }
+        }
+
+        if fragments.is_empty() {
+            None
+        } else {
+            Some(fragments.join(" OR "))
+        }
+    }
+
+    /// Length of `snippet()` windows in tokens. Trigram tokens are single
+    /// character positions, so they need a much larger count than words.
+    fn excerpt_tokens(&self) -> i32 {
+        match self.tokenizer {
+            FtsTokenizer::Trigram => 64,
+            FtsTokenizer::Unicode61 => 16,
+        }
+    }
+
+    fn row_to_clipboard_item(&self, row: &Row) -> rusqlite::Result<ClipboardItem> {
+        Ok(ClipboardItem {
+            id: Some(row.get(0)?),
+            type_: ClipboardType::from(row.get::<_, i64>(1)?),
+            content: row.get(2)?,
+            content_hash: row.get(3)?,
+            source_app: row.get(4)?,
+            created_at: row.get(5)?,
+            is_pinned: row.get(6)?,
+            edited_at: row.get(7)?,
+            pin_order: row.get(8)?,
+            tags: Vec::new(),
+        })
+    }
+
+    fn row_to_snippet(&self, row: &Row) -> rusqlite::Result<Snippet> {
+        Ok(Snippet {
+            id: Some(row.get(0)?),
+            category_id: row.get(1)?,
+            title: row.get(2)?,
+            content: row.get(3)?,
+            is_masked: row.get(4)?,
+            usage_count: row.get(5)?,
+            updated_at: row.get(6)?,
+        })
+    }
+}
+
+/// A read-only connection to the database at `path`; it sees the writer's
+/// commits through WAL.
+fn open_reader(path: &Path) -> Result<Connection> {
+    let reader = Connection::open_with_flags(
+        path,
+        OpenFlags::SQLITE_OPEN_READ_ONLY | OpenFlags::SQLITE_OPEN_NO_MUTEX,
+    )
+    .context("Failed to open read connection")?;
+    reader.busy_timeout(Duration::from_secs(5))?;
+    Ok(reader)
+}
+
+/// Moves the history rows matching `condition` into a new trash batch,
+/// inside the caller's transaction.
+fn trash_where(tx: &Transaction, condition: &str, args: &[&dyn ToSql]) -> Result<ClearReport> {
+    let batch: i64 = tx.query_row("SELECT COALESCE(MAX(batch), 0) + 1 FROM history_trash", [], |row| row.get(0))?;
+    let ids = tx
+        .prepare(&format!("SELECT id FROM history WHERE {}", condition))?
+        .query_map(args, |row| row.get(0))?
+        .collect::<rusqlite::Result<Vec<i64>>>()?;
+    let images: usize = tx.query_row(
+        &format!("SELECT COUNT(*) FROM history WHERE type = {} AND ({})", ClipboardType::Image as i64, condition),
+        args,
+        |row| row.get(0),
+    )?;
+
+    let mut insert_args: Vec<&dyn ToSql> = vec![&batch];
+    insert_args.extend_from_slice(args);
+    let items = tx.execute(
+        &format!(
+            "INSERT INTO history_trash (batch, deleted_at, {cols})
+             SELECT ?, strftime('%s', 'now'), {cols} FROM history WHERE {condition}",
+            cols = HISTORY_COLUMNS
+        ),
+        &*insert_args,
+    )?;
+    tx.execute(&format!("DELETE FROM history WHERE {}", condition), args)?;
+    Ok(ClearReport { batch, items, images, ids })
+}
+
+fn row_to_stats(row: &Row) -> rusqlite::Result<ItemStats> {
+    Ok(ItemStats {
+        first_seen_at: row.get(0)?,
+        last_copied_at: row.get(1)?,
+        copy_count: row.get(2)?,
+        paste_count: row.get(3)?,
+        last_pasted_at: row.get(4)?,
+    })
+}
+
+/// Moves a trash batch back into history, skipping rows whose content is
+/// there again. Returns the ids that came back.
+fn restore_batch(tx: &Transaction, batch: i64) -> Result<Vec<i64>> {
+    const RESTORABLE: &str = "t.batch = ?
+        AND NOT EXISTS (SELECT 1 FROM history h WHERE h.content_hash = t.content_hash)";
+    let restored = tx
+        .prepare(&format!("SELECT t.id FROM history_trash t WHERE {}", RESTORABLE))?
+        .query_map(params![batch], |row| row.get(0))?
+        .collect::<rusqlite::Result<Vec<i64>>>()?;
+    tx.execute(
+        &format!(
+            "INSERT INTO history ({cols}) SELECT {cols} FROM history_trash t WHERE {restorable}",
+            cols = HISTORY_COLUMNS,
+            restorable = RESTORABLE
+        ),
+        params![batch],
+    )?;
+    tx.execute("DELETE FROM history_trash WHERE batch = ?", params![batch])?;
+    Ok(restored)
+}
+
+/// Deletes image files whose history rows are gone. A missing file is not
+/// an error; other failures are logged and skipped.
+fn remove_image_files(paths: &[String]) {
+    for path in paths {
+        match std::fs::remove_file(path) {
+            Ok(()) => {}
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => {}
+            Err(e) => log::warn!("Failed to remove image {}: {}", path, e),
+        }
+    }
+}
+
+/// Wraps a page of rows; a full page may have more rows after it.
+fn page(items: Vec<ClipboardItem>, limit: usize) -> HistoryPage {
+    let next = if items.len() == limit {
+        items.last().and_then(HistoryCursor::after)
+    } else {
+        None
+    };
+    HistoryPage { items, next }
+}
+
+// Markers passed to highlight()/snippet() around matched terms.
+const MATCH_START: char = '\u{2}';
+const MATCH_END: char = '\u{3}';
+
+/// Builds the excerpt for one history or snippet hit from the FTS5 `highlight()` and
+/// `snippet()` output. Terms that went through LIKE have no FTS markers, so
+/// they are located in the content directly.
+fn build_excerpt(
+    content: &str,
+    highlighted: Option<String>,
+    snippet: Option<String>,
+    like_terms: &[String],
+) -> SearchExcerpt {
+    let mut content_matches = highlighted
+        .map(|marked| strip_markers(&marked).1)
+        .unwrap_or_default();
+    content_matches.extend(find_terms(content, like_terms));
+    content_matches.sort_by_key(|span| (span.start, span.end));
+
+    let (text, mut matches) = match snippet {
+        Some(marked) => {
+            let (text, mut matches) = strip_markers(&marked);
+            matches.extend(find_terms(&text, like_terms));
+            (text, matches)
+        }
+        None => excerpt_window(content, &content_matches),
+    };
+    matches.sort_by_key(|span| (span.start, span.end));
+
+    SearchExcerpt { text, matches, content_matches }
+}
+
+/// Removes the match markers from FTS5 output, returning the plain text and
+/// the byte spans the markers enclosed.
+fn strip_markers(marked: &str) -> (String, Vec<MatchSpan>) {
+    let mut text = String::with_capacity(marked.len());
+    let mut spans = Vec::new();
+    let mut start = None;
+
+    for c in marked.chars() {
+        match c {
+            MATCH_START => start = Some(text.len()),
+            MATCH_END => {
+                if let Some(start) = start.take() {
+                    spans.push(MatchSpan { start, end: text.len() });
+                }
+            }
+            _ => text.push(c),
+        }
+    }
+    (text, spans)
+}
+
+/// Case-insensitive (ASCII, like SQLite's LIKE) occurrences of `terms` in `text`.
+fn find_terms(text: &str, terms: &[String]) -> Vec<MatchSpan> {
+    let haystack = text.to_ascii_lowercase();
+    let mut spans = Vec::new();
+    for term in terms {
+        let needle = term.to_ascii_lowercase();
+        spans.extend(haystack.match_indices(&needle).map(|(start, m)| MatchSpan {
+            start,
+            end: start + m.len(),
+        }));
+    }
+    spans
+}
+
+/// Context window around the first match when FTS5 can't produce a snippet.
+pub(crate) fn excerpt_window(content: &str, matches: &[MatchSpan]) -> (String, Vec<MatchSpan>) {
+    const BEFORE: usize = 30;
+    const LENGTH: usize = 120;
+
+    let anchor = matches.first().map_or(0, |span| span.start);
+    let start = content[..anchor]
+        .char_indices()
+        .rev()
+        .nth(BEFORE - 1)
+        .map_or(0, |(i, _)| i);
+    let end = content[start..]
+        .char_indices()
+        .nth(LENGTH)
+        .map_or(content.len(), |(i, _)| start + i);
+
+    let mut text = String::new();
+    if start > 0 {
+        text.push('…');
+    }
+    let offset = text.len();
+    text.push_str(&content[start..end]);
+    if end < content.len() {
+        text.push('…');
+    }
+
+    let spans = matches
+        .iter()
+        .filter(|span| span.start >= start && span.end <= end)
+        .map(|span| MatchSpan {
+            start: span.start - start + offset,
+            end: span.end - start + offset,
+        })
+        .collect();
+    (text, spans)
+}
+
+/// Substring LIKE pattern with `%`, `_` and `\` escaped (use with `ESCAPE '\'`).
+fn like_pattern(input: &str) -> String {
+    let escaped = input
+        .replace('\\', "\\\\")
+        .replace('%', "\\%")
+        .replace('_', "\\_");
+    format!("%{}%", escaped)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_database_init() {
+        let db = Database::open_in_memory().unwrap();
+        let cats = db.get_categories().unwrap();
+        assert_eq!(cats.len(), 3);
+    }
+
+    #[test]
+    fn test_history_crud() {
+        let db = Database::open_in_memory().unwrap();
+        let item = ClipboardItem {
+            id: None,
+            type_: ClipboardType::Text,
+            content: "Hello".to_string(),
+            content_hash: "hash123".to_string(),
+            source_app: Some("test".to_string()),
+            created_at: 0,
+            is_pinned: false,
+            edited_at: None,
+            pin_order: None,
+            tags: vec![],
+        };
+
+        let id = db.insert_history(&item).unwrap();
+        assert!(id > 0);
+
+        let recent = db.get_recent_history(10, 0).unwrap();
+        assert_eq!(recent.len(), 1);
+        assert_eq!(recent[0].content, "Hello");
+
+        let id2 = db.insert_history(&item).unwrap();
+        assert_eq!(id, id2);
+
+        let search = db.search_history("Hello").unwrap();
+        assert_eq!(search.len(), 1);
+
+        db.delete_history(id).unwrap();
+        let recent_after = db.get_recent_history(10, 0).unwrap();
+        assert_eq!(recent_after.len(), 0);
+    }
+
+    #[test]
+    fn test_snippet_crud() {
+        let db = Database::open_in_memory().unwrap();
+        let cats = db.get_categories().unwrap();
+        let cat_id = cats[0].id.unwrap();
+
+        let snip = Snippet {
+            id: None,
+            category_id: cat_id,
+            title: "My Snippet".to_string(),
+            content: "Secret".to_string(),
+            is_masked: true,
+            usage_count: 0,
+            updated_at: 0,
+        };
+
+        let _id = db.add_snippet(&snip).unwrap();
 
         let snippets = db.get_snippets(cat_id).unwrap();
         assert_eq!(snippets.len(), 1);
         assert_eq!(snippets[0].title, "My Snippet");
+
+        let code = Snippet { category_id: cats[2].id.unwrap(), title: "Code".to_string(), ..snip };
+        db.add_snippet(&code).unwrap();
+        let titles: Vec<String> = db.get_all_snippets().unwrap().into_iter().map(|s| s.title).collect();
+        assert_eq!(titles, vec!["My Snippet", "Code"]);
+    }
+
+    fn text_item(content: &str) -> ClipboardItem {
+        ClipboardItem {
+            id: None,
+            type_: ClipboardType::Text,
+            content: content.to_string(),
+            content_hash: format!("hash-{}", content),
+            source_app: None,
+            created_at: 0,
+            is_pinned: false,
+            edited_at: None,
+            pin_order: None,
+            tags: vec![],
+        }
+    }
+
+    fn snippet(category_id: i64, title: &str, content: &str, is_masked: bool) -> Snippet {
+        Snippet {
+            id: None,
+            category_id,
+            title: title.to_string(),
+            content: content.to_string(),
+            is_masked,
+            usage_count: 0,
+            updated_at: 0,
+        }
+    }
+
+    #[test]
+    fn test_search_all_sources() {
+        let db = Database::open_in_memory().unwrap();
+        let cat_id = db.get_categories().unwrap()[0].id.unwrap();
+
+        db.insert_history(&text_item("deploy the server tonight")).unwrap();
+        db.add_snippet(&snippet(cat_id, "Server IP", "10.0.0.1", false)).unwrap();
+        db.add_snippet(&snippet(cat_id, "Wifi", "the server room password", false)).unwrap();
+        db.add_tag("servers", None).unwrap();
+
+        let results = db.search_all("server", 20).unwrap();
+        let history = results.iter().filter(|r| matches!(r.hit, SearchHit::History(_))).count();
+        let snippets = results.iter().filter(|r| matches!(r.hit, SearchHit::Snippet(_))).count();
+        let tags = results.iter().filter(|r| matches!(r.hit, SearchHit::Tag(_))).count();
+        assert_eq!((history, snippets, tags), (1, 2, 1));
+
+        for pair in results.windows(2) {
+            assert!(pair[0].score >= pair[1].score);
+        }
+
+        // Snippets are excerpted from their content like history items
+        let excerpt = |title: &str| {
+            results
+                .iter()
+                .find(|r| matches!(&r.hit, SearchHit::Snippet(s) if s.title == title))
+                .and_then(|r| r.excerpt.clone())
+                .unwrap()
+        };
+        let wifi = excerpt("Wifi");
+        assert_eq!(wifi.text, "the server room password");
+        let span = wifi.matches[0];
+        assert_eq!(&wifi.text[span.start..span.end], "server");
+        // Matched on the title only: the content is shown with nothing marked
+        assert!(excerpt("Server IP").matches.is_empty());
+    }
+
+    #[test]
+    fn test_search_all_skips_masked_content() {
+        let db = Database::open_in_memory().unwrap();
+        let cat_id = db.get_categories().unwrap()[1].id.unwrap();
+        db.add_snippet(&snippet(cat_id, "Bank PIN", "hunter2", true)).unwrap();
+
+        assert!(db.search_all("hunter2", 20).unwrap().is_empty());
+
+        let by_title = db.search_all("bank", 20).unwrap();
+        assert_eq!(by_title.len(), 1);
+        assert!(matches!(by_title[0].hit, SearchHit::Snippet(_)));
+        assert!(by_title[0].excerpt.is_none());
+    }
+
+    #[test]
+    fn test_search_all_tag_filter() {
+        let db = Database::open_in_memory().unwrap();
+        let cat_id = db.get_categories().unwrap()[0].id.unwrap();
+
+        let tagged = db.insert_history(&text_item("call the dentist")).unwrap();
+        db.insert_history(&text_item("untagged note")).unwrap();
+        let snip = db.add_snippet(&snippet(cat_id, "Address", "Main St 1", false)).unwrap();
+
+        let tag = db.add_tag("todo", Some("#ff0000")).unwrap();
+        assert_eq!(db.add_tag("todo", None).unwrap(), tag);
+        db.tag_item(tag, tagged, TagTarget::History).unwrap();
+        db.tag_item(tag, snip, TagTarget::Snippet).unwrap();
+
+        let results = db.search_all("#todo", 20).unwrap();
+        assert_eq!(results.len(), 3);
+        assert!(matches!(&results[0].hit, SearchHit::Tag(t) if t.name == "todo"));
+        assert!(results.iter().any(|r| matches!(&r.hit, SearchHit::History(h) if h.id == Some(tagged))));
+        assert!(results.iter().any(|r| matches!(&r.hit, SearchHit::Snippet(s) if s.id == Some(snip))));
+    }
+
+    #[test]
+    fn test_search_punctuation_is_literal() {
+        let db = Database::open_in_memory().unwrap();
+        db.insert_history(&text_item("docker-compose up")).unwrap();
+
+        assert_eq!(db.search_history("docker-compose").unwrap().len(), 1);
+        assert!(db.search_all("\"unbalanced", 20).is_ok());
+        assert!(db.search_all("   ", 20).unwrap().is_empty());
+    }
+
+    #[test]
+    fn test_trigram_matches_cjk_substrings() {
+        let db = Database::open_in_memory().unwrap();
+        db.insert_history(&text_item("这是一个智能剪贴板管理工具")).unwrap();
+        db.insert_history(&text_item("快捷短语")).unwrap();
+
+        let found = db.search_history("剪贴板管理").unwrap();
+        assert_eq!(found.len(), 1);
+        assert_eq!(found[0].content, "这是一个智能剪贴板管理工具");
+
+        // Two-character words are below the trigram window and go through LIKE
+        assert_eq!(db.search_history("剪贴").unwrap().len(), 1);
+        assert_eq!(db.search_history("短语").unwrap().len(), 1);
+        assert!(db.search_history("翻译").unwrap().is_empty());
+    }
+
+    #[test]
+    fn test_trigram_matches_mid_word_identifiers() {
+        let db = Database::open_in_memory().unwrap();
+        db.insert_history(&text_item("let manager = ClipboardManager::new();")).unwrap();
+        db.insert_history(&text_item("fn get_recent_history(limit: usize)")).unwrap();
+
+        assert_eq!(db.search_history("Manager").unwrap().len(), 1);
+        assert_eq!(db.search_history("board").unwrap().len(), 1);
+        assert_eq!(db.search_history("recent_hist").unwrap().len(), 1);
+
+        let results = db.search_all("Manager", 20).unwrap();
+        assert_eq!(results.len(), 1);
+    }
+
+    #[test]
+    fn test_unicode61_tokenizer_is_word_based() {
+        let db = Database::open_in_memory_with_tokenizer(FtsTokenizer::Unicode61).unwrap();
+        db.insert_history(&text_item("ClipboardManager started")).unwrap();
+
+        assert_eq!(db.search_history("Clip").unwrap().len(), 1);
+        assert!(db.search_history("Manager").unwrap().is_empty());
+    }
+
+    #[test]
+    fn test_tokenizer_change_rebuilds_index() {
+        let path = std::env::temp_dir().join(format!("picaclip_fts_{}.db", std::process::id()));
+        let _ = std::fs::remove_file(&path);
+
+        {
+            let db = Database::with_tokenizer(&path, FtsTokenizer::Unicode61).unwrap();
+            let cat_id = db.get_categories().unwrap()[0].id.unwrap();
+            db.insert_history(&text_item("ClipboardManager started")).unwrap();
+            db.add_snippet(&snippet(cat_id, "Greeting", "你好世界朋友", false)).unwrap();
+            assert!(db.search_history("Manager").unwrap().is_empty());
+        }
+
+        let db = Database::with_tokenizer(&path, FtsTokenizer::Trigram).unwrap();
+        assert_eq!(db.search_history("Manager").unwrap().len(), 1);
+        assert_eq!(db.search_all("世界朋", 20).unwrap().len(), 1);
+
+        drop(db);
+        for suffix in ["", "-wal", "-shm"] {
+            let _ = std::fs::remove_file(format!("{}{}", path.display(), suffix));
+        }
+    }
+
+    fn history_excerpt(results: &[SearchResult]) -> &SearchExcerpt {
+        results
+            .iter()
+            .find(|r| matches!(r.hit, SearchHit::History(_)))
+            .and_then(|r| r.excerpt.as_ref())
+            .expect("history hit with excerpt")
+    }
+
+    #[test]
+    fn test_search_excerpt_for_long_content() {
+        let db = Database::open_in_memory().unwrap();
+        let log = format!(
+            "{}ERROR ClipboardManager failed at line 42\n{}",
+            "INFO polling clipboard\n".repeat(300),
+            "INFO shutting down\n".repeat(300)
+        );
+        db.insert_history(&text_item(&log)).unwrap();
+
+        let results = db.search_all("Manager", 20).unwrap();
+        let excerpt = history_excerpt(&results);
+
+        assert!(excerpt.text.len() < 200, "excerpt too long: {}", excerpt.text.len());
+        assert!(excerpt.text.starts_with('…') && excerpt.text.ends_with('…'));
+        assert_eq!(excerpt.matches.len(), 1);
+        let span = excerpt.matches[0];
+        assert_eq!(&excerpt.text[span.start..span.end], "Manager");
+
+        assert_eq!(excerpt.content_matches.len(), 1);
+        let span = excerpt.content_matches[0];
+        assert_eq!(&log[span.start..span.end], "Manager");
+    }
+
+    #[test]
+    fn test_search_excerpt_marks_every_term() {
+        let db = Database::open_in_memory_with_tokenizer(FtsTokenizer::Unicode61).unwrap();
+        let content = "docker compose up, then docker ps";
+        db.insert_history(&text_item(content)).unwrap();
+
+        let results = db.search_all("docker", 20).unwrap();
+        let excerpt = history_excerpt(&results);
+        assert_eq!(excerpt.text, content);
+        let found: Vec<&str> = excerpt.content_matches.iter().map(|m| &content[m.start..m.end]).collect();
+        assert_eq!(found, vec!["docker", "docker"]);
+    }
+
+    #[test]
+    fn test_search_excerpt_for_short_terms() {
+        let db = Database::open_in_memory().unwrap();
+        let content = format!("{}剪贴板{}", "前".repeat(100), "后".repeat(200));
+        db.insert_history(&text_item(&content)).unwrap();
+
+        // "剪贴" is below the trigram window, so the excerpt is cut in Rust
+        let results = db.search_all("剪贴", 20).unwrap();
+        let excerpt = history_excerpt(&results);
+        assert!(excerpt.text.starts_with('…') && excerpt.text.ends_with('…'));
+        assert_eq!(excerpt.matches.len(), 1);
+        let span = excerpt.matches[0];
+        assert_eq!(&excerpt.text[span.start..span.end], "剪贴");
+        let span = excerpt.content_matches[0];
+        assert_eq!(&content[span.start..span.end], "剪贴");
+    }
+
+    #[test]
+    fn test_migrations_run_once() {
+        let db = Database::open_in_memory().unwrap();
+        let conn = db.conn.lock().unwrap();
+        let version: usize = conn.query_row("PRAGMA user_version", [], |row| row.get(0)).unwrap();
+        assert_eq!(version, MIGRATIONS.len());
+        Database::migrate(&conn).unwrap();
+    }
+
+    #[test]
+    fn test_quick_pick_candidates_reach_old_items() {
+        let db = Database::open_in_memory().unwrap();
+        let old = db.insert_history(&text_item("docker compose up -d")).unwrap();
+        for i in 0..5 {
+            db.insert_history(&text_item(&format!("note {}", i))).unwrap();
+        }
+        db.record_paste(old).unwrap();
+        db.record_paste(old).unwrap();
+
+        // Only 2 recent items fit, but a fragment of "dockr" still hits the index
+        let candidates = db.quick_pick_candidates("dockr", 2, true).unwrap();
+        let history: Vec<&Candidate> = candidates
+            .iter()
+            .filter(|c| matches!(c.hit, SearchHit::History(_)))
+            .collect();
+        assert_eq!(history.len(), 3);
+
+        let docker = history.iter().find(|c| c.text.starts_with("docker")).unwrap();
+        assert_eq!(docker.use_count, 2);
+        let candidates = db.quick_pick_candidates("dockr", 2, false).unwrap();
+        assert!(candidates.iter().all(|c| c.use_count == 0));
+    }
+
+    #[test]
+    fn test_history_pages_cover_everything_once() {
+        let db = Database::open_in_memory().unwrap();
+        for i in 0..7 {
+            db.insert_history(&text_item(&format!("item {}", i))).unwrap();
+        }
+        let pinned = db.insert_history(&text_item("pinned")).unwrap();
+        db.toggle_pin(pinned).unwrap();
+
+        let mut seen = Vec::new();
+        let mut cursor = None;
+        loop {
+            let page = db.get_history_page(cursor.as_ref(), 3).unwrap();
+            seen.extend(page.items.iter().map(|item| item.content.clone()));
+            match page.next {
+                Some(next) => cursor = Some(next),
+                None => break,
+            }
+        }
+
+        assert_eq!(seen.len(), 8);
+        assert_eq!(seen[0], "pinned");
+        assert_eq!(seen[1], "item 6");
+        assert_eq!(seen[7], "item 0");
+
+        let mut image = text_item("/tmp/image.png");
+        image.type_ = ClipboardType::Image;
+        db.insert_history(&image).unwrap();
+        let images = db.get_history_page_of_type(None, 3, Some(ClipboardType::Image)).unwrap();
+        assert_eq!(images.items.len(), 1);
+        assert_eq!(images.items[0].content, "/tmp/image.png");
+        assert!(images.next.is_none());
+    }
+
+    #[test]
+    fn test_history_pages_stable_under_inserts() {
+        let db = Database::open_in_memory().unwrap();
+        for i in 0..6 {
+            db.insert_history(&text_item(&format!("item {}", i))).unwrap();
+        }
+
+        let first = db.get_history_page(None, 3).unwrap();
+        let contents: Vec<&str> = first.items.iter().map(|i| i.content.as_str()).collect();
+        assert_eq!(contents, vec!["item 5", "item 4", "item 3"]);
+
+        // New clipboard items arrive while the user is scrolled down
+        db.insert_history(&text_item("new 1")).unwrap();
+        db.insert_history(&text_item("new 2")).unwrap();
+
+        let second = db.get_history_page(first.next.as_ref(), 3).unwrap();
+        let contents: Vec<&str> = second.items.iter().map(|i| i.content.as_str()).collect();
+        assert_eq!(contents, vec!["item 2", "item 1", "item 0"]);
+    }
+
+    #[test]
+    fn test_search_history_pages() {
+        let db = Database::open_in_memory().unwrap();
+        for i in 0..60 {
+            db.insert_history(&text_item(&format!("log line {}", i))).unwrap();
+        }
+        db.insert_history(&text_item("unrelated")).unwrap();
+
+        let first = db.search_history_page("log line", None, 50).unwrap();
+        assert_eq!(first.items.len(), 50);
+        let rest = db.search_history_page("log line", first.next.as_ref(), 50).unwrap();
+        assert_eq!(rest.items.len(), 10);
+        assert!(rest.next.is_none());
+    }
+
+    #[test]
+    fn test_reader_sees_committed_writes() {
+        let path = std::env::temp_dir().join(format!("picaclip_reader_{}.db", std::process::id()));
+        let _ = std::fs::remove_file(&path);
+
+        let db = Database::new(&path).unwrap();
+        let id = db.insert_history(&text_item("written then read")).unwrap();
+        assert_eq!(db.get_item_by_id(id).unwrap().unwrap().content, "written then read");
+        assert_eq!(db.search_history("then read").unwrap().len(), 1);
+        // A search in progress leaves the reader free for lookups
+        let searching = db.searcher.lock().unwrap();
+        assert!(db.reader.try_lock().is_ok());
+        drop(searching);
+
+        db.delete_history(id).unwrap();
+        assert!(db.get_item_by_id(id).unwrap().is_none());
+        while db.vacuum_step(16).unwrap() {}
+
+        drop(db);
+        for suffix in ["", "-wal", "-shm"] {
+            let _ = std::fs::remove_file(format!("{}{}", path.display(), suffix));
+        }
+    }
+
+    #[test]
+    fn test_bump_history_moves_item_to_top() {
+        let db = Database::open_in_memory().unwrap();
+        let old = db.insert_history(&text_item("old")).unwrap();
+        db.insert_history(&text_item("new")).unwrap();
+        db.age_history(old, 60).unwrap();
+        assert_eq!(db.get_recent_history(1, 0).unwrap()[0].content, "new");
+
+        db.bump_history(old).unwrap();
+        assert_eq!(db.get_recent_history(1, 0).unwrap()[0].content, "old");
+    }
+
+    #[test]
+    fn test_queue_order_and_reorder() {
+        let db = Database::open_in_memory().unwrap();
+        let mut item = text_item("first");
+        item.id = Some(db.insert_history(&item).unwrap());
+        let first = db.enqueue(&item).unwrap();
+        let second = db.enqueue(&text_item("second")).unwrap();
+        let third = db.enqueue(&text_item("third")).unwrap();
+
+        let contents = |db: &Database| -> Vec<String> {
+            db.get_queue().unwrap().into_iter().map(|e| e.content).collect()
+        };
+        assert_eq!(contents(&db), ["first", "second", "third"]);
+        assert_eq!(db.get_queue().unwrap()[0].history_id, item.id);
+
+        db.move_queue_entry(first, 99).unwrap();
+        assert_eq!(contents(&db), ["second", "third", "first"]);
+        db.move_queue_entry(first, 0).unwrap();
+        db.remove_queue_entry(second).unwrap();
+        assert_eq!(contents(&db), ["first", "third"]);
+
+        assert_eq!(db.pop_queue(QueueOrder::Lifo).unwrap().unwrap().id, third);
+        assert_eq!(db.pop_queue(QueueOrder::Fifo).unwrap().unwrap().id, first);
+        assert!(db.pop_queue(QueueOrder::Fifo).unwrap().is_none());
+    }
+
+    #[test]
+    fn test_concurrent_pops_take_distinct_entries() {
+        let path = std::env::temp_dir().join(format!("picaclip_pop_{}.db", std::process::id()));
+        let _ = std::fs::remove_file(&path);
+
+        let db = Database::new(&path).unwrap();
+        let items: Vec<ClipboardItem> = (0..40).map(|i| text_item(&format!("entry {}", i))).collect();
+        db.enqueue_many(&items).unwrap();
+        let poppers: Vec<_> = (0..4)
+            .map(|_| {
+                let db = db.clone();
+                std::thread::spawn(move || {
+                    std::iter::from_fn(|| db.pop_queue(QueueOrder::Fifo).unwrap()).map(|e| e.id).collect::<Vec<_>>()
+                })
+            })
+            .collect();
+        let mut popped: Vec<i64> = poppers.into_iter().flat_map(|popper| popper.join().unwrap()).collect();
+        popped.sort();
+        popped.dedup();
+        assert_eq!(popped.len(), 40);
+
+        drop(db);
+        for suffix in ["", "-wal", "-shm"] {
+            let _ = std::fs::remove_file(format!("{}{}", path.display(), suffix));
+        }
+    }
+
+    #[test]
+    fn test_enqueue_many_appends_in_order() {
+        let db = Database::open_in_memory().unwrap();
+        db.enqueue(&text_item("existing")).unwrap();
+        let items = [text_item("x"), text_item("y")];
+        assert_eq!(db.enqueue_many(&items).unwrap(), 2);
+
+        let contents: Vec<String> = db.get_queue().unwrap().into_iter().map(|e| e.content).collect();
+        assert_eq!(contents, ["existing", "x", "y"]);
+    }
+
+    #[test]
+    fn test_queue_survives_history_delete() {
+        let db = Database::open_in_memory().unwrap();
+        let mut item = text_item("kept in queue");
+        item.id = Some(db.insert_history(&item).unwrap());
+        db.enqueue(&item).unwrap();
+        db.delete_history(item.id.unwrap()).unwrap();
+
+        let queue = db.get_queue().unwrap();
+        assert_eq!(queue[0].content, "kept in queue");
+        assert_eq!(queue[0].history_id, None);
+    }
+
+    #[test]
+    fn test_settings_round_trip() {
+        let db = Database::open_in_memory().unwrap();
+        assert_eq!(db.get_setting("queue.order").unwrap(), None);
+        db.set_setting("queue.order", "lifo").unwrap();
+        db.set_setting("queue.order", "fifo").unwrap();
+        assert_eq!(db.get_setting("queue.order").unwrap().as_deref(), Some("fifo"));
+    }
+
+    #[test]
+    fn test_trash_restore_and_purge() {
+        let db = Database::open_in_memory().unwrap();
+        let kept = db.insert_history(&text_item("kept")).unwrap();
+        let deleted = db.insert_history(&text_item("deleted")).unwrap();
+        let recopied = db.insert_history(&text_item("recopied")).unwrap();
+        db.toggle_pin(deleted).unwrap();
+        db.record_paste(deleted).unwrap();
+
+        let (batch, moved) = db.trash_history(&[deleted, recopied, 999]).unwrap();
+        assert_eq!(moved, 2);
+        assert!(db.get_item_by_id(deleted).unwrap().is_none());
+        assert!(db.search_history("deleted").unwrap().is_empty());
+
+        // The same content copied again while in the trash wins over the restore
+        db.insert_history(&text_item("recopied")).unwrap();
+        assert_eq!(db.restore_trash(batch).unwrap(), vec![deleted]);
+        let restored = db.get_item_by_id(deleted).unwrap().unwrap();
+        assert!(restored.is_pinned);
+        assert_eq!(db.search_history("deleted").unwrap().len(), 1);
+        assert_eq!(db.get_recent_history(10, 0).unwrap().len(), 3);
+        assert!(db.get_item_by_id(kept).unwrap().is_some());
+        assert!(db.restore_trash(batch).unwrap().is_empty());
+    }
+
+    #[test]
+    fn test_purge_trash_removes_image_files() {
+        let db = Database::open_in_memory().unwrap();
+        let path = std::env::temp_dir().join(format!("picaclip_trash_{}.png", std::process::id()));
+        std::fs::write(&path, b"png").unwrap();
+        let image = ClipboardItem {
+            type_: ClipboardType::Image,
+            ..text_item(&path.to_string_lossy())
+        };
+        let id = db.insert_history(&image).unwrap();
+        let tag = db.add_tag("shot", None).unwrap();
+        db.tag_item(tag, id, TagTarget::History).unwrap();
+
+        db.trash_history(&[id]).unwrap();
+        assert_eq!(db.purge_trash(0).unwrap(), 0);
+        assert!(path.exists());
+
+        assert_eq!(db.purge_trash(i64::MAX).unwrap(), 1);
+        assert!(!path.exists());
+        assert!(db.search_all("#shot", 10).unwrap().iter().all(|r| !matches!(r.hit, SearchHit::History(_))));
+    }
+
+    #[test]
+    fn test_clear_history_filters() {
+        let db = Database::open_in_memory().unwrap();
+        let insert = |content: &str, app: &str, created_at: i64, type_: ClipboardType| {
+            let id = db
+                .insert_history(&ClipboardItem {
+                    type_,
+                    source_app: Some(app.to_string()),
+                    ..text_item(content)
+                })
+                .unwrap();
+            let conn = db.conn.lock().unwrap();
+            conn.execute("UPDATE history SET created_at = ? WHERE id = ?", params![created_at, id]).unwrap();
+            id
+        };
+        let pinned = insert("pinned note", "editor", 10, ClipboardType::Text);
+        db.toggle_pin(pinned).unwrap();
+        insert("old note", "editor", 10, ClipboardType::Text);
+        insert("terminal output", "terminal", 20, ClipboardType::Text);
+        insert("/tmp/shot.png", "viewer", 30, ClipboardType::Image);
+        insert("new note", "editor", 40, ClipboardType::Text);
+        let remaining = |db: &Database| db.get_recent_history(10, 0).unwrap().len();
+
+        let report = db.clear_history(&ClearFilter::DateRange { from: 0, to: 20 }).unwrap();
+        assert_eq!((report.items, report.images), (1, 0));
+        let report = db.clear_history(&ClearFilter::Type(ClipboardType::Image)).unwrap();
+        assert_eq!((report.items, report.images), (1, 1));
+        assert_eq!(db.clear_history(&ClearFilter::SourceApp("terminal".to_string())).unwrap().items, 1);
+        let notes = db.search_history("note").unwrap().into_iter().filter_map(|item| item.id).collect();
+        assert_eq!(db.clear_history(&ClearFilter::Items(notes)).unwrap().items, 1);
+        assert_eq!(db.clear_history(&ClearFilter::Items(Vec::new())).unwrap().items, 0);
+        assert_eq!(remaining(&db), 1);
+        assert_eq!(db.clear_history(&ClearFilter::Unpinned).unwrap().items, 0);
+
+        let report = db.clear_history(&ClearFilter::All).unwrap();
+        assert_eq!((report.items, report.ids.clone()), (1, vec![pinned]));
+        assert_eq!(remaining(&db), 0);
+        assert_eq!(db.restore_trash(report.batch).unwrap(), vec![pinned]);
+        assert!(db.get_item_by_id(pinned).unwrap().unwrap().is_pinned);
+    }
+
+    #[test]
+    fn test_edit_history_keeps_original_and_reindexes() {
+        let db = Database::open_in_memory().unwrap();
+        let id = db.insert_history(&text_item("first draft")).unwrap();
+
+        let edit = db.edit_history(id, "second version", "hash-second version").unwrap().unwrap();
+        assert_eq!(edit.content, "first draft");
+        let item = db.get_item_by_id(id).unwrap().unwrap();
+        assert_eq!(item.content_hash, "hash-second version");
+        assert!(item.edited_at.is_some());
+        assert_eq!(db.original_content(id).unwrap().as_deref(), Some("first draft"));
+        assert!(db.search_history("draft").unwrap().is_empty());
+        assert_eq!(db.search_history("version").unwrap().len(), 1);
+
+        // Copying the new text again bumps the edited row instead of adding one
+        assert_eq!(db.insert_history(&text_item("second version")).unwrap(), id);
+        assert!(db.edit_history(id, "second version", "hash-second version").unwrap().is_none());
+
+        // A second edit still remembers the captured text; editing back to it clears the edit
+        db.edit_history(id, "third", "hash-third").unwrap();
+        assert_eq!(db.original_content(id).unwrap().as_deref(), Some("first draft"));
+        db.edit_history(id, "first draft", "hash-first draft").unwrap();
+        assert_eq!(db.original_content(id).unwrap(), None);
+        assert_eq!(db.get_item_by_id(id).unwrap().unwrap().edited_at, None);
+
+        assert!(db.edit_history(999, "x", "hash-x").is_err());
+    }
+
+    #[test]
+    fn test_edit_history_merges_duplicate_and_reverts() {
+        let db = Database::open_in_memory().unwrap();
+        let edited = db.insert_history(&text_item("typo")).unwrap();
+        let duplicate = db.insert_history(&text_item("fixed")).unwrap();
+        db.toggle_pin(duplicate).unwrap();
+        db.record_paste(duplicate).unwrap();
+
+        let edit = db.edit_history(edited, "fixed", "hash-fixed").unwrap().unwrap();
+        assert!(edit.replaced_batch.is_some());
+        assert_eq!(edit.replaced_id, Some(duplicate));
+        assert!(db.get_item_by_id(duplicate).unwrap().is_none());
+        let merged = db.get_item_by_id(edited).unwrap().unwrap();
+        assert!(merged.is_pinned);
+        assert_eq!(db.search_history("fixed").unwrap().len(), 1);
+
+        db.revert_edit(&edit).unwrap();
+        let reverted = db.get_item_by_id(edited).unwrap().unwrap();
+        assert_eq!((reverted.content.as_str(), reverted.is_pinned, reverted.edited_at), ("typo", false, None));
+        assert_eq!(db.original_content(edited).unwrap(), None);
+        assert!(db.get_item_by_id(duplicate).unwrap().unwrap().is_pinned);
+    }
+
+    #[test]
+    fn test_pinned_board_order() {
+        let db = Database::open_in_memory().unwrap();
+        let ids: Vec<i64> = ["a", "b", "c", "d"]
+            .iter()
+            .map(|content| db.insert_history(&text_item(content)).unwrap())
+            .collect();
+        for &id in &ids[..3] {
+            db.toggle_pin(id).unwrap();
+        }
+        let board = |db: &Database| -> Vec<String> {
+            db.get_pinned_board().unwrap().into_iter().map(|item| item.content).collect()
+        };
+        // Newly pinned items go on top
+        assert_eq!(board(&db), ["c", "b", "a"]);
+
+        db.move_pinned(ids[0], PinMove::Up).unwrap();
+        assert_eq!(board(&db), ["c", "a", "b"]);
+        db.move_pinned(ids[2], PinMove::To(10)).unwrap();
+        assert_eq!(board(&db), ["a", "b", "c"]);
+        db.move_pinned(ids[0], PinMove::Up).unwrap();
+        db.move_pinned(ids[1], PinMove::Down).unwrap();
+        assert_eq!(board(&db), ["a", "c", "b"]);
+        assert!(db.move_pinned(ids[3], PinMove::Up).is_err());
+
+        // Re-copying and re-pinning don't move anything
+        db.insert_history(&text_item("b")).unwrap();
+        db.set_pinned(ids[1], true).unwrap();
+        assert_eq!(board(&db), ["a", "c", "b"]);
+
+        let page = db.get_history_page(None, 2).unwrap();
+        let rest = db.get_history_page(page.next.as_ref(), 10).unwrap();
+        let contents: Vec<String> = page.items.into_iter().chain(rest.items).map(|item| item.content).collect();
+        assert_eq!(contents, ["a", "c", "b", "d"]);
+
+        // Unpinning drops the board position; restoring puts it back
+        let order = db.get_item_by_id(ids[2]).unwrap().unwrap().pin_order;
+        db.toggle_pin(ids[2]).unwrap();
+        assert_eq!(db.get_item_by_id(ids[2]).unwrap().unwrap().pin_order, None);
+        db.restore_pin(ids[2], order).unwrap();
+        assert_eq!(board(&db), ["a", "c", "b"]);
+    }
+
+    #[test]
+    fn test_usage_stats() {
+        let db = Database::open_in_memory().unwrap();
+        let item = |content: &str, app: &str| ClipboardItem {
+            source_app: Some(app.to_string()),
+            ..text_item(content)
+        };
+        let once = db.insert_history(&item("once", "editor")).unwrap();
+        let copied = db.insert_history(&item("copied", "browser")).unwrap();
+        let pasted = db.insert_history(&item("pasted", "editor")).unwrap();
+        {
+            // Pretend the first capture was a while ago
+            let conn = db.conn.lock().unwrap();
+            conn.execute("UPDATE history SET first_seen_at = 1000 WHERE id = ?", params![copied]).unwrap();
+        }
+        db.insert_history(&item("copied", "browser")).unwrap();
+        db.insert_history(&item("copied", "browser")).unwrap();
+        db.record_paste(pasted).unwrap();
+
+        let stats = db.item_stats(copied).unwrap().unwrap();
+        assert_eq!((stats.copy_count, stats.paste_count, stats.first_seen_at), (3, 0, Some(1000)));
+        assert!(stats.last_copied_at.unwrap() > 1000);
+        let stats = db.item_stats(pasted).unwrap().unwrap();
+        assert_eq!((stats.copy_count, stats.paste_count), (1, 1));
+        assert!(stats.last_pasted_at.is_some());
+        assert!(db.item_stats(999).unwrap().is_none());
+
+        let ranked: Vec<i64> = db.most_used(10).unwrap().iter().map(|used| used.item.id.unwrap()).collect();
+        assert_eq!(ranked, [copied, pasted, once]);
+
+        let summary = db.stats(7, 1).unwrap();
+        assert_eq!((summary.total_items, summary.copies, summary.pastes), (3, 5, 1));
+        assert_eq!(summary.top_apps, [("editor".to_string(), 2)]);
+        // The back-dated item falls outside the window
+        assert_eq!(summary.items_per_day.len(), 1);
+        assert_eq!(summary.items_per_day[0].1, 2);
+        assert!(summary.storage.database_bytes > 0);
+        assert_eq!(summary.storage.image_files, 0);
     }
 }
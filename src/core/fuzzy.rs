@@ -0,0 +1,495 @@
+use crate::core::config::RankingConfig;
+use crate::core::database::excerpt_window;
+use crate::core::types::{MatchSpan, SearchExcerpt, SearchHit, SearchResult};
+
+// Scoring in the style of fzf's v1 algorithm
+const SCORE_MATCH: i64 = 16;
+const SCORE_GAP_START: i64 = -3;
+const SCORE_GAP_EXTENSION: i64 = -1;
+const BONUS_BOUNDARY: i64 = 8;
+const BONUS_CAMEL: i64 = 7;
+const BONUS_CONSECUTIVE: i64 = 4;
+const BONUS_FIRST_CHAR_MULTIPLIER: i64 = 2;
+/// Charged per edit when a term only matches a word up to a typo.
+const SCORE_TYPO: i64 = -2 * SCORE_MATCH;
+
+/// Only the head of huge items (log pastes) is fuzzy-matched; anything
+/// further down is still reachable through the FTS search.
+const MAX_MATCH_CHARS: usize = 4096;
+
+/// Use count at which the usage signal reaches one half.
+const USAGE_SATURATION: f64 = 5.0;
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct FuzzyMatch {
+    pub score: i64,
+    /// Byte ranges of the matched characters, adjacent ones merged.
+    pub spans: Vec<MatchSpan>,
+}
+
+/// Something the quick-pick can offer, with the signals used for ranking.
+#[derive(Debug, Clone)]
+pub struct Candidate {
+    pub hit: SearchHit,
+    /// Text the query is matched against.
+    pub text: String,
+    /// Unix timestamp of the last copy or use.
+    pub last_used: i64,
+    pub is_pinned: bool,
+    /// Pastes for history items, usage count for snippets.
+    pub use_count: i64,
+}
+
+/// Matches every whitespace-separated term of `pattern` as a case-insensitive
+/// subsequence of `text`, so "dockr cmpse" finds "docker compose up". A term
+/// that is not a subsequence may still match a word within a small edit
+/// distance ("recieve" finds "receive"), at a lower score.
+///
+/// Returns `None` if a term is missing, or if the matched characters are so
+/// spread out that gap penalties outweigh them.
+pub fn fuzzy_match(pattern: &str, text: &str) -> Option<FuzzyMatch> {
+    let chars: Vec<(usize, char)> = text.char_indices().take(MAX_MATCH_CHARS).collect();
+
+    let mut score = 0;
+    let mut positions = Vec::new();
+    for term in pattern.split_whitespace() {
+        let (term_score, term_positions) = match_term(term, &chars).or_else(|| match_typo(term, &chars))?;
+        score += term_score;
+        positions.extend(term_positions);
+    }
+    if score <= 0 {
+        return None;
+    }
+
+    positions.sort_unstable();
+    positions.dedup();
+
+    let mut spans: Vec<MatchSpan> = Vec::new();
+    for i in positions {
+        let (start, c) = chars[i];
+        let end = start + c.len_utf8();
+        match spans.last_mut() {
+            Some(last) if last.end == start => last.end = end,
+            _ => spans.push(MatchSpan { start, end }),
+        }
+    }
+
+    Some(FuzzyMatch { score, spans })
+}
+
+/// Ranks `candidates` for `query` by blending match quality, recency, pin
+/// state and use count. An empty query ranks by the other signals alone.
+pub fn rank(query: &str, candidates: Vec<Candidate>, now: i64, config: &RankingConfig) -> Vec<SearchResult> {
+    let perfect = perfect_score(query);
+
+    let mut results = Vec::new();
+    for candidate in candidates {
+        let (match_score, spans) = if perfect == 0 {
+            (1.0, Vec::new())
+        } else {
+            match fuzzy_match(query, &candidate.text) {
+                Some(m) => ((m.score as f64 / perfect as f64).min(1.0), m.spans),
+                None => continue,
+            }
+        };
+
+        let score = blend(match_score, &candidate, now, config);
+        let excerpt = match &candidate.hit {
+            SearchHit::History(_) if !spans.is_empty() => Some(excerpt(&candidate.text, spans)),
+            // Matched against "title content"; only the content part is shown
+            SearchHit::Snippet(snippet) if !snippet.is_masked && perfect != 0 => {
+                let offset = snippet.title.len() + 1;
+                let spans = spans
+                    .iter()
+                    .filter(|span| span.start >= offset)
+                    .map(|span| MatchSpan { start: span.start - offset, end: span.end - offset })
+                    .collect();
+                Some(excerpt(&snippet.content, spans))
+            }
+            _ => None,
+        };
+        results.push(SearchResult { hit: candidate.hit, score, excerpt });
+    }
+
+    results.sort_by(|a, b| b.score.total_cmp(&a.score));
+    results
+}
+
+fn excerpt(content: &str, spans: Vec<MatchSpan>) -> SearchExcerpt {
+    let (text, matches) = excerpt_window(content, &spans);
+    SearchExcerpt { text, matches, content_matches: spans }
+}
+
+/// The ranking function: weighted sum of signals normalized to 0..=1.
+pub fn blend(match_score: f64, candidate: &Candidate, now: i64, config: &RankingConfig) -> f64 {
+    let recency = if config.recency_half_life_hours > 0.0 {
+        let age_hours = (now - candidate.last_used).max(0) as f64 / 3600.0;
+        0.5f64.powf(age_hours / config.recency_half_life_hours)
+    } else {
+        0.0
+    };
+    let pinned = if candidate.is_pinned { 1.0 } else { 0.0 };
+    let uses = candidate.use_count.max(0) as f64;
+    let usage = uses / (uses + USAGE_SATURATION);
+
+    config.match_weight * match_score
+        + config.recency_weight * recency
+        + config.pin_weight * pinned
+        + config.usage_weight * usage
+}
+
+fn match_term(term: &str, chars: &[(usize, char)]) -> Option<(i64, Vec<usize>)> {
+    let pattern: Vec<char> = term.chars().map(fold).collect();
+
+    // Forward: earliest position where the whole term has been seen
+    let mut pi = 0;
+    let mut end = None;
+    for (i, &(_, c)) in chars.iter().enumerate() {
+        if fold(c) == pattern[pi] {
+            pi += 1;
+            if pi == pattern.len() {
+                end = Some(i);
+                break;
+            }
+        }
+    }
+    let end = end?;
+
+    // Backward: latest start, which gives the shortest window ending there
+    let mut pi = pattern.len();
+    let mut start = 0;
+    for i in (0..=end).rev() {
+        if fold(chars[i].1) == pattern[pi - 1] {
+            pi -= 1;
+            if pi == 0 {
+                start = i;
+                break;
+            }
+        }
+    }
+
+    let mut positions = Vec::with_capacity(pattern.len());
+    for (i, &(_, c)) in chars.iter().enumerate().take(end + 1).skip(start) {
+        if positions.len() < pattern.len() && fold(c) == pattern[positions.len()] {
+            positions.push(i);
+        }
+    }
+
+    Some((score_positions(&positions, chars), positions))
+}
+
+/// Matches `term` against the word, or the head of a word still being typed,
+/// with the fewest edits, allowing one edit from four characters and two from
+/// eight. Shorter terms are too ambiguous to correct.
+fn match_typo(term: &str, chars: &[(usize, char)]) -> Option<(i64, Vec<usize>)> {
+    let pattern: Vec<char> = term.chars().map(fold).collect();
+    let max_edits = match pattern.len() {
+        0..=3 => return None,
+        4..=7 => 1,
+        _ => 2,
+    };
+
+    // (edits, start, length) of the closest word
+    let mut best: Option<(usize, usize, usize)> = None;
+    let mut i = 0;
+    while i < chars.len() {
+        if !chars[i].1.is_alphanumeric() {
+            i += 1;
+            continue;
+        }
+        let start = i;
+        while i < chars.len() && chars[i].1.is_alphanumeric() {
+            i += 1;
+        }
+        let word: Vec<char> = chars[start..i].iter().map(|&(_, c)| fold(c)).collect();
+        for len in [word.len(), pattern.len().min(word.len())] {
+            if len.abs_diff(pattern.len()) > max_edits {
+                continue;
+            }
+            let edits = edit_distance(&pattern, &word[..len]);
+            if edits <= max_edits && best.is_none_or(|(fewest, _, _)| edits < fewest) {
+                best = Some((edits, start, len));
+            }
+        }
+    }
+
+    let (edits, start, len) = best?;
+    let positions: Vec<usize> = (start..start + len).collect();
+    Some((score_positions(&positions, chars) + SCORE_TYPO * edits as i64, positions))
+}
+
+/// Levenshtein distance that also counts swapping two adjacent characters as
+/// a single edit (optimal string alignment).
+fn edit_distance(a: &[char], b: &[char]) -> usize {
+    let mut rows = vec![vec![0; b.len() + 1]; a.len() + 1];
+    for (i, row) in rows.iter_mut().enumerate() {
+        row[0] = i;
+    }
+    for (j, cell) in rows[0].iter_mut().enumerate() {
+        *cell = j;
+    }
+    for i in 1..=a.len() {
+        for j in 1..=b.len() {
+            let cost = usize::from(a[i - 1] != b[j - 1]);
+            let mut d = (rows[i - 1][j] + 1).min(rows[i][j - 1] + 1).min(rows[i - 1][j - 1] + cost);
+            if i > 1 && j > 1 && a[i - 1] == b[j - 2] && a[i - 2] == b[j - 1] {
+                d = d.min(rows[i - 2][j - 2] + 1);
+            }
+            rows[i][j] = d;
+        }
+    }
+    rows[a.len()][b.len()]
+}
+
+fn score_positions(positions: &[usize], chars: &[(usize, char)]) -> i64 {
+    let mut score = 0;
+    let mut prev: Option<usize> = None;
+
+    for (n, &i) in positions.iter().enumerate() {
+        let mut bonus = bonus_at(chars, i);
+        if n == 0 {
+            bonus *= BONUS_FIRST_CHAR_MULTIPLIER;
+        }
+        score += SCORE_MATCH + bonus;
+
+        if let Some(prev) = prev {
+            let gap = (i - prev - 1) as i64;
+            if gap == 0 {
+                score += BONUS_CONSECUTIVE;
+            } else {
+                score += SCORE_GAP_START + SCORE_GAP_EXTENSION * (gap - 1);
+            }
+        }
+        prev = Some(i);
+    }
+    score
+}
+
+/// Bonus for matching at the start of a word or of a camelCase hump.
+fn bonus_at(chars: &[(usize, char)], i: usize) -> i64 {
+    let current = chars[i].1;
+    let Some(&(_, previous)) = i.checked_sub(1).and_then(|p| chars.get(p)) else {
+        return BONUS_BOUNDARY;
+    };
+
+    if !previous.is_alphanumeric() && current.is_alphanumeric() {
+        BONUS_BOUNDARY
+    } else if (previous.is_lowercase() && current.is_uppercase())
+        || (previous.is_alphabetic() && current.is_numeric())
+    {
+        BONUS_CAMEL
+    } else {
+        0
+    }
+}
+
+/// Score of every term matching contiguously at a word start.
+fn perfect_score(pattern: &str) -> i64 {
+    pattern
+        .split_whitespace()
+        .map(|term| {
+            let len = term.chars().count() as i64;
+            len * SCORE_MATCH + BONUS_BOUNDARY * BONUS_FIRST_CHAR_MULTIPLIER + (len - 1) * BONUS_CONSECUTIVE
+        })
+        .sum()
+}
+
+fn fold(c: char) -> char {
+    c.to_lowercase().next().unwrap_or(c)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::core::types::{ClipboardItem, ClipboardType, Snippet};
+
+    const NOW: i64 = 1_700_000_000;
+    const HOUR: i64 = 3600;
+
+    fn candidate(id: i64, text: &str, age_hours: i64, is_pinned: bool, use_count: i64) -> Candidate {
+        Candidate {
+            hit: SearchHit::History(ClipboardItem {
+                id: Some(id),
+                type_: ClipboardType::Text,
+                content: text.to_string(),
+                content_hash: format!("hash-{}", id),
+                source_app: None,
+                created_at: NOW - age_hours * HOUR,
+                is_pinned,
+                edited_at: None,
+                pin_order: None,
+                tags: Vec::new(),
+            }),
+            text: text.to_string(),
+            last_used: NOW - age_hours * HOUR,
+            is_pinned,
+            use_count,
+        }
+    }
+
+    fn corpus() -> Vec<Candidate> {
+        vec![
+            candidate(1, "docker compose up -d", 48, false, 0),
+            candidate(2, "git commit -m 'fix docker image'", 1, false, 0),
+            candidate(3, "kubectl get pods --all-namespaces", 2, false, 3),
+            candidate(4, "ssh deploy@10.0.0.12", 200, true, 10),
+            candidate(5, "The quick brown fox jumps over the lazy dog", 5, false, 0),
+            candidate(6, "let manager = ClipboardManager::new(db);", 10, false, 1),
+        ]
+    }
+
+    fn ids(results: &[SearchResult]) -> Vec<i64> {
+        results
+            .iter()
+            .map(|r| match &r.hit {
+                SearchHit::History(item) => item.id.unwrap(),
+                _ => -1,
+            })
+            .collect()
+    }
+
+    #[test]
+    fn test_subsequence_with_typos() {
+        let m = fuzzy_match("dockr cmpse", "docker compose up -d").unwrap();
+        assert!(m.score > 0);
+        assert!(fuzzy_match("dockr cmpse", "kubectl get pods").is_none());
+        assert!(fuzzy_match("DOCKER", "docker").is_some());
+    }
+
+    #[test]
+    fn test_misspelled_words_within_edit_distance() {
+        let text = "please receive the package";
+        let m = fuzzy_match("recieve", text).unwrap();
+        let matched: Vec<&str> = m.spans.iter().map(|s| &text[s.start..s.end]).collect();
+        assert_eq!(matched, vec!["receive"]);
+        assert!(fuzzy_match("pakcage recieve", text).is_some());
+
+        // Too many edits, or too short to correct
+        assert!(fuzzy_match("rceevie", text).is_none());
+        assert!(fuzzy_match("teh", text).is_none());
+
+        let exact = fuzzy_match("receive", text).unwrap().score;
+        assert!(m.score < exact);
+    }
+
+    #[test]
+    fn test_spans_are_byte_ranges() {
+        let text = "剪贴板 docker";
+        let m = fuzzy_match("剪板 dock", text).unwrap();
+        let matched: Vec<&str> = m.spans.iter().map(|s| &text[s.start..s.end]).collect();
+        assert_eq!(matched, vec!["剪", "板", "dock"]);
+    }
+
+    #[test]
+    fn test_boundaries_and_contiguity_score_higher() {
+        let boundary = fuzzy_match("cm", "clipboard manager").unwrap().score;
+        let inner = fuzzy_match("cm", "xcxxxmx").unwrap().score;
+        assert!(boundary > inner);
+
+        let camel = fuzzy_match("cm", "ClipboardManager").unwrap().score;
+        assert!(camel > inner);
+
+        let contiguous = fuzzy_match("pods", "get pods").unwrap().score;
+        let scattered = fuzzy_match("pods", "put on dashes").unwrap().score;
+        assert!(contiguous > scattered);
+    }
+
+    #[test]
+    fn test_spread_out_matches_are_rejected() {
+        let text = format!("a{}b{}c", " ".repeat(200), " ".repeat(200));
+        assert!(fuzzy_match("abc", &text).is_none());
+    }
+
+    #[test]
+    fn test_rank_fixed_corpus() {
+        let config = RankingConfig::default();
+
+        let results = rank("dockr cmpse", corpus(), NOW, &config);
+        assert_eq!(ids(&results)[0], 1);
+
+        let results = rank("docker", corpus(), NOW, &config);
+        assert_eq!(ids(&results), vec![2, 1]);
+
+        let results = rank("ClipMgr", corpus(), NOW, &config);
+        assert_eq!(ids(&results), vec![6]);
+        let excerpt = results[0].excerpt.as_ref().unwrap();
+        assert_eq!(excerpt.text, "let manager = ClipboardManager::new(db);");
+        assert!(!excerpt.matches.is_empty());
+    }
+
+    #[test]
+    fn test_empty_query_ranks_by_signals() {
+        let config = RankingConfig::default();
+        let results = rank("", corpus(), NOW, &config);
+        assert_eq!(results.len(), corpus().len());
+        // Pinned and heavily used beats an hour-old item
+        assert_eq!(ids(&results)[0], 4);
+        assert!(results.iter().all(|r| r.excerpt.is_none()));
+    }
+
+    #[test]
+    fn test_snippet_excerpt_marks_content() {
+        let snippet = |title: &str, content: &str, is_masked: bool| Candidate {
+            hit: SearchHit::Snippet(Snippet {
+                id: Some(1),
+                category_id: 1,
+                title: title.to_string(),
+                content: content.to_string(),
+                is_masked,
+                usage_count: 0,
+                updated_at: NOW,
+            }),
+            text: if is_masked { title.to_string() } else { format!("{} {}", title, content) },
+            last_used: NOW,
+            is_pinned: false,
+            use_count: 0,
+        };
+        let results = rank(
+            "deploy",
+            vec![snippet("Ops", "ssh deploy@host", false), snippet("deploy key", "secret", true)],
+            NOW,
+            &RankingConfig::default(),
+        );
+        assert_eq!(results.len(), 2);
+        let excerpts: Vec<_> = results.iter().map(|r| r.excerpt.as_ref()).collect();
+        let open = excerpts.iter().flatten().next().unwrap();
+        assert_eq!(open.text, "ssh deploy@host");
+        let span = open.matches[0];
+        assert_eq!(&open.text[span.start..span.end], "deploy");
+        // Masked content is never shown, so there is nothing to excerpt
+        assert_eq!(excerpts.iter().filter(|e| e.is_none()).count(), 1);
+    }
+
+    #[test]
+    fn test_weights_are_configurable() {
+        let corpus = vec![
+            candidate(1, "release notes draft", 300, false, 0),
+            candidate(2, "release notes", 0, false, 0),
+            candidate(3, "re-lease nodes", 0, true, 20),
+        ];
+
+        let matching_only = RankingConfig {
+            match_weight: 1.0,
+            recency_weight: 0.0,
+            pin_weight: 0.0,
+            usage_weight: 0.0,
+            ..RankingConfig::default()
+        };
+        let results = rank("release", corpus.clone(), NOW, &matching_only);
+        assert_eq!(results[0].score, results[1].score);
+        assert_eq!(ids(&results)[2], 3);
+
+        let recency_first = RankingConfig {
+            recency_weight: 2.0,
+            ..matching_only.clone()
+        };
+        assert_eq!(ids(&rank("release", corpus.clone(), NOW, &recency_first))[0], 2);
+
+        let usage_first = RankingConfig {
+            pin_weight: 1.0,
+            usage_weight: 1.0,
+            ..matching_only
+        };
+        assert_eq!(ids(&rank("release", corpus, NOW, &usage_first))[0], 3);
+    }
+}
@@ -0,0 +1,677 @@
+//! Control socket for scripts, editor plugins and launchers.
+//!
+//! Newline-delimited JSON over a Unix socket in the data directory. Each
+//! request is one line, like `{"id": 1, "method": "get", "item": 42}`, with
+//! the method's parameters next to `method`; each reply echoes the request
+//! id and carries either `result` or a typed `error`. After `subscribe`, the
+//! connection also receives each `ManagerEvent` as an `{"event": ...}` line.
+
+use crate::core::async_db::AsyncDatabase;
+use crate::core::clipboard;
+use crate::core::manager::{CommandRequest, ManagerError};
+use crate::core::types::{
+    AppCommand, ClipboardItem, ClipboardType, HistoryCursor, ManagerEvent, PasteOptions,
+};
+use anyhow::{bail, Context, Result};
+use serde::de::DeserializeOwned;
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+use tokio::io::{AsyncBufReadExt, AsyncReadExt, AsyncWriteExt, BufReader};
+use tokio::net::unix::OwnedReadHalf;
+use tokio::net::{UnixListener, UnixStream};
+use tokio::sync::{broadcast, mpsc};
+
+pub const DEFAULT_LIMIT: usize = 50;
+pub const DEFAULT_STATS_DAYS: u32 = 7;
+pub const DEFAULT_TOP_APPS: usize = 5;
+/// Longest request line accepted; longer ones close the connection.
+pub const MAX_REQUEST_LINE: usize = 64 * 1024;
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Request {
+    /// Echoed in the reply; any JSON value.
+    #[serde(default)]
+    pub id: Value,
+    #[serde(flatten)]
+    pub call: Call,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(tag = "method", rename_all = "snake_case")]
+pub enum Call {
+    /// A page of history in display order.
+    List {
+        #[serde(default)]
+        limit: Option<usize>,
+        #[serde(default)]
+        after: Option<HistoryCursor>,
+        /// Only items of this type.
+        #[serde(default, rename = "type")]
+        type_: Option<ClipboardType>,
+    },
+    /// Unified search over history, snippets and tags, like the search box.
+    Search {
+        query: String,
+        #[serde(default)]
+        limit: Option<usize>,
+    },
+    Get {
+        item: i64,
+    },
+    Paste {
+        item: i64,
+        #[serde(default)]
+        options: PasteOptions,
+    },
+    /// Puts an item on the clipboard without pasting it.
+    Copy {
+        item: i64,
+        #[serde(default)]
+        options: PasteOptions,
+    },
+    PasteSnippet {
+        snippet: i64,
+        #[serde(default)]
+        options: PasteOptions,
+    },
+    CopySnippet {
+        snippet: i64,
+        #[serde(default)]
+        options: PasteOptions,
+    },
+    /// Saves text to history as if it had been copied.
+    Add {
+        content: String,
+    },
+    /// Sets the pin state, or toggles it when `pinned` is left out.
+    Pin {
+        item: i64,
+        #[serde(default)]
+        pinned: Option<bool>,
+    },
+    Delete {
+        item: i64,
+    },
+    /// The paste queue and whether queue mode is on.
+    Queue,
+    Enqueue {
+        items: Vec<i64>,
+    },
+    QueueNext,
+    QueueClear,
+    /// Stops saving clipboard changes, or resumes with `paused: false`.
+    Pause {
+        paused: bool,
+    },
+    Snippets,
+    /// Usage totals, with items per day over the last `days` days.
+    Stats {
+        #[serde(default)]
+        days: Option<u32>,
+        #[serde(default)]
+        top_apps: Option<usize>,
+    },
+    /// Starts streaming events on this connection.
+    Subscribe,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Reply {
+    pub id: Value,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub result: Option<Value>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub error: Option<IpcError>,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct IpcError {
+    pub code: ErrorCode,
+    pub message: String,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ErrorCode {
+    /// The line isn't a valid request.
+    BadRequest,
+    NotFound,
+    /// The request was valid but couldn't be carried out.
+    Failed,
+}
+
+impl IpcError {
+    fn new(code: ErrorCode, message: impl Into<String>) -> Self {
+        Self { code, message: message.into() }
+    }
+
+    fn failed(error: anyhow::Error) -> Self {
+        Self::new(ErrorCode::Failed, format!("{:#}", error))
+    }
+}
+
+impl std::fmt::Display for IpcError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(&self.message)
+    }
+}
+
+impl std::error::Error for IpcError {}
+
+impl From<ManagerError> for IpcError {
+    fn from(error: ManagerError) -> Self {
+        let code = match error {
+            ManagerError::ItemNotFound(_) | ManagerError::SnippetNotFound(_) => ErrorCode::NotFound,
+            _ => ErrorCode::Failed,
+        };
+        Self::new(code, error.to_string())
+    }
+}
+
+/// Blocking client sending one request at a time, as the command line does.
+pub struct Client {
+    stream: std::io::BufReader<std::os::unix::net::UnixStream>,
+    next_id: u64,
+}
+
+impl Client {
+    /// Fails when no instance is listening on `path`.
+    pub fn connect(path: &Path) -> std::io::Result<Self> {
+        let stream = std::os::unix::net::UnixStream::connect(path)?;
+        Ok(Self {
+            stream: std::io::BufReader::new(stream),
+            next_id: 0,
+        })
+    }
+
+    /// Sends `call` and waits for its reply. An error reply is returned as
+    /// an `IpcError` inside the `anyhow::Error`.
+    pub fn call<T: DeserializeOwned>(&mut self, call: Call) -> Result<T> {
+        use std::io::{BufRead, Write};
+
+        self.next_id += 1;
+        let request = Request { id: Value::from(self.next_id), call };
+        let mut line = serde_json::to_vec(&request)?;
+        line.push(b'\n');
+        self.stream.get_mut().write_all(&line).context("Failed to send request")?;
+
+        let mut line = String::new();
+        if self.stream.read_line(&mut line).context("Failed to read reply")? == 0 {
+            bail!("picaclip closed the connection");
+        }
+        let reply: Reply = serde_json::from_str(&line).context("Malformed reply")?;
+        if let Some(error) = reply.error {
+            return Err(error.into());
+        }
+        Ok(serde_json::from_value(reply.result.unwrap_or(Value::Null))?)
+    }
+}
+
+#[derive(Serialize)]
+struct EventLine<'a> {
+    event: &'a ManagerEvent,
+}
+
+struct Shared {
+    db: AsyncDatabase,
+    commands: mpsc::Sender<CommandRequest>,
+    events: broadcast::Sender<ManagerEvent>,
+}
+
+/// Listens on the control socket. The socket file is removed when the
+/// server is dropped.
+pub struct IpcServer {
+    listener: UnixListener,
+    path: PathBuf,
+    shared: Arc<Shared>,
+}
+
+impl IpcServer {
+    /// Binds the socket, replacing a stale one. Callers hold the instance
+    /// lock, so an existing file can't belong to a live server.
+    pub fn bind(
+        path: &Path,
+        db: AsyncDatabase,
+        commands: mpsc::Sender<CommandRequest>,
+        events: broadcast::Sender<ManagerEvent>,
+    ) -> Result<Self> {
+        let _ = std::fs::remove_file(path);
+        let listener =
+            bind_private(path).with_context(|| format!("Failed to bind control socket {}", path.display()))?;
+        Ok(Self {
+            listener,
+            path: path.to_path_buf(),
+            shared: Arc::new(Shared { db, commands, events }),
+        })
+    }
+
+    pub async fn run(self) {
+        log::info!("Listening on {}", self.path.display());
+        loop {
+            match self.listener.accept().await {
+                Ok((stream, _)) => {
+                    tokio::spawn(serve(stream, self.shared.clone()));
+                }
+                Err(e) => log::error!("Failed to accept IPC connection: {}", e),
+            }
+        }
+    }
+}
+
+impl Drop for IpcServer {
+    fn drop(&mut self) {
+        let _ = std::fs::remove_file(&self.path);
+    }
+}
+
+/// Binds `path` so that only this user can ever connect: history is
+/// private. The socket is created in a fresh 0700 directory, made 0600 and
+/// only then moved into place, so it is never reachable with the umask's
+/// permissions.
+fn bind_private(path: &Path) -> Result<UnixListener> {
+    use std::os::unix::fs::{DirBuilderExt, PermissionsExt};
+
+    let name = path.file_name().context("Socket path has no file name")?.to_string_lossy();
+    let staging = path.with_file_name(format!(".{}.{}", name, std::process::id()));
+    let _ = std::fs::remove_dir_all(&staging);
+    std::fs::DirBuilder::new().mode(0o700).create(&staging)?;
+    let staged = staging.join("socket");
+    let bound = UnixListener::bind(&staged)
+        .map_err(anyhow::Error::from)
+        .and_then(|listener| {
+            std::fs::set_permissions(&staged, std::fs::Permissions::from_mode(0o600))?;
+            std::fs::rename(&staged, path)?;
+            Ok(listener)
+        });
+    let _ = std::fs::remove_dir_all(&staging);
+    bound
+}
+
+/// Reads one request line into `buf`, without the newline. Cancel safe:
+/// a partial line stays in `buf` for the next call. Lines longer than
+/// `MAX_REQUEST_LINE` are an `InvalidData` error.
+async fn read_request_line(reader: &mut BufReader<OwnedReadHalf>, buf: &mut Vec<u8>) -> std::io::Result<Option<String>> {
+    let limit = (MAX_REQUEST_LINE + 1).saturating_sub(buf.len()) as u64;
+    let read = (&mut *reader).take(limit).read_until(b'\n', buf).await?;
+    if buf.len() > MAX_REQUEST_LINE && !buf.ends_with(b"\n") {
+        return Err(std::io::Error::new(
+            std::io::ErrorKind::InvalidData,
+            format!("request line longer than {} bytes", MAX_REQUEST_LINE),
+        ));
+    }
+    if read == 0 && buf.is_empty() {
+        return Ok(None);
+    }
+    let mut line = std::mem::take(buf);
+    if line.ends_with(b"\n") {
+        line.pop();
+    }
+    String::from_utf8(line)
+        .map(Some)
+        .map_err(|e| std::io::Error::new(std::io::ErrorKind::InvalidData, e))
+}
+
+async fn serve(stream: UnixStream, shared: Arc<Shared>) {
+    let (reader, mut writer) = stream.into_split();
+    let mut reader = BufReader::new(reader);
+    let mut buf = Vec::new();
+    let mut events: Option<broadcast::Receiver<ManagerEvent>> = None;
+
+    loop {
+        let line = tokio::select! {
+            line = read_request_line(&mut reader, &mut buf) => match line {
+                Ok(Some(line)) => line,
+                Ok(None) => break,
+                Err(e) if e.kind() == std::io::ErrorKind::InvalidData => {
+                    // The rest of the line can't be told apart from the next request
+                    let error = IpcError::new(ErrorCode::BadRequest, e.to_string());
+                    let _ = write_line(&mut writer, &Reply { id: Value::Null, result: None, error: Some(error) }).await;
+                    break;
+                }
+                Err(e) => {
+                    log::debug!("IPC connection closed: {}", e);
+                    break;
+                }
+            },
+            event = next_event(&mut events) => {
+                match event {
+                    Some(event) => {
+                        if write_line(&mut writer, &EventLine { event: &event }).await.is_err() {
+                            break;
+                        }
+                    }
+                    None => events = None,
+                }
+                continue;
+            }
+        };
+        if line.trim().is_empty() {
+            continue;
+        }
+
+        let reply = match serde_json::from_str::<Request>(&line) {
+            Ok(Request { id, call: Call::Subscribe }) => {
+                events = Some(shared.events.subscribe());
+                Reply { id, result: Some(Value::Null), error: None }
+            }
+            Ok(Request { id, call }) => match handle(&shared, call).await {
+                Ok(result) => Reply { id, result: Some(result), error: None },
+                Err(error) => Reply { id, result: None, error: Some(error) },
+            },
+            Err(e) => Reply {
+                id: request_id(&line),
+                result: None,
+                error: Some(IpcError::new(ErrorCode::BadRequest, e.to_string())),
+            },
+        };
+        if write_line(&mut writer, &reply).await.is_err() {
+            break;
+        }
+    }
+}
+
+/// The next event for a subscribed connection; pending forever when not
+/// subscribed. `None` once the channel is closed.
+async fn next_event(events: &mut Option<broadcast::Receiver<ManagerEvent>>) -> Option<ManagerEvent> {
+    let Some(receiver) = events else {
+        return std::future::pending().await;
+    };
+    loop {
+        match receiver.recv().await {
+            Ok(event) => return Some(event),
+            // A slow client misses events rather than holding up the manager
+            Err(broadcast::error::RecvError::Lagged(skipped)) => {
+                log::warn!("IPC subscriber lagged, {} events dropped", skipped);
+            }
+            Err(broadcast::error::RecvError::Closed) => return None,
+        }
+    }
+}
+
+/// Best-effort id of a request that failed to parse, so the error can still
+/// be matched up.
+fn request_id(line: &str) -> Value {
+    serde_json::from_str::<Value>(line)
+        .ok()
+        .and_then(|value| value.get("id").cloned())
+        .unwrap_or(Value::Null)
+}
+
+async fn write_line<T: Serialize>(writer: &mut tokio::net::unix::OwnedWriteHalf, value: &T) -> Result<()> {
+    let mut line = serde_json::to_vec(value)?;
+    line.push(b'\n');
+    writer.write_all(&line).await?;
+    Ok(())
+}
+
+async fn handle(shared: &Shared, call: Call) -> Result<Value, IpcError> {
+    let result = match call {
+        Call::List { limit, after, type_ } => {
+            let limit = limit.unwrap_or(DEFAULT_LIMIT);
+            let page = shared
+                .db
+                .call(move |db| db.get_history_page_of_type(after.as_ref(), limit, type_))
+                .await
+                .map_err(IpcError::failed)?;
+            to_value(page)?
+        }
+        Call::Search { query, limit } => {
+            let limit = limit.unwrap_or(DEFAULT_LIMIT);
+            let results = shared
+                .db
+                .call(move |db| db.search_all(&query, limit))
+                .await
+                .map_err(IpcError::failed)?;
+            to_value(results)?
+        }
+        Call::Get { item } => to_value(existing_item(shared, item).await?)?,
+        Call::Paste { item, options } => {
+            send(shared, AppCommand::PasteItem(item, options)).await?;
+            Value::Null
+        }
+        Call::Copy { item, options } => {
+            send(shared, AppCommand::CopyItem(item, options)).await?;
+            Value::Null
+        }
+        Call::PasteSnippet { snippet, options } => {
+            send(shared, AppCommand::PasteSnippet(snippet, options)).await?;
+            Value::Null
+        }
+        Call::CopySnippet { snippet, options } => {
+            send(shared, AppCommand::CopySnippet(snippet, options)).await?;
+            Value::Null
+        }
+        Call::Add { content } => {
+            if clipboard::captured_text(&content).is_none() {
+                return Err(IpcError::new(ErrorCode::BadRequest, "content is empty"));
+            }
+            send(shared, AppCommand::AddText(content)).await?;
+            Value::Null
+        }
+        Call::Pin { item, pinned } => {
+            // Decided by the manager, so concurrent clients can't undo each other
+            let pinned = match pinned {
+                Some(pinned) => {
+                    send(shared, AppCommand::SetPinned(item, pinned)).await?;
+                    pinned
+                }
+                None => {
+                    send(shared, AppCommand::TogglePin(item)).await?;
+                    existing_item(shared, item).await?.is_pinned
+                }
+            };
+            serde_json::json!({ "pinned": pinned })
+        }
+        Call::Delete { item } => {
+            send(shared, AppCommand::DeleteHistory(item)).await?;
+            Value::Null
+        }
+        Call::Queue => to_value(shared.db.call(|db| db.get_queue_state()).await.map_err(IpcError::failed)?)?,
+        Call::Enqueue { items } => {
+            send(shared, AppCommand::EnqueueHistory(items)).await?;
+            Value::Null
+        }
+        Call::QueueNext => {
+            send(shared, AppCommand::NextQueueItem).await?;
+            Value::Null
+        }
+        Call::QueueClear => {
+            send(shared, AppCommand::ClearQueue).await?;
+            Value::Null
+        }
+        Call::Pause { paused } => {
+            send(shared, AppCommand::SetPaused(paused)).await?;
+            Value::Null
+        }
+        Call::Snippets => to_value(shared.db.call(|db| db.get_all_snippets()).await.map_err(IpcError::failed)?)?,
+        Call::Stats { days, top_apps } => {
+            let days = days.unwrap_or(DEFAULT_STATS_DAYS);
+            let top_apps = top_apps.unwrap_or(DEFAULT_TOP_APPS);
+            to_value(shared.db.call(move |db| db.stats(days, top_apps)).await.map_err(IpcError::failed)?)?
+        }
+        // Handled by the connection, which owns the subscription
+        Call::Subscribe => Value::Null,
+    };
+    Ok(result)
+}
+
+async fn existing_item(shared: &Shared, id: i64) -> Result<ClipboardItem, IpcError> {
+    shared
+        .db
+        .call(move |db| db.get_item_by_id(id))
+        .await
+        .map_err(IpcError::failed)?
+        .ok_or_else(|| IpcError::new(ErrorCode::NotFound, format!("history item {} not found", id)))
+}
+
+/// Runs `command` on the manager and waits for its outcome.
+async fn send(shared: &Shared, command: AppCommand) -> Result<(), IpcError> {
+    let (request, outcome) = CommandRequest::with_reply(command);
+    shared.commands.send(request).await.map_err(|_| ManagerError::Stopped)?;
+    // A dropped reply means the manager exited before getting to it
+    outcome.await.unwrap_or(Err(ManagerError::Stopped)).map_err(IpcError::from)
+}
+
+fn to_value<T: Serialize>(value: T) -> Result<Value, IpcError> {
+    serde_json::to_value(value).map_err(|e| IpcError::failed(e.into()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::core::database::Database;
+    use crate::core::types::{ClipboardType, Snippet};
+    use tokio::io::{AsyncBufReadExt, Lines};
+
+    fn text_item(content: &str) -> ClipboardItem {
+        ClipboardItem {
+            id: None,
+            type_: ClipboardType::Text,
+            content: content.to_string(),
+            content_hash: format!("hash-{}", content),
+            source_app: None,
+            created_at: 0,
+            is_pinned: false,
+            edited_at: None,
+            pin_order: None,
+            tags: Vec::new(),
+        }
+    }
+
+    struct Connection {
+        lines: Lines<BufReader<OwnedReadHalf>>,
+        writer: tokio::net::unix::OwnedWriteHalf,
+    }
+
+    impl Connection {
+        async fn connect(path: &Path) -> Self {
+            let (reader, writer) = UnixStream::connect(path).await.unwrap().into_split();
+            Self { lines: BufReader::new(reader).lines(), writer }
+        }
+
+        async fn call(&mut self, line: &str) -> Value {
+            self.writer.write_all(format!("{}\n", line).as_bytes()).await.unwrap();
+            self.next().await
+        }
+
+        async fn next(&mut self) -> Value {
+            let line = self.lines.next_line().await.unwrap().unwrap();
+            serde_json::from_str(&line).unwrap()
+        }
+    }
+
+    #[tokio::test]
+    async fn test_requests_and_errors() {
+        let db = Database::open_in_memory().unwrap();
+        let id = db.insert_history(&text_item("hello ipc")).unwrap();
+        let (command_tx, mut command_rx) = mpsc::channel::<CommandRequest>(8);
+        let (events, _) = broadcast::channel(8);
+        // Stands in for the manager: deleting item 999 fails, other commands are recorded
+        let (seen_tx, mut seen) = mpsc::unbounded_channel();
+        tokio::spawn(async move {
+            while let Some(CommandRequest { command, reply }) = command_rx.recv().await {
+                let result = match command {
+                    AppCommand::DeleteHistory(999) => Err(ManagerError::ItemNotFound(999)),
+                    _ => Ok(()),
+                };
+                if result.is_ok() {
+                    let _ = seen_tx.send(command);
+                }
+                let _ = reply.unwrap().send(result);
+            }
+        });
+        let path = std::env::temp_dir().join(format!("picaclip_ipc_{}.sock", std::process::id()));
+        let server = IpcServer::bind(&path, AsyncDatabase::new(db.clone()), command_tx, events.clone()).unwrap();
+        let server = tokio::spawn(server.run());
+
+        let mut client = Connection::connect(&path).await;
+        let reply = client.call(r#"{"id": 1, "method": "list", "limit": 10}"#).await;
+        assert_eq!(reply["id"], 1);
+        assert_eq!(reply["result"]["items"][0]["content"], "hello ipc");
+
+        let reply = client.call(r#"{"id": "a", "method": "search", "query": "ipc"}"#).await;
+        assert_eq!(reply["id"], "a");
+        assert_eq!(reply["result"].as_array().unwrap().len(), 1);
+
+        let reply = client.call(r#"{"id": 2, "method": "get", "item": 999}"#).await;
+        assert_eq!(reply["error"]["code"], "not_found");
+        assert!(reply.get("result").is_none());
+
+        let reply = client.call(r#"{"id": 3, "method": "frobnicate"}"#).await;
+        assert_eq!(reply["id"], 3);
+        assert_eq!(reply["error"]["code"], "bad_request");
+        let reply = client.call("not json").await;
+        assert_eq!((reply["id"].clone(), reply["error"]["code"].clone()), (Value::Null, "bad_request".into()));
+
+        let reply = client.call(&format!(r#"{{"id": 4, "method": "pin", "item": {}, "pinned": true}}"#, id)).await;
+        assert_eq!(reply["result"]["pinned"], true);
+        assert!(matches!(seen.recv().await, Some(AppCommand::SetPinned(pinned, true)) if pinned == id));
+        let reply = client.call(&format!(r#"{{"id": 5, "method": "delete", "item": {}}}"#, id)).await;
+        assert_eq!(reply["result"], Value::Null);
+        assert!(matches!(seen.recv().await, Some(AppCommand::DeleteHistory(deleted)) if deleted == id));
+        // Failures reported by the manager come back as the reply
+        let reply = client.call(r#"{"id": 5, "method": "delete", "item": 999}"#).await;
+        assert_eq!(reply["error"]["code"], "not_found");
+        assert_eq!(reply["error"]["message"], "history item 999 not found");
+
+        // Events only reach subscribed connections, after the subscribe reply
+        let reply = client.call(r#"{"id": 6, "method": "subscribe"}"#).await;
+        assert_eq!(reply["result"], Value::Null);
+        events.send(ManagerEvent::ItemDeleted { ids: vec![id] }).unwrap();
+        let event = client.next().await;
+        assert_eq!(event["event"], serde_json::json!({"type": "item_deleted", "ids": [id]}));
+        events.send(ManagerEvent::Paused { paused: true }).unwrap();
+        assert_eq!(client.next().await["event"], serde_json::json!({"type": "paused", "paused": true}));
+
+        db.set_setting("queue.active", "true").unwrap();
+        let reply = client.call(r#"{"id": 7, "method": "queue"}"#).await;
+        assert_eq!(reply["result"]["active"], true);
+        client.call(r#"{"id": 8, "method": "pause", "paused": false}"#).await;
+        assert!(matches!(seen.recv().await, Some(AppCommand::SetPaused(false))));
+
+        // The blocking client used by the command line
+        let client_path = path.clone();
+        let (snippets, missing) = tokio::task::spawn_blocking(move || {
+            let mut client = Client::connect(&client_path).unwrap();
+            let snippets: Vec<Snippet> = client.call(Call::Snippets).unwrap();
+            (snippets, client.call::<ClipboardItem>(Call::Get { item: 999 }).unwrap_err())
+        })
+        .await
+        .unwrap();
+        assert!(snippets.is_empty());
+        assert_eq!(missing.downcast_ref::<IpcError>().unwrap().code, ErrorCode::NotFound);
+        client.call(r#"{"id": 9, "method": "add", "content": "from a script"}"#).await;
+        assert!(matches!(seen.recv().await, Some(AppCommand::AddText(text)) if text == "from a script"));
+
+        server.abort();
+        let _ = server.await;
+        assert!(!path.exists());
+    }
+
+    #[tokio::test]
+    async fn test_socket_is_private_and_lines_are_bounded() {
+        use std::os::unix::fs::PermissionsExt;
+
+        let (command_tx, _command_rx) = mpsc::channel::<CommandRequest>(1);
+        let (events, _) = broadcast::channel(1);
+        let db = AsyncDatabase::new(Database::open_in_memory().unwrap());
+        let path = std::env::temp_dir().join(format!("picaclip_ipc_bounded_{}.sock", std::process::id()));
+        let server = IpcServer::bind(&path, db, command_tx, events).unwrap();
+        assert_eq!(std::fs::metadata(&path).unwrap().permissions().mode() & 0o777, 0o600);
+        let server = tokio::spawn(server.run());
+
+        let mut client = Connection::connect(&path).await;
+        let reply = client.call(&"x".repeat(MAX_REQUEST_LINE + 1)).await;
+        assert_eq!(reply["error"]["code"], "bad_request");
+        assert_eq!(
+            reply["error"]["message"],
+            format!("request line longer than {} bytes", MAX_REQUEST_LINE)
+        );
+        assert!(client.lines.next_line().await.unwrap().is_none());
+
+        server.abort();
+        let _ = server.await;
+    }
+}
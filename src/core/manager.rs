@@ -1,169 +1,1845 @@
+use crate::core::async_db::AsyncDatabase;
+use crate::core::clipboard::{self, SelfWrites};
+use crate::core::config::{Config, SelfWritePolicy};
 use crate::core::database::Database;
-use crate::core::types::{AppCommand, ClipboardItem, ClipboardType};
-use enigo::{Enigo, Key, Keyboard, Settings, Direction};
+use crate::core::fuzzy;
+use crate::core::merge::{self, MergeOrder, MergeSeparator};
+use crate::core::paste::{self, ArboardClipboard, ClipboardSnapshot, EnigoInjector, Injector, SystemClipboard};
+use crate::core::transform::{self, Transform};
+use crate::core::types::{
+    AppCommand, ClearFilter, ClearReport, ClipboardItem, ClipboardType, HistoryCursor, HistoryEdit, ManagerEvent,
+    PasteOptions, QueueEntry, QueueOrder, QueueState, SearchHit, SearchResult, Snippet,
+};
 use std::collections::VecDeque;
-use std::sync::Arc;
-use tokio::sync::mpsc;
+use std::fmt;
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+use tokio::sync::{broadcast, mpsc, oneshot};
+use tokio::task::JoinHandle;
 
-// Trait for UI updates
+const PAGE_SIZE: usize = 50;
+/// Ranked search results are computed once, up to this many, then paged from memory.
+const SEARCH_LIMIT: usize = 500;
+/// Pages released per vacuum step; the write lock is dropped between steps.
+const VACUUM_STEP_PAGES: u32 = 64;
+/// Oldest undo entries are dropped beyond this.
+const UNDO_STACK_LIMIT: usize = 20;
+const PURGE_INTERVAL: Duration = Duration::from_secs(60 * 60);
+/// Events a subscriber can fall behind by before it starts missing them.
+const EVENT_CAPACITY: usize = 256;
+
+/// Where a command's outcome goes when the sender waits for it.
+pub type CommandReply = oneshot::Sender<Result<(), ManagerError>>;
+
+/// A command for the manager. Without a reply channel, a failure is shown
+/// to the user as a notification instead.
+#[derive(Debug)]
+pub struct CommandRequest {
+    pub command: AppCommand,
+    pub reply: Option<CommandReply>,
+}
+
+impl CommandRequest {
+    /// The command along with the receiver its outcome arrives on.
+    pub fn with_reply(command: AppCommand) -> (Self, oneshot::Receiver<Result<(), ManagerError>>) {
+        let (reply, outcome) = oneshot::channel();
+        (Self { command, reply: Some(reply) }, outcome)
+    }
+}
+
+impl From<AppCommand> for CommandRequest {
+    fn from(command: AppCommand) -> Self {
+        Self { command, reply: None }
+    }
+}
+
+/// Why a command failed.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ManagerError {
+    ItemNotFound(i64),
+    SnippetNotFound(i64),
+    /// The command doesn't apply to what it was given, e.g. editing an image.
+    /// The message is shown as is.
+    Invalid(String),
+    Database(String),
+    /// The clipboard couldn't be opened or written, e.g. because an image
+    /// file is gone.
+    Clipboard(String),
+    /// Sending the paste keystrokes or running the paste command failed.
+    Paste(String),
+    /// The manager stopped before the command ran.
+    Stopped,
+}
+
+impl fmt::Display for ManagerError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::ItemNotFound(id) => write!(f, "history item {} not found", id),
+            Self::SnippetNotFound(id) => write!(f, "snippet {} not found", id),
+            Self::Invalid(msg) => f.write_str(msg),
+            Self::Database(msg) => write!(f, "Database error: {}", msg),
+            Self::Clipboard(msg) => write!(f, "Clipboard write failed: {}", msg),
+            Self::Paste(msg) => write!(f, "Paste failed: {}", msg),
+            Self::Stopped => f.write_str("picaclip is shutting down"),
+        }
+    }
+}
+
+impl std::error::Error for ManagerError {}
+
+/// Errors out of `AsyncDatabase::call` are database failures.
+impl From<anyhow::Error> for ManagerError {
+    fn from(error: anyhow::Error) -> Self {
+        Self::Database(format!("{:#}", error))
+    }
+}
+
+// Trait for UI updates. Changes to single items and to the queue are not
+// pushed here; a window subscribes to `Manager::events` and applies them to
+// the rows it shows.
 pub trait UiHandle: Send + Sync {
     fn update_history(&self, items: Vec<ClipboardItem>);
+    /// Adds the next page below the rows already shown.
+    fn append_history(&self, items: Vec<ClipboardItem>);
     fn update_search_results(&self, items: Vec<ClipboardItem>);
+    fn update_unified_results(&self, results: Vec<SearchResult>);
+    fn append_unified_results(&self, results: Vec<SearchResult>);
+    /// Offers to undo the change described by `msg` for the given time.
+    fn show_undo(&self, msg: String, window: Duration);
     fn hide_window(&self);
     fn show_notification(&self, msg: String);
 }
 
+/// What the list currently shows, and where the next page starts.
+enum ListView {
+    History {
+        next: Option<HistoryCursor>,
+        loaded: usize,
+    },
+    Search {
+        remaining: VecDeque<SearchResult>,
+    },
+}
+
+/// Results of work spawned off the manager loop.
+enum TaskResult {
+    /// `generation` is compared against the latest search so stale results are dropped.
+    Search {
+        generation: u64,
+        results: anyhow::Result<Vec<SearchResult>>,
+    },
+    Vacuumed(anyhow::Result<()>),
+    Purged(anyhow::Result<usize>),
+}
+
+/// A change that can still be reverted.
+enum UndoAction {
+    /// History rows moved to the trash as one batch, by a delete or a clear.
+    Delete { batch: i64 },
+    /// Unpinned from this place on the pinned board.
+    Unpin { id: i64, pin_order: Option<i64> },
+    Edit(HistoryEdit),
+}
+
+struct UndoEntry {
+    action: UndoAction,
+    expires: Instant,
+}
+
+/// A clipboard restore waiting for its delay to pass.
+struct PendingRestore {
+    snapshot: Arc<ClipboardSnapshot>,
+    task: JoinHandle<()>,
+}
+
 pub struct Manager {
-    db: Database,
+    db: AsyncDatabase,
     clipboard_rx: mpsc::Receiver<ClipboardItem>,
-    command_rx: mpsc::Receiver<AppCommand>,
+    command_rx: mpsc::Receiver<CommandRequest>,
+    task_tx: mpsc::Sender<TaskResult>,
+    task_rx: mpsc::Receiver<TaskResult>,
     ui: Arc<dyn UiHandle>,
-    paste_queue: VecDeque<ClipboardItem>,
+    events: broadcast::Sender<ManagerEvent>,
+    config: Arc<Config>,
+    view: ListView,
+    /// Bumped by every search and history reload.
+    search_generation: u64,
+    is_vacuuming: bool,
+    /// Clipboard changes are dropped while set; explicit adds still count.
+    is_paused: bool,
+    /// Queue mode and order are persisted in the settings table; the
+    /// entries themselves live in `paste_queue`.
     is_queue_mode: bool,
-    enigo: Enigo,
+    queue_order: QueueOrder,
+    injector: Box<dyn Injector>,
+    /// Shared with pending restores, which write to it later.
+    clipboard: Arc<Mutex<Box<dyn SystemClipboard>>>,
+    /// Shared with the clipboard monitor.
+    self_writes: SelfWrites,
+    pending_restore: Option<PendingRestore>,
+    undo_stack: Vec<UndoEntry>,
 }
 
 impl Manager {
     pub fn new(
         db: Database,
         clipboard_rx: mpsc::Receiver<ClipboardItem>,
-        command_rx: mpsc::Receiver<AppCommand>,
+        command_rx: mpsc::Receiver<CommandRequest>,
         ui: Arc<dyn UiHandle>,
+        config: Config,
+        self_writes: SelfWrites,
     ) -> Self {
+        let (task_tx, task_rx) = mpsc::channel(16);
+        let (events, _) = broadcast::channel(EVENT_CAPACITY);
         Self {
-            db,
+            db: AsyncDatabase::new(db),
             clipboard_rx,
             command_rx,
+            task_tx,
+            task_rx,
             ui,
-            paste_queue: VecDeque::new(),
+            events,
+            config: Arc::new(config),
+            view: ListView::History { next: None, loaded: 0 },
+            search_generation: 0,
+            is_vacuuming: false,
+            is_paused: false,
             is_queue_mode: false,
-            enigo: Enigo::new(&Settings::default()).expect("Failed to init enigo"),
+            queue_order: QueueOrder::default(),
+            injector: Box::new(EnigoInjector::default()),
+            clipboard: Arc::new(Mutex::new(Box::new(ArboardClipboard::default()))),
+            self_writes,
+            pending_restore: None,
+            undo_stack: Vec::new(),
         }
     }
 
+    /// Replaces the enigo-backed input injection, e.g. with a fake in tests.
+    pub fn with_injector(mut self, injector: Box<dyn Injector>) -> Self {
+        self.injector = injector;
+        self
+    }
+
+    /// Replaces the arboard-backed clipboard, e.g. with a fake in tests.
+    pub fn with_clipboard(mut self, clipboard: Box<dyn SystemClipboard>) -> Self {
+        self.clipboard = Arc::new(Mutex::new(clipboard));
+        self
+    }
+
+    /// The channel every change is published on; `subscribe` to it, before
+    /// `run` to also get the initial queue. Sending with no subscribers is
+    /// not an error, so the manager never waits on them.
+    pub fn events(&self) -> broadcast::Sender<ManagerEvent> {
+        self.events.clone()
+    }
+
     pub async fn run(mut self) {
         log::info!("Starting Core Manager");
 
-        // Load initial history
-        if let Ok(items) = self.db.get_recent_history(50, 0) {
-            self.ui.update_history(items);
-        }
+        // Load initial history and the queue left from the last run
+        self.refresh_history().await;
+        self.load_queue_settings().await;
+        self.report_queue().await;
+
+        // First tick fires immediately, purging what expired while we were closed
+        let mut purge_timer = tokio::time::interval(PURGE_INTERVAL);
 
         loop {
             tokio::select! {
                 Some(item) = self.clipboard_rx.recv() => {
-                    self.handle_clipboard_update(item).await;
+                    if self.is_paused {
+                        continue;
+                    }
+                    if let Err(e) = self.handle_clipboard_update(item).await {
+                        log::error!("Failed to save history: {}", e);
+                    }
                 }
-                Some(cmd) = self.command_rx.recv() => {
-                    if let AppCommand::Exit = cmd {
+                Some(CommandRequest { command, reply }) = self.command_rx.recv() => {
+                    if let AppCommand::Exit = command {
+                        self.finish_restore().await;
                         break;
                     }
-                    self.handle_command(cmd).await;
+                    let result = self.handle_command(command).await;
+                    match reply {
+                        Some(reply) => {
+                            // The sender may have stopped waiting
+                            let _ = reply.send(result);
+                        }
+                        None => {
+                            if let Err(e) = result {
+                                log::error!("Command failed: {}", e);
+                                self.ui.show_notification(e.to_string());
+                            }
+                        }
+                    }
+                }
+                Some(result) = self.task_rx.recv() => {
+                    self.handle_task_result(result);
+                }
+                _ = purge_timer.tick() => {
+                    self.spawn_purge();
                 }
             }
         }
     }
 
-    async fn handle_clipboard_update(&mut self, item: ClipboardItem) {
-        if self.is_queue_mode {
-            if let Ok(_id) = self.db.insert_history(&item) {
-                 self.paste_queue.push_back(item.clone());
-                 self.ui.show_notification(format!("Added to queue. Size: {}", self.paste_queue.len()));
-
-                 self.refresh_history();
-            }
+    async fn handle_clipboard_update(&mut self, item: ClipboardItem) -> Result<(), ManagerError> {
+        let stored = item.clone();
+        let id = self.db.call(move |db| db.insert_history(&stored)).await?;
+        let enqueued = if self.is_queue_mode {
+            let queued = ClipboardItem { id: Some(id), ..item };
+            let enqueued = self.db.call(move |db| db.enqueue(&queued)).await.map(|_| ());
+            self.report_queue().await;
+            enqueued
         } else {
-             if let Err(e) = self.db.insert_history(&item) {
-                 log::error!("Failed to save history: {}", e);
-             } else {
-                 self.refresh_history();
-             }
-        }
+            Ok(())
+        };
+        self.show_captured(id).await;
+        enqueued.map_err(ManagerError::from)
     }
 
-    async fn handle_command(&mut self, cmd: AppCommand) {
+    async fn handle_command(&mut self, cmd: AppCommand) -> Result<(), ManagerError> {
         match cmd {
-            AppCommand::PasteItem(id) => {
-                if let Ok(Some(item)) = self.db.get_item_by_id(id) {
-                     self.perform_paste(item);
+            AppCommand::PasteItem(id, options) => {
+                let item = self.item(id).await?;
+                self.perform_paste(item, options).await?;
+                self.apply_self_write_policy(id).await?;
+            }
+            AppCommand::CopyItem(id, options) => {
+                let item = self.item(id).await?;
+                self.copy_item(&item, options).await?;
+                self.apply_self_write_policy(id).await?;
+            }
+            AppCommand::AddText(text) => {
+                let item = clipboard::captured_text(&text)
+                    .ok_or_else(|| ManagerError::Invalid("Nothing to add: text is empty".to_string()))?;
+                self.handle_clipboard_update(item).await?
+            }
+            AppCommand::PasteSnippet(id, options) => {
+                let snippet = self.use_snippet(id).await?;
+                self.perform_paste(text_item(snippet.content), options).await?;
+            }
+            AppCommand::CopySnippet(id, options) => {
+                let snippet = self.use_snippet(id).await?;
+                self.copy_item(&text_item(snippet.content), options).await?;
+            }
+            AppCommand::PasteTransformed(id, transforms) => {
+                let text = self.transformed_text(id, transforms).await?;
+                self.perform_paste(text_item(text), PasteOptions::default()).await?;
+                self.apply_self_write_policy(id).await?;
+            }
+            AppCommand::SaveTransformed(id, transforms) => {
+                let item = text_item(self.transformed_text(id, transforms).await?);
+                let id = self.db.call(move |db| db.insert_history(&item)).await?;
+                self.show_captured(id).await;
+            }
+            AppCommand::PasteMerged { ids, separator, order } => {
+                let text = self.merged_text(ids.clone(), separator, order).await?;
+                self.perform_paste(text_item(text), PasteOptions::default()).await?;
+                for id in ids {
+                    self.apply_self_write_policy(id).await?;
                 }
             }
+            AppCommand::MergeItems { ids, separator, order } => {
+                let item = text_item(self.merged_text(ids, separator, order).await?);
+                let id = self.db.call(move |db| db.insert_history(&item)).await?;
+                self.show_captured(id).await;
+            }
             AppCommand::DeleteHistory(id) => {
-                let _ = self.db.delete_history(id);
-                self.refresh_history();
+                let (batch, moved) = self.db.call(move |db| db.trash_history(&[id])).await?;
+                if moved == 0 {
+                    return Err(ManagerError::ItemNotFound(id));
+                }
+                self.push_undo(UndoAction::Delete { batch }, "Item deleted".to_string());
+                self.emit(ManagerEvent::ItemDeleted { ids: vec![id] });
+            }
+            AppCommand::ClearHistory(filter) => {
+                let report = self.db.call(move |db| db.clear_history(&filter)).await?;
+                self.report_clear(report);
+            }
+            AppCommand::ClearSearch(query) => {
+                // Exact matches only; a destructive clear must not reach typo matches
+                let report = self
+                    .db
+                    .call(move |db| {
+                        let matches = db.search_history_page(&query, None, SEARCH_LIMIT)?.items;
+                        db.clear_history(&ClearFilter::Items(matches.into_iter().filter_map(|item| item.id).collect()))
+                    })
+                    .await?;
+                self.report_clear(report);
+            }
+            AppCommand::EmptyTrash => {
+                let purged = self.db.call(|db| db.purge_trash(i64::MAX)).await?;
+                // Nothing left to restore
+                self.undo_stack.retain(|entry| !matches!(entry.action, UndoAction::Delete { .. }));
+                self.ui.show_notification(format!("Trash emptied: {}", items(purged)));
+            }
+            AppCommand::EditItem(id, content) => self.edit_item(id, content).await?,
+            AppCommand::RevertEdit(id) => {
+                self.item(id).await?;
+                match self.db.call(move |db| db.original_content(id)).await? {
+                    Some(original) => self.edit_item(id, original).await?,
+                    None => self.ui.show_notification("Item has not been edited".to_string()),
+                }
             }
             AppCommand::TogglePin(id) => {
-                let _ = self.db.toggle_pin(id);
-                self.refresh_history();
+                let pinned = !self.item(id).await?.is_pinned;
+                self.set_pinned(id, pinned).await?;
             }
+            AppCommand::SetPinned(id, pinned) => self.set_pinned(id, pinned).await?,
+            AppCommand::MovePinned(id, to) => {
+                if !self.item(id).await?.is_pinned {
+                    return Err(ManagerError::Invalid(format!("Move failed: item {} is not pinned", id)));
+                }
+                self.db.call(move |db| db.move_pinned(id, to)).await?;
+                // The whole board is renumbered
+                let board = self.db.call(|db| db.get_pinned_board()).await?;
+                board.into_iter().filter_map(pin_change).for_each(|event| self.emit(event));
+            }
+            AppCommand::Undo => self.undo().await?,
             AppCommand::Search(query) => {
                 if query.is_empty() {
-                    self.refresh_history();
+                    self.refresh_history().await;
                 } else {
-                    if let Ok(results) = self.db.search_history(&query) {
-                        self.ui.update_search_results(results);
-                    }
+                    self.spawn_search(query);
                 }
             }
-            AppCommand::ToggleQueueMode(enabled) => {
-                self.is_queue_mode = enabled;
-                if !enabled {
-                    self.paste_queue.clear();
+            AppCommand::SetPaused(paused) => {
+                if paused != self.is_paused {
+                    self.is_paused = paused;
+                    let msg = if paused { "Capture paused" } else { "Capture resumed" };
+                    self.ui.show_notification(msg.to_string());
+                    self.emit(ManagerEvent::Paused { paused });
                 }
+            }
+            AppCommand::ToggleQueueMode(enabled) => {
+                // Leaving queue mode keeps the entries for next time
+                self.set_queue_mode(enabled).await?;
                 self.ui.show_notification(format!("Queue Mode: {}", enabled));
+                self.report_queue().await;
             }
             AppCommand::AddSnippet(snip) => {
-                let _ = self.db.add_snippet(&snip);
+                self.db.call(move |db| db.add_snippet(&snip)).await?;
             }
-            AppCommand::NextQueueItem => {
-                if let Some(item) = self.paste_queue.pop_front() {
-                    self.perform_paste(item);
-                    self.ui.show_notification(format!("Queue Size: {}", self.paste_queue.len()));
-                } else {
-                    self.ui.show_notification("Queue empty".to_string());
+            AppCommand::NextQueueItem => self.paste_next_queued().await?,
+            AppCommand::MoveQueueEntry(id, index) => {
+                self.db.call(move |db| db.move_queue_entry(id, index)).await?;
+                self.report_queue().await;
+            }
+            AppCommand::RemoveQueueEntry(id) => {
+                self.db.call(move |db| db.remove_queue_entry(id)).await?;
+                self.end_queue_mode_if_drained().await?;
+                self.report_queue().await;
+            }
+            AppCommand::SetQueueOrder(order) => {
+                self.queue_order = order;
+                let value = serde_json::to_string(&order).unwrap_or_default();
+                self.db.call(move |db| db.set_setting("queue.order", &value)).await?;
+                self.report_queue().await;
+            }
+            AppCommand::PasteQueueAll(separator) => self.paste_queue_all(separator).await?,
+            AppCommand::ClearQueue => {
+                self.db.call(|db| db.clear_queue()).await?;
+                self.end_queue_mode_if_drained().await?;
+                self.report_queue().await;
+            }
+            AppCommand::EnqueueHistory(ids) => {
+                let mut items = Vec::with_capacity(ids.len());
+                for id in ids {
+                    items.push(self.item(id).await?);
+                }
+                self.enqueue_items(items).await?;
+            }
+            AppCommand::EnqueueSnippets(ids) => {
+                let mut snippets = Vec::with_capacity(ids.len());
+                for id in ids {
+                    snippets.push(self.snippet(id).await?);
+                }
+                if snippets.iter().any(|snippet| snippet.is_masked) {
+                    return Err(ManagerError::Invalid("Could not queue: masked snippets can't be queued".to_string()));
                 }
+                self.enqueue_items(snippets.into_iter().map(|snippet| text_item(snippet.content)).collect())
+                    .await?;
             }
+            AppCommand::EnqueueSearch(query) => {
+                let config = self.config.clone();
+                let items = self
+                    .db
+                    .call(move |db| {
+                        let items = Self::search(db, &config, &query)?
+                            .into_iter()
+                            .take(PAGE_SIZE)
+                            .filter_map(|result| match result.hit {
+                                SearchHit::History(item) => Some(item),
+                                SearchHit::Snippet(snippet) if !snippet.is_masked => Some(text_item(snippet.content)),
+                                _ => None,
+                            })
+                            .collect();
+                        Ok(items)
+                    })
+                    .await?;
+                self.enqueue_items(items).await?;
+            }
+            AppCommand::EnqueueLines(id) => {
+                let item = self.item(id).await?;
+                if item.type_ != ClipboardType::Text {
+                    return Err(ManagerError::Invalid(
+                        "Could not queue: only text items can be split into lines".to_string(),
+                    ));
+                }
+                let items = item
+                    .content
+                    .lines()
+                    .filter(|line| !line.trim().is_empty())
+                    .map(|line| text_item(line.to_string()))
+                    .collect();
+                self.enqueue_items(items).await?;
+            }
+            AppCommand::LoadMore => self.load_more().await,
+            AppCommand::Vacuum => self.spawn_vacuum(),
             AppCommand::Exit => {}
         }
+        Ok(())
+    }
+
+    async fn set_pinned(&mut self, id: i64, pinned: bool) -> Result<(), ManagerError> {
+        let item = self.item(id).await?;
+        if item.is_pinned == pinned {
+            return Ok(());
+        }
+        self.db.call(move |db| db.set_pinned(id, pinned)).await?;
+        if !pinned {
+            let pin_order = item.pin_order;
+            self.push_undo(UndoAction::Unpin { id, pin_order }, "Item unpinned".to_string());
+        }
+        self.report_pins(vec![id]).await;
+        Ok(())
+    }
+
+    /// Offers to undo a bulk clear and tells windows which rows went.
+    fn report_clear(&mut self, report: ClearReport) {
+        if report.items == 0 {
+            self.ui.show_notification("Nothing to clear".to_string());
+            return;
+        }
+        let msg = format!("Cleared {}", items(report.items));
+        self.push_undo(UndoAction::Delete { batch: report.batch }, msg);
+        self.emit(ManagerEvent::ItemDeleted { ids: report.ids });
+    }
+
+    /// A history item the command needs.
+    async fn item(&self, id: i64) -> Result<ClipboardItem, ManagerError> {
+        self.db
+            .call(move |db| db.get_item_by_id(id))
+            .await?
+            .ok_or(ManagerError::ItemNotFound(id))
+    }
+
+    /// A snippet the command needs, without counting a use.
+    async fn snippet(&self, id: i64) -> Result<Snippet, ManagerError> {
+        self.db
+            .call(move |db| db.get_snippet_by_id(id))
+            .await?
+            .ok_or(ManagerError::SnippetNotFound(id))
+    }
+
+    async fn load_queue_settings(&mut self) {
+        match self.db.call(|db| db.get_queue_state()).await {
+            Ok(state) => {
+                self.is_queue_mode = state.active;
+                self.queue_order = state.order;
+            }
+            Err(e) => log::error!("Failed to load queue settings: {}", e),
+        }
+    }
+
+    async fn set_queue_mode(&mut self, enabled: bool) -> Result<(), ManagerError> {
+        self.is_queue_mode = enabled;
+        let value = enabled.to_string();
+        self.db.call(move |db| db.set_setting("queue.active", &value)).await?;
+        Ok(())
+    }
+
+    /// Queue mode ends once the queue is empty, however it got there.
+    async fn end_queue_mode_if_drained(&mut self) -> Result<(), ManagerError> {
+        if self.is_queue_mode && self.db.call(|db| db.get_queue()).await?.is_empty() {
+            self.set_queue_mode(false).await?;
+        }
+        Ok(())
+    }
+
+    /// Appends `items` to the queue, turning queue mode on, and reports the result.
+    async fn enqueue_items(&mut self, items: Vec<ClipboardItem>) -> Result<(), ManagerError> {
+        match self.db.call(move |db| db.enqueue_many(&items)).await? {
+            0 => self.ui.show_notification("Nothing to queue".to_string()),
+            added => {
+                if !self.is_queue_mode {
+                    self.set_queue_mode(true).await?;
+                }
+                let size = self.db.call(|db| db.get_queue()).await.map(|q| q.len()).unwrap_or(added);
+                self.ui.show_notification(format!("Queued {} items. Size: {}", added, size));
+            }
+        }
+        self.report_queue().await;
+        Ok(())
+    }
+
+    async fn report_queue(&self) {
+        match self.db.call(|db| db.get_queue()).await {
+            Ok(entries) => {
+                let state = QueueState {
+                    active: self.is_queue_mode,
+                    order: self.queue_order,
+                    entries,
+                };
+                self.emit(ManagerEvent::QueueChanged { state });
+            }
+            Err(e) => log::error!("Failed to load queue: {}", e),
+        }
+    }
+
+    /// Pastes the next entry; queue mode ends once the queue is drained.
+    async fn paste_next_queued(&mut self) -> Result<(), ManagerError> {
+        let order = self.queue_order;
+        let Some(entry) = self.db.call(move |db| db.pop_queue(order)).await? else {
+            self.ui.show_notification("Queue empty".to_string());
+            return Ok(());
+        };
+
+        // The entry is off the queue either way
+        let pasted = self.paste_queue_entry(entry).await;
+        let remaining = self.db.call(|db| db.get_queue()).await?.len();
+        if remaining == 0 {
+            self.set_queue_mode(false).await?;
+            self.ui.show_notification("Queue finished".to_string());
+        } else {
+            self.ui.show_notification(format!("Queue Size: {}", remaining));
+        }
+        self.report_queue().await;
+        pasted
+    }
+
+    async fn paste_queue_entry(&mut self, entry: QueueEntry) -> Result<(), ManagerError> {
+        let history_id = entry.history_id;
+        let item = ClipboardItem {
+            id: history_id,
+            type_: entry.type_,
+            content_hash: clipboard::content_hash(entry.content.as_bytes()),
+            content: entry.content,
+            source_app: None,
+            created_at: chrono::Utc::now().timestamp(),
+            is_pinned: false,
+            edited_at: None,
+            pin_order: None,
+            tags: Vec::new(),
+        };
+        self.perform_paste(item, PasteOptions::default()).await?;
+        if let Some(id) = history_id {
+            self.apply_self_write_policy(id).await?;
+        }
+        Ok(())
+    }
+
+    /// Pastes all queued text in paste order as one block, then ends queue mode.
+    async fn paste_queue_all(&mut self, separator: String) -> Result<(), ManagerError> {
+        let mut entries = self.db.call(|db| db.get_queue()).await?;
+        if entries.is_empty() {
+            self.ui.show_notification("Queue empty".to_string());
+            return Ok(());
+        }
+        if entries.iter().any(|entry| entry.type_ != ClipboardType::Text) {
+            return Err(ManagerError::Invalid(
+                "Queue holds images or files; paste them one at a time".to_string(),
+            ));
+        }
+        if self.queue_order == QueueOrder::Lifo {
+            entries.reverse();
+        }
+
+        let text = entries
+            .iter()
+            .map(|entry| entry.content.as_str())
+            .collect::<Vec<_>>()
+            .join(&separator);
+        self.perform_paste(text_item(text), PasteOptions::default()).await?;
+        for id in entries.iter().filter_map(|entry| entry.history_id) {
+            self.apply_self_write_policy(id).await?;
+        }
+
+        self.db.call(|db| db.clear_queue()).await?;
+        self.set_queue_mode(false).await?;
+        self.ui.show_notification("Queue finished".to_string());
+        self.report_queue().await;
+        Ok(())
+    }
+
+    /// Counts a successful paste of history item `id`, then applies the
+    /// configured effect on its place in history.
+    async fn apply_self_write_policy(&mut self, id: i64) -> Result<(), ManagerError> {
+        self.db.call(move |db| db.record_paste(id)).await?;
+        if self.config.paste.self_write_policy == SelfWritePolicy::Bump {
+            self.db.call(move |db| db.bump_history(id)).await?;
+            self.show_captured(id).await;
+        }
+        Ok(())
+    }
+
+    async fn merged_text(
+        &self,
+        ids: Vec<i64>,
+        separator: MergeSeparator,
+        order: MergeOrder,
+    ) -> Result<String, ManagerError> {
+        let mut items = Vec::new();
+        for id in ids {
+            items.push(self.item(id).await?);
+        }
+        merge::merge(items, &separator, order).map_err(|e| ManagerError::Invalid(format!("Merge failed: {:#}", e)))
+    }
+
+    /// Runs a text item through `transforms`. Images and files are refused.
+    async fn transformed_text(&self, id: i64, transforms: Vec<Transform>) -> Result<String, ManagerError> {
+        let item = self.item(id).await?;
+        if item.type_ != ClipboardType::Text {
+            return Err(ManagerError::Invalid(
+                "Transform failed: only text items can be transformed".to_string(),
+            ));
+        }
+        transform::apply_all(&transforms, &item.content)
+            .map_err(|e| ManagerError::Invalid(format!("Transform failed: {:#}", e)))
+    }
+
+    fn handle_task_result(&mut self, result: TaskResult) {
+        match result {
+            TaskResult::Search { generation, results } => {
+                if generation != self.search_generation {
+                    return;
+                }
+                match results {
+                    Ok(results) => {
+                        let mut remaining = VecDeque::from(results);
+                        let first = remaining.drain(..PAGE_SIZE.min(remaining.len())).collect();
+                        self.ui.update_unified_results(first);
+                        self.view = ListView::Search { remaining };
+                    }
+                    Err(e) => log::error!("Search failed: {}", e),
+                }
+            }
+            TaskResult::Purged(result) => match result {
+                Ok(0) => {}
+                Ok(purged) => log::info!("Purged {} items from the trash", purged),
+                Err(e) => log::error!("Failed to purge trash: {}", e),
+            },
+            TaskResult::Vacuumed(result) => {
+                self.is_vacuuming = false;
+                match result {
+                    Ok(()) => self.ui.show_notification("Database compacted".to_string()),
+                    Err(e) => log::error!("Vacuum failed: {}", e),
+                }
+            }
+        }
+    }
+
+    fn push_undo(&mut self, action: UndoAction, msg: String) {
+        let window = Duration::from_secs(self.config.history.undo_seconds);
+        self.undo_stack.push(UndoEntry {
+            action,
+            expires: Instant::now() + window,
+        });
+        if self.undo_stack.len() > UNDO_STACK_LIMIT {
+            self.undo_stack.remove(0);
+        }
+        self.ui.show_undo(msg, window);
+    }
+
+    async fn undo(&mut self) -> Result<(), ManagerError> {
+        let now = Instant::now();
+        self.undo_stack.retain(|entry| entry.expires > now);
+        let Some(entry) = self.undo_stack.pop() else {
+            self.ui.show_notification("Nothing to undo".to_string());
+            return Ok(());
+        };
+
+        let result = match entry.action {
+            UndoAction::Delete { batch } => {
+                self.db
+                    .call(move |db| {
+                        let restored = items_by_id(db, &db.restore_trash(batch)?)?;
+                        Ok(restored.into_iter().map(|item| ManagerEvent::ItemAdded { item }).collect())
+                    })
+                    .await
+            }
+            UndoAction::Unpin { id, pin_order } => {
+                self.db
+                    .call(move |db| {
+                        db.restore_pin(id, pin_order)?;
+                        pin_changes(db, &[id])
+                    })
+                    .await
+            }
+            UndoAction::Edit(edit) => {
+                self.db
+                    .call(move |db| {
+                        db.revert_edit(&edit)?;
+                        let edited = items_by_id(db, &[edit.id])?;
+                        let replaced = items_by_id(db, edit.replaced_id.as_slice())?;
+                        let edited = edited.into_iter().map(|item| ManagerEvent::ItemUpdated { item });
+                        Ok(edited.chain(replaced.into_iter().map(|item| ManagerEvent::ItemAdded { item })).collect())
+                    })
+                    .await
+            }
+        };
+        for event in result? {
+            self.emit(event);
+        }
+        self.ui.show_notification("Undone".to_string());
+        Ok(())
+    }
+
+    async fn edit_item(&mut self, id: i64, content: String) -> Result<(), ManagerError> {
+        if self.item(id).await?.type_ != ClipboardType::Text {
+            return Err(ManagerError::Invalid(format!(
+                "Edit failed: item {} is not text; only text items can be edited",
+                id
+            )));
+        }
+        // Stored the way a capture of the same text would be, so they dedup
+        let Some(edited) = clipboard::captured_text(&content) else {
+            return Err(ManagerError::Invalid("Edit failed: content is empty".to_string()));
+        };
+        let (content, hash) = (edited.content, edited.content_hash);
+        let Some(edit) = self.db.call(move |db| db.edit_history(id, &content, &hash)).await? else {
+            return Ok(());
+        };
+
+        let replaced = edit.replaced_id;
+        self.push_undo(UndoAction::Edit(edit), "Item edited".to_string());
+        let item = self.item(id).await?;
+        if let Some(replaced) = replaced {
+            self.emit(ManagerEvent::ItemDeleted { ids: vec![replaced] });
+        }
+        self.emit(ManagerEvent::ItemUpdated { item });
+        Ok(())
+    }
+
+    /// Empties the trash of items past the retention time, in the background.
+    fn spawn_purge(&self) {
+        let history = &self.config.history;
+        let keep = (history.trash_retention_hours * 3600).max(history.undo_seconds);
+        let before = chrono::Utc::now().timestamp() - keep as i64;
+        let db = self.db.clone();
+        let task_tx = self.task_tx.clone();
+        tokio::spawn(async move {
+            let result = db.call(move |db| db.purge_trash(before)).await;
+            let _ = task_tx.send(TaskResult::Purged(result)).await;
+        });
+    }
+
+    /// Runs the search in the background; the loop keeps handling captures and
+    /// commands, and only the latest search's results are shown.
+    fn spawn_search(&mut self, query: String) {
+        self.search_generation += 1;
+        let generation = self.search_generation;
+        let db = self.db.clone();
+        let config = self.config.clone();
+        let task_tx = self.task_tx.clone();
+        tokio::spawn(async move {
+            let results = db.call(move |db| Self::search(db, &config, &query)).await;
+            let _ = task_tx.send(TaskResult::Search { generation, results }).await;
+        });
+    }
+
+    fn search(db: &Database, config: &Config, query: &str) -> anyhow::Result<Vec<SearchResult>> {
+        if !config.search.fuzzy {
+            return db.search_all(query, SEARCH_LIMIT);
+        }
+
+        let count_pastes = config.paste.self_write_policy == SelfWritePolicy::CountAsUsage;
+        let candidates = db.quick_pick_candidates(query, config.search.recent_candidates, count_pastes)?;
+        let mut results = fuzzy::rank(query, candidates, chrono::Utc::now().timestamp(), &config.ranking);
+        results.truncate(SEARCH_LIMIT);
+        Ok(results)
+    }
+
+    /// Vacuums in small steps so captures can write in between.
+    fn spawn_vacuum(&mut self) {
+        if self.is_vacuuming {
+            return;
+        }
+        self.is_vacuuming = true;
+        let db = self.db.clone();
+        let task_tx = self.task_tx.clone();
+        tokio::spawn(async move {
+            let result = async {
+                while db.call(|db| db.vacuum_step(VACUUM_STEP_PAGES)).await? {
+                    tokio::task::yield_now().await;
+                }
+                Ok(())
+            }
+            .await;
+            let _ = task_tx.send(TaskResult::Vacuumed(result)).await;
+        });
     }
 
-    fn perform_paste(&mut self, item: ClipboardItem) {
+    async fn perform_paste(&mut self, item: ClipboardItem, options: PasteOptions) -> Result<(), ManagerError> {
         self.ui.hide_window();
-        std::thread::sleep(std::time::Duration::from_millis(100));
-
-        if let Ok(mut clipboard) = arboard::Clipboard::new() {
-             match item.type_ {
-                 ClipboardType::Image => {
-                     let path = std::path::Path::new(&item.content);
-                     if let Ok(img) = image::open(path) {
-                         let rgba8 = img.to_rgba8();
-                         let img_data = arboard::ImageData {
-                             width: rgba8.width() as usize,
-                             height: rgba8.height() as usize,
-                             bytes: std::borrow::Cow::Owned(rgba8.into_vec()),
-                         };
-                         let _ = clipboard.set_image(img_data);
-                         log::info!("Pasted image from {}", item.content);
-                     } else {
-                         log::error!("Failed to load image from {}", item.content);
-                     }
-                 },
-                 _ => {
-                     let _ = clipboard.set_text(item.content.clone());
-                 }
-             }
-        }
-
-        log::info!("Simulating paste input");
-
-        let _ = self.enigo.key(Key::Control, Direction::Press);
-        let _ = self.enigo.key(Key::Unicode('v'), Direction::Click);
-        let _ = self.enigo.key(Key::Control, Direction::Release);
-    }
-
-    fn refresh_history(&self) {
-        if let Ok(items) = self.db.get_recent_history(50, 0) {
-             self.ui.update_history(items);
+        tokio::time::sleep(Duration::from_millis(100)).await;
+
+        // The window is hidden by now, so this is the paste target
+        let app = self.injector.active_app().await;
+        let strategy = self.config.paste.strategy_for(app.as_deref());
+
+        let payload = paste::payload(&item, &options);
+        // A paste before the last restore ran keeps the older snapshot,
+        // which holds what the user actually had copied
+        let kept = match self.config.paste.restore_clipboard {
+            true => Some(self.pending_restore.take().map(|pending| {
+                pending.task.abort();
+                pending.snapshot
+            })),
+            false => None,
+        };
+        let (clipboard, self_writes, to_write) = (self.clipboard.clone(), self.self_writes.clone(), payload.clone());
+        let (restore, written) = tokio::task::spawn_blocking(move || {
+            let mut clipboard = clipboard.lock().unwrap();
+            let restore =
+                kept.map(|kept| kept.unwrap_or_else(|| Arc::new(ClipboardSnapshot::capture(clipboard.as_mut()))));
+            (restore, paste::write_payload(clipboard.as_mut(), &to_write, &self_writes))
+        })
+        .await
+        .map_err(|e| ManagerError::Clipboard(format!("Clipboard task failed: {}", e)))?;
+
+        let result = match written {
+            // Pasting now would paste whatever was on the clipboard before
+            Err(e) if paste::uses_clipboard(strategy, &payload) => Err(ManagerError::Clipboard(format!("{:#}", e))),
+            written => {
+                if let Err(e) = written {
+                    log::warn!("Failed to write clipboard: {:#}", e);
+                }
+                log::info!("Pasting into {:?} with {:?}", app, strategy);
+                paste::inject(self.injector.as_mut(), strategy, &payload)
+                    .await
+                    .map_err(|e| ManagerError::Paste(format!("{:#}", e)))
+            }
+        };
+
+        if let Some(snapshot) = restore {
+            self.schedule_restore(snapshot);
+        }
+        result
+    }
+
+    /// Fetches a snippet that is about to be used, counting the use.
+    async fn use_snippet(&self, id: i64) -> Result<Snippet, ManagerError> {
+        self.db
+            .call(move |db| {
+                let snippet = db.get_snippet_by_id(id)?;
+                if snippet.is_some() {
+                    db.increment_snippet_usage(id)?;
+                }
+                Ok(snippet)
+            })
+            .await?
+            .ok_or(ManagerError::SnippetNotFound(id))
+    }
+
+    /// Writes an item to the clipboard for the user to paste themselves.
+    async fn copy_item(&mut self, item: &ClipboardItem, options: PasteOptions) -> Result<(), ManagerError> {
+        // A pending restore would put the old clipboard back over this copy
+        if let Some(pending) = self.pending_restore.take() {
+            pending.task.abort();
+        }
+        let payload = paste::payload(item, &options);
+        let (clipboard, self_writes) = (self.clipboard.clone(), self.self_writes.clone());
+        tokio::task::spawn_blocking(move || {
+            let mut clipboard = clipboard.lock().unwrap();
+            paste::write_payload(clipboard.as_mut(), &payload, &self_writes)
+        })
+        .await
+        .map_err(|e| ManagerError::Clipboard(format!("Clipboard task failed: {}", e)))?
+        .map_err(|e| ManagerError::Clipboard(format!("{:#}", e)))
+    }
+
+    fn schedule_restore(&mut self, snapshot: Arc<ClipboardSnapshot>) {
+        let delay = Duration::from_millis(self.config.paste.restore_delay_ms);
+        let self_writes = self.self_writes.clone();
+        let clipboard = self.clipboard.clone();
+        let restored = snapshot.clone();
+        let task = tokio::spawn(async move {
+            tokio::time::sleep(delay).await;
+            let result = tokio::task::spawn_blocking(move || {
+                let mut clipboard = clipboard.lock().unwrap();
+                restored.restore(clipboard.as_mut(), &self_writes)
+            })
+            .await;
+            match result {
+                Ok(Err(e)) => log::error!("Failed to restore clipboard: {:#}", e),
+                Err(e) => log::error!("Clipboard restore task failed: {}", e),
+                Ok(Ok(())) => {}
+            }
+        });
+        self.pending_restore = Some(PendingRestore { snapshot, task });
+    }
+
+    /// Puts the clipboard back now if a restore is still waiting, so quitting
+    /// right after a paste doesn't leave the pasted content behind.
+    async fn finish_restore(&mut self) {
+        let Some(pending) = self.pending_restore.take() else {
+            return;
+        };
+        if pending.task.is_finished() {
+            return;
+        }
+        pending.task.abort();
+        let (clipboard, self_writes) = (self.clipboard.clone(), self.self_writes.clone());
+        let result = tokio::task::spawn_blocking(move || {
+            let mut clipboard = clipboard.lock().unwrap();
+            pending.snapshot.restore(clipboard.as_mut(), &self_writes)
+        })
+        .await;
+        match result {
+            Ok(Err(e)) => log::error!("Failed to restore clipboard: {:#}", e),
+            Err(e) => log::error!("Clipboard restore task failed: {}", e),
+            Ok(Ok(())) => {}
+        }
+    }
+
+    async fn load_more(&mut self) {
+        match &mut self.view {
+            ListView::History { next, .. } => {
+                let Some(cursor) = *next else {
+                    return;
+                };
+                let page = self.db.call(move |db| db.get_history_page(Some(&cursor), PAGE_SIZE)).await;
+                // A reload may have replaced the view while the page was loading
+                let ListView::History { next, loaded } = &mut self.view else {
+                    return;
+                };
+                if *next != Some(cursor) {
+                    return;
+                }
+                match page {
+                    Ok(page) => {
+                        *loaded += page.items.len();
+                        *next = page.next;
+                        self.ui.append_history(page.items);
+                    }
+                    Err(e) => log::error!("Failed to load history page: {}", e),
+                }
+            }
+            ListView::Search { remaining } => {
+                let page: Vec<SearchResult> = remaining.drain(..PAGE_SIZE.min(remaining.len())).collect();
+                if !page.is_empty() {
+                    self.ui.append_unified_results(page);
+                }
+            }
+        }
+    }
+
+    /// Announces a freshly captured item. Windows put it on top of the rows
+    /// they show, leaving the loaded pages and cursor untouched.
+    async fn show_captured(&mut self, id: i64) {
+        let item = match self.db.call(move |db| db.get_item_by_id(id)).await {
+            Ok(Some(item)) => item,
+            Ok(None) => return,
+            Err(e) => {
+                log::error!("Failed to load captured item: {}", e);
+                return;
+            }
+        };
+        self.emit(ManagerEvent::ItemAdded { item });
+        if let ListView::History { loaded, .. } = &mut self.view {
+            *loaded += 1;
+        }
+    }
+
+    /// Publishes the current pin state of each item.
+    async fn report_pins(&self, ids: Vec<i64>) {
+        match self.db.call(move |db| pin_changes(db, &ids)).await {
+            Ok(events) => events.into_iter().for_each(|event| self.emit(event)),
+            Err(e) => log::error!("Failed to load pinned items: {}", e),
+        }
+    }
+
+    fn emit(&self, event: ManagerEvent) {
+        // No subscribers is fine
+        let _ = self.events.send(event);
+    }
+
+    /// Reloads the history list from the top, keeping as many rows as were loaded.
+    async fn refresh_history(&mut self) {
+        // Any search still running is now stale
+        self.search_generation += 1;
+        let loaded = match self.view {
+            ListView::History { loaded, .. } => loaded.max(PAGE_SIZE),
+            ListView::Search { .. } => PAGE_SIZE,
+        };
+        match self.db.call(move |db| db.get_history_page(None, loaded)).await {
+            Ok(page) => {
+                self.view = ListView::History {
+                    next: page.next,
+                    loaded: page.items.len(),
+                };
+                self.ui.update_history(page.items);
+            }
+            Err(e) => log::error!("Failed to load history: {}", e),
+        }
+    }
+}
+
+/// "1 item", "3 items".
+fn items(count: usize) -> String {
+    format!("{} item{}", count, if count == 1 { "" } else { "s" })
+}
+
+/// The items that still exist, in the order of `ids`.
+fn items_by_id(db: &Database, ids: &[i64]) -> anyhow::Result<Vec<ClipboardItem>> {
+    let mut items = Vec::new();
+    for &id in ids {
+        items.extend(db.get_item_by_id(id)?);
+    }
+    Ok(items)
+}
+
+fn pin_changes(db: &Database, ids: &[i64]) -> anyhow::Result<Vec<ManagerEvent>> {
+    Ok(items_by_id(db, ids)?.into_iter().filter_map(pin_change).collect())
+}
+
+fn pin_change(item: ClipboardItem) -> Option<ManagerEvent> {
+    Some(ManagerEvent::PinChanged {
+        id: item.id?,
+        pinned: item.is_pinned,
+        pin_order: item.pin_order,
+    })
+}
+
+/// A text item that isn't stored yet, for pasting or saving derived text.
+fn text_item(content: String) -> ClipboardItem {
+    ClipboardItem {
+        id: None,
+        type_: ClipboardType::Text,
+        content_hash: clipboard::content_hash(content.as_bytes()),
+        content,
+        source_app: None,
+        created_at: chrono::Utc::now().timestamp(),
+        is_pinned: false,
+        edited_at: None,
+        pin_order: None,
+        tags: Vec::new(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::core::config::{PasteRule, PasteStrategy};
+    use crate::core::paste::{FakeClipboard, FakeContents, FakeInjector, Injected};
+    use crate::core::types::PinMove;
+    use std::sync::Mutex;
+
+    #[derive(Default)]
+    struct FakeUi {
+        notifications: Mutex<Vec<String>>,
+        undo_offers: Mutex<Vec<String>>,
+    }
+
+    impl UiHandle for FakeUi {
+        fn update_history(&self, _items: Vec<ClipboardItem>) {}
+        fn append_history(&self, _items: Vec<ClipboardItem>) {}
+        fn update_search_results(&self, _items: Vec<ClipboardItem>) {}
+        fn update_unified_results(&self, _results: Vec<SearchResult>) {}
+        fn append_unified_results(&self, _results: Vec<SearchResult>) {}
+        fn show_undo(&self, msg: String, _window: Duration) {
+            self.undo_offers.lock().unwrap().push(msg);
+        }
+        fn hide_window(&self) {}
+        fn show_notification(&self, msg: String) {
+            self.notifications.lock().unwrap().push(msg);
+        }
+    }
+
+    /// A manager over the fake injector and clipboard, with the sender for
+    /// its commands; tests subscribe or swap fakes on it before `run`.
+    fn fake_manager(db: Database, config: Config, ui: Arc<FakeUi>) -> (mpsc::Sender<CommandRequest>, Manager) {
+        let (_clipboard_tx, clipboard_rx) = mpsc::channel(1);
+        let (command_tx, command_rx) = mpsc::channel(16);
+        let manager = Manager::new(db, clipboard_rx, command_rx, ui, config, SelfWrites::default())
+            .with_injector(Box::new(FakeInjector::default()))
+            .with_clipboard(Box::new(FakeClipboard::default()));
+        (command_tx, manager)
+    }
+
+    fn drain(events: &mut broadcast::Receiver<ManagerEvent>) -> Vec<ManagerEvent> {
+        std::iter::from_fn(|| events.try_recv().ok()).collect()
+    }
+
+    /// The queue as last published, if it was published at all.
+    fn last_queue(events: &mut broadcast::Receiver<ManagerEvent>) -> Option<QueueState> {
+        drain(events).into_iter().rev().find_map(|event| match event {
+            ManagerEvent::QueueChanged { state } => Some(state),
+            _ => None,
+        })
+    }
+
+    #[tokio::test]
+    async fn test_paste_uses_strategy_for_focused_app() {
+        let db = Database::open_in_memory().unwrap();
+        let id = db.insert_history(&text_item("echo hi".to_string())).unwrap();
+
+        let mut config = Config::default();
+        config.paste.rules.insert(
+            0,
+            PasteRule {
+                app: "Emacs".to_string(),
+                exact: false,
+                strategy: PasteStrategy::TypeText,
+            },
+        );
+        let injector = FakeInjector {
+            app: Some("emacs".to_string()),
+            ..FakeInjector::default()
+        };
+        let injected = injector.injected.clone();
+
+        let (command_tx, manager) = fake_manager(db, config, Arc::new(FakeUi::default()));
+        let manager = manager.with_injector(Box::new(injector));
+
+        command_tx.send(AppCommand::PasteItem(id, PasteOptions::default()).into()).await.unwrap();
+        command_tx.send(AppCommand::Exit.into()).await.unwrap();
+        manager.run().await;
+
+        assert_eq!(*injected.lock().unwrap(), vec![Injected::Text("echo hi".to_string())]);
+    }
+
+    #[tokio::test]
+    async fn test_paste_restores_previous_clipboard() {
+        let db = Database::open_in_memory().unwrap();
+        let first = db.insert_history(&text_item("first".to_string())).unwrap();
+        let second = db.insert_history(&text_item("second".to_string())).unwrap();
+
+        let mut config = Config::default();
+        config.paste.restore_clipboard = true;
+        // Never due on its own; the restore runs when the manager exits
+        config.paste.restore_delay_ms = 60_000;
+        let clipboard = FakeClipboard::default();
+        *clipboard.contents.lock().unwrap() = Some(FakeContents::Html("<b>mine</b>".to_string(), "mine".to_string()));
+        let self_writes = SelfWrites::default();
+
+        let (command_tx, mut manager) = fake_manager(db, config, Arc::new(FakeUi::default()));
+        manager.self_writes = self_writes.clone();
+        let manager = manager.with_clipboard(Box::new(clipboard.clone()));
+        let driver = async {
+            for (id, content) in [(first, "first"), (second, "second")] {
+                let (request, outcome) = CommandRequest::with_reply(AppCommand::PasteItem(id, PasteOptions::default()));
+                command_tx.send(request).await.unwrap();
+                outcome.await.unwrap().unwrap();
+                assert_eq!(*clipboard.contents.lock().unwrap(), Some(FakeContents::Text(content.to_string())));
+            }
+            command_tx.send(AppCommand::Exit.into()).await.unwrap();
+        };
+        tokio::join!(manager.run(), driver);
+
+        // The second paste kept the snapshot taken before the first
+        assert_eq!(
+            *clipboard.contents.lock().unwrap(),
+            Some(FakeContents::Html("<b>mine</b>".to_string(), "mine".to_string()))
+        );
+        // Seeing the restore accounts for the pastes it overwrote
+        assert!(self_writes.take(&clipboard::content_hash(b"mine")));
+        assert!(!self_writes.take(&clipboard::content_hash(b"first")));
+        assert!(!self_writes.take(&clipboard::content_hash(b"second")));
+    }
+
+    #[tokio::test]
+    async fn test_self_write_policy() {
+        for (policy, on_top, ranked_uses) in [
+            (SelfWritePolicy::Ignore, "newer", 0),
+            (SelfWritePolicy::Bump, "pasted", 0),
+            (SelfWritePolicy::CountAsUsage, "newer", 1),
+        ] {
+            let db = Database::open_in_memory().unwrap();
+            let id = db.insert_history(&text_item("pasted".to_string())).unwrap();
+            let newer = db.insert_history(&text_item("newer".to_string())).unwrap();
+            db.age_history(id, 120).unwrap();
+            db.age_history(newer, 60).unwrap();
+
+            let mut config = Config::default();
+            config.paste.self_write_policy = policy;
+            let clipboard = FakeClipboard::default();
+            let (command_tx, manager) = fake_manager(db.clone(), config, Arc::new(FakeUi::default()));
+            let manager = manager.with_clipboard(Box::new(clipboard.clone()));
+
+            command_tx.send(AppCommand::PasteItem(id, PasteOptions::default()).into()).await.unwrap();
+            command_tx.send(AppCommand::Exit.into()).await.unwrap();
+            manager.run().await;
+
+            assert_eq!(*clipboard.contents.lock().unwrap(), Some(FakeContents::Text("pasted".to_string())));
+            let history = db.get_history_page(None, 10).unwrap().items;
+            assert_eq!(history[0].content, on_top, "{:?}", policy);
+            // The paste always shows in the statistics; the policy decides the ranking
+            assert_eq!(db.item_stats(id).unwrap().unwrap().paste_count, 1, "{:?}", policy);
+            let count_pastes = policy == SelfWritePolicy::CountAsUsage;
+            let candidates = db.quick_pick_candidates("pasted", 10, count_pastes).unwrap();
+            let pasted = candidates.iter().find(|c| c.text == "pasted").unwrap();
+            assert_eq!(pasted.use_count, ranked_uses, "{:?}", policy);
+        }
+    }
+
+    #[tokio::test]
+    async fn test_pastes_show_in_stats_when_bumping() {
+        let db = Database::open_in_memory().unwrap();
+        let pasted = db.insert_history(&text_item("pasted".to_string())).unwrap();
+        db.insert_history(&text_item("copied".to_string())).unwrap();
+        let mut config = Config::default();
+        config.paste.self_write_policy = SelfWritePolicy::Bump;
+
+        let (command_tx, manager) = fake_manager(db.clone(), config, Arc::new(FakeUi::default()));
+        for _ in 0..2 {
+            command_tx.send(AppCommand::PasteItem(pasted, PasteOptions::default()).into()).await.unwrap();
+        }
+        command_tx.send(AppCommand::Exit.into()).await.unwrap();
+        manager.run().await;
+
+        let stats = db.item_stats(pasted).unwrap().unwrap();
+        assert_eq!(stats.paste_count, 2);
+        assert!(stats.last_pasted_at.is_some());
+        assert_eq!(db.most_used(1).unwrap()[0].item.id, Some(pasted));
+        assert_eq!(db.stats(7, 0).unwrap().pastes, 2);
+    }
+
+    #[tokio::test]
+    async fn test_added_text_counts_as_a_copy() {
+        let db = Database::open_in_memory().unwrap();
+        let (command_tx, manager) = fake_manager(db.clone(), Config::default(), Arc::new(FakeUi::default()));
+
+        command_tx.send(AppCommand::AddText("from a script\n".to_string()).into()).await.unwrap();
+        command_tx.send(AppCommand::AddText("from a script".to_string()).into()).await.unwrap();
+        let (request, blank) = CommandRequest::with_reply(AppCommand::AddText(" \n".to_string()));
+        command_tx.send(request).await.unwrap();
+        command_tx.send(AppCommand::Exit.into()).await.unwrap();
+        manager.run().await;
+
+        assert_eq!(blank.await.unwrap(), Err(ManagerError::Invalid("Nothing to add: text is empty".to_string())));
+        // Piped text dedups with the same text copied by hand
+        let items = db.get_history_page(None, 10).unwrap().items;
+        assert_eq!(items.len(), 1);
+        assert_eq!(items[0].content, "from a script");
+        let stats = db.item_stats(items[0].id.unwrap()).unwrap().unwrap();
+        assert_eq!(stats.copy_count, 2);
+    }
+
+    #[tokio::test]
+    async fn test_command_errors_reach_the_sender() {
+        let db = Database::open_in_memory().unwrap();
+        let image = db
+            .insert_history(&ClipboardItem {
+                type_: ClipboardType::Image,
+                ..text_item("/nonexistent/picaclip.png".to_string())
+            })
+            .unwrap();
+        let ui = Arc::new(FakeUi::default());
+        let (command_tx, manager) = fake_manager(db.clone(), Config::default(), ui.clone());
+
+        let mut outcomes = Vec::new();
+        for command in [
+            AppCommand::TogglePin(999),
+            AppCommand::CopySnippet(999, PasteOptions::default()),
+            AppCommand::PasteItem(image, PasteOptions::default()),
+            AppCommand::AddText("fine".to_string()),
+        ] {
+            let (request, outcome) = CommandRequest::with_reply(command);
+            command_tx.send(request).await.unwrap();
+            outcomes.push(outcome);
+        }
+        // Nobody waits for this one, so the user is told
+        command_tx.send(AppCommand::MovePinned(image, PinMove::Up).into()).await.unwrap();
+        command_tx.send(AppCommand::Exit.into()).await.unwrap();
+        manager.run().await;
+
+        let mut outcomes = outcomes.into_iter();
+        let mut next = || outcomes.next().unwrap().try_recv().unwrap();
+        assert_eq!(next(), Err(ManagerError::ItemNotFound(999)));
+        assert_eq!(next(), Err(ManagerError::SnippetNotFound(999)));
+        // The image file is gone, so there's nothing to put on the clipboard
+        assert!(matches!(next(), Err(ManagerError::Clipboard(_))));
+        // A failed paste is not counted
+        assert_eq!(db.item_stats(image).unwrap().unwrap().paste_count, 0);
+        assert_eq!(next(), Ok(()));
+        assert_eq!(
+            *ui.notifications.lock().unwrap(),
+            [format!("Move failed: item {} is not pinned", image)]
+        );
+        assert_eq!(ManagerError::ItemNotFound(999).to_string(), "history item 999 not found");
+    }
+
+    #[tokio::test]
+    async fn test_move_pinned_reorders_the_board() {
+        let db = Database::open_in_memory().unwrap();
+        let first = db.insert_history(&text_item("first".to_string())).unwrap();
+        let second = db.insert_history(&text_item("second".to_string())).unwrap();
+        let image = db
+            .insert_history(&ClipboardItem {
+                type_: ClipboardType::Image,
+                ..text_item("/tmp/pinned.png".to_string())
+            })
+            .unwrap();
+        let loose = db.insert_history(&text_item("loose".to_string())).unwrap();
+
+        let (command_tx, manager) = fake_manager(db.clone(), Config::default(), Arc::new(FakeUi::default()));
+        let mut events = manager.events().subscribe();
+
+        // Each pin goes on top: [image, second, first]
+        let mut outcomes = Vec::new();
+        for command in [
+            AppCommand::SetPinned(first, true),
+            AppCommand::SetPinned(second, true),
+            AppCommand::SetPinned(image, true),
+            // Images sit on the board like any other item
+            AppCommand::MovePinned(image, PinMove::Down),
+            AppCommand::MovePinned(first, PinMove::Up),
+            // Already on top
+            AppCommand::MovePinned(second, PinMove::Up),
+            AppCommand::MovePinned(loose, PinMove::Up),
+            AppCommand::MovePinned(999, PinMove::Down),
+        ] {
+            let (request, outcome) = CommandRequest::with_reply(command);
+            command_tx.send(request).await.unwrap();
+            outcomes.push(outcome);
+        }
+        command_tx.send(AppCommand::Exit.into()).await.unwrap();
+        manager.run().await;
+
+        let results: Vec<_> = outcomes.into_iter().map(|mut outcome| outcome.try_recv().unwrap()).collect();
+        assert!(results[..6].iter().all(Result::is_ok));
+        assert_eq!(
+            results[6],
+            Err(ManagerError::Invalid(format!("Move failed: item {} is not pinned", loose)))
+        );
+        assert_eq!(results[7], Err(ManagerError::ItemNotFound(999)));
+
+        let board: Vec<i64> = db.get_pinned_board().unwrap().into_iter().filter_map(|item| item.id).collect();
+        assert_eq!(board, [second, first, image]);
+        assert!(!db.get_item_by_id(loose).unwrap().unwrap().is_pinned);
+
+        // The last word on each item matches the board
+        let mut orders = std::collections::HashMap::new();
+        for event in drain(&mut events) {
+            if let ManagerEvent::PinChanged { id, pinned: true, pin_order } = event {
+                orders.insert(id, pin_order.unwrap());
+            }
+        }
+        assert!(orders[&second] < orders[&first] && orders[&first] < orders[&image]);
+    }
+
+    #[tokio::test]
+    async fn test_changes_are_published_as_events() {
+        let db = Database::open_in_memory().unwrap();
+        let id = db.insert_history(&text_item("kept".to_string())).unwrap();
+        let (command_tx, manager) = fake_manager(db.clone(), Config::default(), Arc::new(FakeUi::default()));
+        let mut events = manager.events().subscribe();
+
+        for command in [
+            AppCommand::AddText("new".to_string()),
+            AppCommand::TogglePin(id),
+            AppCommand::DeleteHistory(id),
+            AppCommand::Undo,
+            AppCommand::SetPaused(true),
+            AppCommand::SetPaused(true),
+            AppCommand::Exit,
+        ] {
+            command_tx.send(command.into()).await.unwrap();
+        }
+        manager.run().await;
+
+        let received = drain(&mut events);
+        let kinds: Vec<String> = received
+            .iter()
+            .map(|event| serde_json::to_value(event).unwrap()["type"].as_str().unwrap().to_string())
+            .collect();
+        assert_eq!(
+            kinds,
+            ["queue_changed", "item_added", "pin_changed", "item_deleted", "item_added", "paused"]
+        );
+        assert!(matches!(&received[1], ManagerEvent::ItemAdded { item } if item.content == "new"));
+        assert!(matches!(received[2], ManagerEvent::PinChanged { id: pinned_id, pinned: true, .. } if pinned_id == id));
+        assert!(matches!(&received[3], ManagerEvent::ItemDeleted { ids } if *ids == [id]));
+        // Undo brings the item back with its pin
+        assert!(matches!(&received[4], ManagerEvent::ItemAdded { item } if item.id == Some(id) && item.is_pinned));
+    }
+
+    #[tokio::test]
+    async fn test_queue_persists_and_exits_when_drained() {
+        let db = Database::open_in_memory().unwrap();
+        let injector = FakeInjector::default();
+        let injected = injector.injected.clone();
+        let mut config = Config::default();
+        config.paste.strategy = PasteStrategy::TypeText;
+
+        // First run: collect two copies in LIFO mode
+        let (command_tx, mut manager) = fake_manager(db.clone(), config.clone(), Arc::new(FakeUi::default()));
+        let (clipboard_tx, clipboard_rx) = mpsc::channel(4);
+        manager.clipboard_rx = clipboard_rx;
+        let mut events = manager.events().subscribe();
+        let mut captured = manager.events().subscribe();
+        let manager = manager.with_injector(Box::new(injector.clone()));
+        let send = |command| {
+            let command_tx = command_tx.clone();
+            async move {
+                let (request, outcome) = CommandRequest::with_reply(command);
+                command_tx.send(request).await.unwrap();
+                outcome.await.unwrap().unwrap();
+            }
+        };
+        let driver = async {
+            send(AppCommand::ToggleQueueMode(true)).await;
+            send(AppCommand::SetQueueOrder(QueueOrder::Lifo)).await;
+            for content in ["one", "two"] {
+                clipboard_tx.send(text_item(content.to_string())).await.unwrap();
+                // Copies and commands arrive on separate channels; wait for this one to land
+                loop {
+                    if let ManagerEvent::ItemAdded { item } = captured.recv().await.unwrap() {
+                        if item.content == content {
+                            break;
+                        }
+                    }
+                }
+            }
+            // Switching queue mode off and on again keeps what was collected
+            send(AppCommand::ToggleQueueMode(false)).await;
+            assert_eq!(db.get_queue().unwrap().len(), 2);
+            send(AppCommand::ToggleQueueMode(true)).await;
+            command_tx.send(AppCommand::Exit.into()).await.unwrap();
+        };
+        tokio::join!(manager.run(), driver);
+        let state = last_queue(&mut events).unwrap();
+        assert!(state.active);
+        assert_eq!(state.entries.len(), 2);
+
+        // Second run picks the queue and mode back up
+        let ui = Arc::new(FakeUi::default());
+        let (command_tx, manager) = fake_manager(db.clone(), config, ui.clone());
+        let mut events = manager.events().subscribe();
+        let manager = manager.with_injector(Box::new(injector));
+        command_tx.send(AppCommand::NextQueueItem.into()).await.unwrap();
+        command_tx.send(AppCommand::NextQueueItem.into()).await.unwrap();
+        command_tx.send(AppCommand::Exit.into()).await.unwrap();
+        manager.run().await;
+
+        assert_eq!(
+            *injected.lock().unwrap(),
+            vec![Injected::Text("two".to_string()), Injected::Text("one".to_string())]
+        );
+        let state = last_queue(&mut events).unwrap();
+        assert!(!state.active);
+        assert!(state.entries.is_empty());
+        assert!(ui.notifications.lock().unwrap().contains(&"Queue finished".to_string()));
+    }
+
+    #[tokio::test]
+    async fn test_emptying_the_queue_ends_queue_mode() {
+        let db = Database::open_in_memory().unwrap();
+        let first = db.insert_history(&text_item("first".to_string())).unwrap();
+        let second = db.insert_history(&text_item("second".to_string())).unwrap();
+
+        let (command_tx, manager) = fake_manager(db.clone(), Config::default(), Arc::new(FakeUi::default()));
+        let mut events = manager.events().subscribe();
+        let send = |command| {
+            let command_tx = command_tx.clone();
+            async move {
+                let (request, outcome) = CommandRequest::with_reply(command);
+                command_tx.send(request).await.unwrap();
+                outcome.await.unwrap().unwrap();
+            }
+        };
+        let driver = async {
+            send(AppCommand::EnqueueHistory(vec![first, second])).await;
+            let entries = db.get_queue().unwrap();
+            send(AppCommand::RemoveQueueEntry(entries[0].id)).await;
+            assert!(last_queue(&mut events).unwrap().active);
+            send(AppCommand::RemoveQueueEntry(entries[1].id)).await;
+            assert!(!last_queue(&mut events).unwrap().active);
+
+            send(AppCommand::EnqueueHistory(vec![first])).await;
+            assert!(last_queue(&mut events).unwrap().active);
+            send(AppCommand::ClearQueue).await;
+            assert!(!last_queue(&mut events).unwrap().active);
+            command_tx.send(AppCommand::Exit.into()).await.unwrap();
+        };
+        tokio::join!(manager.run(), driver);
+
+        assert_eq!(db.get_setting("queue.active").unwrap().as_deref(), Some("false"));
+    }
+
+    #[tokio::test]
+    async fn test_enqueue_search_takes_the_first_page() {
+        let db = Database::open_in_memory().unwrap();
+        for i in 0..PAGE_SIZE + 10 {
+            db.insert_history(&text_item(format!("deploy step {}", i))).unwrap();
         }
+        let (command_tx, manager) = fake_manager(db.clone(), Config::default(), Arc::new(FakeUi::default()));
+        command_tx.send(AppCommand::EnqueueSearch("deploy".to_string()).into()).await.unwrap();
+        command_tx.send(AppCommand::Exit.into()).await.unwrap();
+        manager.run().await;
+
+        assert_eq!(db.get_queue().unwrap().len(), PAGE_SIZE);
+    }
+
+    #[tokio::test]
+    async fn test_paste_queue_all_joins_in_order() {
+        let db = Database::open_in_memory().unwrap();
+        for content in ["a", "b", "c"] {
+            db.enqueue(&text_item(content.to_string())).unwrap();
+        }
+        let injector = FakeInjector::default();
+        let injected = injector.injected.clone();
+        let mut config = Config::default();
+        config.paste.strategy = PasteStrategy::TypeText;
+
+        let (command_tx, manager) = fake_manager(db.clone(), config, Arc::new(FakeUi::default()));
+        let manager = manager.with_injector(Box::new(injector));
+        command_tx.send(AppCommand::PasteQueueAll(", ".to_string()).into()).await.unwrap();
+        command_tx.send(AppCommand::Exit.into()).await.unwrap();
+        manager.run().await;
+
+        assert_eq!(*injected.lock().unwrap(), vec![Injected::Text("a, b, c".to_string())]);
+        assert!(db.get_queue().unwrap().is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_enqueue_existing_items() {
+        let db = Database::open_in_memory().unwrap();
+        let first = db.insert_history(&text_item("alpha\n\nbeta".to_string())).unwrap();
+        let second = db.insert_history(&text_item("gamma".to_string())).unwrap();
+        let cat_id = db.get_categories().unwrap()[0].id.unwrap();
+        let snippet = |title: &str, is_masked: bool| Snippet {
+            id: None,
+            category_id: cat_id,
+            title: title.to_string(),
+            content: format!("{} body", title),
+            is_masked,
+            usage_count: 0,
+            updated_at: 0,
+        };
+        let open = db.add_snippet(&snippet("open", false)).unwrap();
+        let secret = db.add_snippet(&snippet("secret", true)).unwrap();
+
+        let ui = Arc::new(FakeUi::default());
+        let (command_tx, manager) = fake_manager(db.clone(), Config::default(), ui.clone());
+        let mut events = manager.events().subscribe();
+        command_tx.send(AppCommand::EnqueueHistory(vec![second, first]).into()).await.unwrap();
+        command_tx.send(AppCommand::EnqueueLines(first).into()).await.unwrap();
+        command_tx.send(AppCommand::EnqueueSnippets(vec![open]).into()).await.unwrap();
+        command_tx.send(AppCommand::EnqueueSnippets(vec![secret]).into()).await.unwrap();
+        // A missing id queues nothing from the batch
+        let (request, missing_item) = CommandRequest::with_reply(AppCommand::EnqueueHistory(vec![second, 999]));
+        command_tx.send(request).await.unwrap();
+        let (request, missing_snippet) = CommandRequest::with_reply(AppCommand::EnqueueSnippets(vec![open, 999]));
+        command_tx.send(request).await.unwrap();
+        command_tx.send(AppCommand::Exit.into()).await.unwrap();
+        manager.run().await;
+
+        assert_eq!(missing_item.await.unwrap(), Err(ManagerError::ItemNotFound(999)));
+        assert_eq!(missing_snippet.await.unwrap(), Err(ManagerError::SnippetNotFound(999)));
+        let state = last_queue(&mut events).unwrap();
+        assert!(state.active);
+        let contents: Vec<&str> = state.entries.iter().map(|e| e.content.as_str()).collect();
+        assert_eq!(contents, ["gamma", "alpha\n\nbeta", "alpha", "beta", "open body"]);
+        assert_eq!(state.entries[0].history_id, Some(second));
+        assert!(ui
+            .notifications
+            .lock()
+            .unwrap()
+            .iter()
+            .any(|msg| msg.contains("masked snippets")));
+    }
+
+    #[tokio::test]
+    async fn test_merge_items_and_mixed_selection() {
+        let db = Database::open_in_memory().unwrap();
+        let a = db.insert_history(&text_item("a".to_string())).unwrap();
+        let b = db.insert_history(&text_item("b".to_string())).unwrap();
+        let image = db
+            .insert_history(&ClipboardItem {
+                type_: ClipboardType::Image,
+                ..text_item("/cache/img.png".to_string())
+            })
+            .unwrap();
+
+        let ui = Arc::new(FakeUi::default());
+        let (command_tx, manager) = fake_manager(db.clone(), Config::default(), ui.clone());
+        command_tx
+            .send(AppCommand::MergeItems {
+                ids: vec![b, a],
+                separator: MergeSeparator::Comma,
+                order: MergeOrder::Selection,
+            }.into())
+            .await
+            .unwrap();
+        command_tx
+            .send(AppCommand::PasteMerged {
+                ids: vec![a, image],
+                separator: MergeSeparator::Newline,
+                order: MergeOrder::Selection,
+            }.into())
+            .await
+            .unwrap();
+        command_tx.send(AppCommand::Exit.into()).await.unwrap();
+        manager.run().await;
+
+        assert!(db.search_history("b, a").unwrap().iter().any(|item| item.content == "b, a"));
+        assert_eq!(
+            *ui.notifications.lock().unwrap(),
+            vec![format!("Merge failed: item {} is an image; only text items can be merged", image)]
+        );
+    }
+
+    #[tokio::test]
+    async fn test_undo_delete_and_unpin() {
+        let db = Database::open_in_memory().unwrap();
+        let deleted = db.insert_history(&text_item("deleted".to_string())).unwrap();
+        let pinned = db.insert_history(&text_item("pinned".to_string())).unwrap();
+        db.set_pinned(pinned, true).unwrap();
+
+        let ui = Arc::new(FakeUi::default());
+        let (command_tx, manager) = fake_manager(db.clone(), Config::default(), ui.clone());
+        command_tx.send(AppCommand::DeleteHistory(deleted).into()).await.unwrap();
+        command_tx.send(AppCommand::TogglePin(pinned).into()).await.unwrap();
+        command_tx.send(AppCommand::Undo.into()).await.unwrap();
+        command_tx.send(AppCommand::Undo.into()).await.unwrap();
+        command_tx.send(AppCommand::Undo.into()).await.unwrap();
+        command_tx.send(AppCommand::Exit.into()).await.unwrap();
+        manager.run().await;
+
+        assert!(db.get_item_by_id(pinned).unwrap().unwrap().is_pinned);
+        assert!(db.get_item_by_id(deleted).unwrap().is_some());
+        assert_eq!(*ui.undo_offers.lock().unwrap(), ["Item deleted", "Item unpinned"]);
+        assert_eq!(
+            *ui.notifications.lock().unwrap(),
+            ["Undone", "Undone", "Nothing to undo"]
+        );
+    }
+
+    #[tokio::test]
+    async fn test_clear_history_undo_and_empty_trash() {
+        let db = Database::open_in_memory().unwrap();
+        let pinned = db.insert_history(&text_item("pinned".to_string())).unwrap();
+        db.set_pinned(pinned, true).unwrap();
+        let cleared = db.insert_history(&text_item("cleared".to_string())).unwrap();
+        db.insert_history(&text_item("also cleared".to_string())).unwrap();
+        let loose = db.insert_history(&text_item("receive mail".to_string())).unwrap();
+
+        let ui = Arc::new(FakeUi::default());
+        let (command_tx, manager) = fake_manager(db.clone(), Config::default(), ui.clone());
+        command_tx.send(AppCommand::ClearHistory(ClearFilter::Unpinned).into()).await.unwrap();
+        command_tx.send(AppCommand::Undo.into()).await.unwrap();
+        command_tx.send(AppCommand::ClearSearch("also".to_string()).into()).await.unwrap();
+        // Only the fuzzy search box matches this, so nothing is cleared
+        command_tx.send(AppCommand::ClearSearch("rcvml".to_string()).into()).await.unwrap();
+        command_tx.send(AppCommand::ClearSearch("missing".to_string()).into()).await.unwrap();
+        command_tx.send(AppCommand::EmptyTrash.into()).await.unwrap();
+        command_tx.send(AppCommand::Undo.into()).await.unwrap();
+        command_tx.send(AppCommand::Exit.into()).await.unwrap();
+        manager.run().await;
+
+        assert_eq!(db.get_recent_history(10, 0).unwrap().len(), 3);
+        assert!(db.get_item_by_id(cleared).unwrap().is_some());
+        assert!(db.get_item_by_id(loose).unwrap().is_some());
+        assert_eq!(*ui.undo_offers.lock().unwrap(), ["Cleared 3 items", "Cleared 1 item"]);
+        assert_eq!(
+            *ui.notifications.lock().unwrap(),
+            ["Undone", "Nothing to clear", "Nothing to clear", "Trash emptied: 1 item", "Nothing to undo"]
+        );
+    }
+
+    #[tokio::test]
+    async fn test_edit_updates_row_and_undoes() {
+        let db = Database::open_in_memory().unwrap();
+        let id = db.insert_history(&text_item("draft".to_string())).unwrap();
+        let image = db
+            .insert_history(&ClipboardItem {
+                type_: ClipboardType::Image,
+                ..text_item("/tmp/shot.png".to_string())
+            })
+            .unwrap();
+
+        let ui = Arc::new(FakeUi::default());
+        let (command_tx, manager) = fake_manager(db.clone(), Config::default(), ui.clone());
+        let mut events = manager.events().subscribe();
+        command_tx.send(AppCommand::EditItem(id, "  final  ".to_string()).into()).await.unwrap();
+        command_tx.send(AppCommand::EditItem(image, "text".to_string()).into()).await.unwrap();
+        command_tx.send(AppCommand::RevertEdit(image).into()).await.unwrap();
+        command_tx.send(AppCommand::Undo.into()).await.unwrap();
+        command_tx.send(AppCommand::Exit.into()).await.unwrap();
+        manager.run().await;
+
+        let updated: Vec<ClipboardItem> = drain(&mut events)
+            .into_iter()
+            .filter_map(|event| match event {
+                ManagerEvent::ItemUpdated { item } => Some(item),
+                _ => None,
+            })
+            .collect();
+        // The edit, then the undo putting the draft back
+        assert_eq!(updated.len(), 2);
+        assert_eq!(updated[0].content, "final");
+        assert!(updated[0].edited_at.is_some());
+        assert_eq!(updated[1].content, "draft");
+        assert_eq!(db.get_item_by_id(id).unwrap().unwrap().content, "draft");
+        assert_eq!(*ui.undo_offers.lock().unwrap(), ["Item edited"]);
+        assert_eq!(
+            *ui.notifications.lock().unwrap(),
+            [
+                format!("Edit failed: item {} is not text; only text items can be edited", image),
+                "Item has not been edited".to_string(),
+                "Undone".to_string(),
+            ]
+        );
+    }
+
+    #[tokio::test]
+    async fn test_undone_revert_can_be_reverted_again() {
+        let db = Database::open_in_memory().unwrap();
+        let id = db.insert_history(&text_item("A".to_string())).unwrap();
+
+        let ui = Arc::new(FakeUi::default());
+        let (command_tx, manager) = fake_manager(db.clone(), Config::default(), ui.clone());
+        command_tx.send(AppCommand::EditItem(id, "B".to_string()).into()).await.unwrap();
+        command_tx.send(AppCommand::RevertEdit(id).into()).await.unwrap();
+        command_tx.send(AppCommand::Undo.into()).await.unwrap();
+        command_tx.send(AppCommand::Exit.into()).await.unwrap();
+        manager.run().await;
+
+        // Undoing the revert puts the edit back, original included
+        let item = db.get_item_by_id(id).unwrap().unwrap();
+        assert_eq!(item.content, "B");
+        assert!(item.edited_at.is_some());
+        assert_eq!(db.original_content(id).unwrap().as_deref(), Some("A"));
+
+        let (command_tx, manager) = fake_manager(db.clone(), Config::default(), ui.clone());
+        command_tx.send(AppCommand::RevertEdit(id).into()).await.unwrap();
+        command_tx.send(AppCommand::Exit.into()).await.unwrap();
+        manager.run().await;
+
+        let item = db.get_item_by_id(id).unwrap().unwrap();
+        assert_eq!((item.content.as_str(), item.edited_at), ("A", None));
+        assert_eq!(*ui.notifications.lock().unwrap(), ["Undone"]);
+    }
+
+    #[tokio::test]
+    async fn test_undo_expires() {
+        let db = Database::open_in_memory().unwrap();
+        let id = db.insert_history(&text_item("gone".to_string())).unwrap();
+        let mut config = Config::default();
+        config.history.undo_seconds = 0;
+
+        let ui = Arc::new(FakeUi::default());
+        let (command_tx, manager) = fake_manager(db.clone(), config, ui.clone());
+        command_tx.send(AppCommand::DeleteHistory(id).into()).await.unwrap();
+        command_tx.send(AppCommand::Undo.into()).await.unwrap();
+        command_tx.send(AppCommand::Exit.into()).await.unwrap();
+        manager.run().await;
+
+        assert!(db.get_item_by_id(id).unwrap().is_none());
+        assert_eq!(*ui.notifications.lock().unwrap(), ["Nothing to undo"]);
     }
 }
@@ -0,0 +1,145 @@
+use crate::core::types::{ClipboardItem, ClipboardType};
+use anyhow::{bail, Result};
+use serde::{Deserialize, Serialize};
+
+/// How merged items are joined.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum MergeSeparator {
+    Newline,
+    /// `", "`
+    Comma,
+    Custom(String),
+    /// Rendered once per item and concatenated without a separator.
+    /// `{content}`, `{index}` (from 1) and `{source}` are replaced.
+    Template(String),
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum MergeOrder {
+    /// The order the ids were given in.
+    #[default]
+    Selection,
+    OldestFirst,
+    NewestFirst,
+}
+
+/// Joins the text of `items`. Images and files can't be merged, so any in
+/// the selection is an error naming the offending item.
+pub fn merge(mut items: Vec<ClipboardItem>, separator: &MergeSeparator, order: MergeOrder) -> Result<String> {
+    if let Some(item) = items.iter().find(|item| item.type_ != ClipboardType::Text) {
+        let kind = match item.type_ {
+            ClipboardType::Image => "an image",
+            _ => "a file",
+        };
+        bail!(
+            "item {} is {}; only text items can be merged",
+            item.id.unwrap_or_default(),
+            kind
+        );
+    }
+
+    match order {
+        MergeOrder::Selection => {}
+        MergeOrder::OldestFirst => items.sort_by_key(|item| (item.created_at, item.id)),
+        MergeOrder::NewestFirst => {
+            items.sort_by_key(|item| (item.created_at, item.id));
+            items.reverse();
+        }
+    }
+
+    let joined = match separator {
+        MergeSeparator::Newline => join(&items, "\n"),
+        MergeSeparator::Comma => join(&items, ", "),
+        MergeSeparator::Custom(separator) => join(&items, separator),
+        MergeSeparator::Template(template) => items
+            .iter()
+            .enumerate()
+            .map(|(i, item)| {
+                template
+                    .replace("{index}", &(i + 1).to_string())
+                    .replace("{source}", item.source_app.as_deref().unwrap_or(""))
+                    // Last, so braces inside the content are left alone
+                    .replace("{content}", &item.content)
+            })
+            .collect(),
+    };
+    Ok(joined)
+}
+
+fn join(items: &[ClipboardItem], separator: &str) -> String {
+    items
+        .iter()
+        .map(|item| item.content.as_str())
+        .collect::<Vec<_>>()
+        .join(separator)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn item(id: i64, content: &str, created_at: i64) -> ClipboardItem {
+        ClipboardItem {
+            id: Some(id),
+            type_: ClipboardType::Text,
+            content: content.to_string(),
+            content_hash: String::new(),
+            source_app: Some("editor".to_string()),
+            created_at,
+            is_pinned: false,
+            edited_at: None,
+            pin_order: None,
+            tags: Vec::new(),
+        }
+    }
+
+    fn items() -> Vec<ClipboardItem> {
+        vec![item(2, "b", 20), item(1, "a", 10), item(3, "c", 30)]
+    }
+
+    #[test]
+    fn test_separators() {
+        let merged = |separator| merge(items(), &separator, MergeOrder::Selection).unwrap();
+        assert_eq!(merged(MergeSeparator::Newline), "b\na\nc");
+        assert_eq!(merged(MergeSeparator::Comma), "b, a, c");
+        assert_eq!(merged(MergeSeparator::Custom(" | ".to_string())), "b | a | c");
+        assert_eq!(
+            merged(MergeSeparator::Template("{index}. {content} ({source})\n".to_string())),
+            "1. b (editor)\n2. a (editor)\n3. c (editor)\n"
+        );
+    }
+
+    #[test]
+    fn test_template_leaves_placeholders_in_content() {
+        let merged = merge(
+            vec![item(1, "{index}", 0)],
+            &MergeSeparator::Template("<{content}>".to_string()),
+            MergeOrder::Selection,
+        )
+        .unwrap();
+        assert_eq!(merged, "<{index}>");
+    }
+
+    #[test]
+    fn test_order() {
+        assert_eq!(merge(items(), &MergeSeparator::Comma, MergeOrder::OldestFirst).unwrap(), "a, b, c");
+        assert_eq!(merge(items(), &MergeSeparator::Comma, MergeOrder::NewestFirst).unwrap(), "c, b, a");
+    }
+
+    #[test]
+    fn test_images_and_files_are_rejected() {
+        let mut mixed = items();
+        mixed[1].type_ = ClipboardType::Image;
+        let err = merge(mixed, &MergeSeparator::Newline, MergeOrder::Selection).unwrap_err();
+        assert_eq!(err.to_string(), "item 1 is an image; only text items can be merged");
+
+        let mut mixed = items();
+        mixed[2].type_ = ClipboardType::File;
+        assert!(merge(mixed, &MergeSeparator::Newline, MergeOrder::Selection)
+            .unwrap_err()
+            .to_string()
+            .contains("a file"));
+    }
+}
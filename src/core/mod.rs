@@ -1,4 +1,12 @@
 pub mod types;
+pub mod config;
 pub mod database;
+pub mod async_db;
+pub mod fuzzy;
 pub mod clipboard;
+pub mod paste;
+pub mod transform;
+pub mod merge;
 pub mod manager;
+#[cfg(unix)]
+pub mod ipc;
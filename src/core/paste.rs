@@ -0,0 +1,616 @@
+use crate::core::clipboard::SelfWrites;
+use crate::core::config::PasteStrategy;
+use crate::core::types::{ClipboardItem, ClipboardType, PasteOptions};
+use anyhow::{anyhow, bail, Context, Result};
+use arboard::{Clipboard, ImageData};
+use enigo::{Direction, Enigo, Key, Keyboard, Settings};
+use std::borrow::Cow;
+use std::future::Future;
+use std::path::PathBuf;
+use std::pin::Pin;
+use std::process::Stdio;
+use std::time::Duration;
+use tokio::io::AsyncWriteExt;
+use tokio::process::Command;
+
+/// A paste command that hasn't finished by then is killed.
+const COMMAND_TIMEOUT: Duration = Duration::from_secs(5);
+/// Looking up the focused window gives up after this and uses the default strategy.
+#[cfg(all(unix, not(target_os = "macos")))]
+const ACTIVE_APP_TIMEOUT: Duration = Duration::from_millis(500);
+
+/// What ends up on the system clipboard for a paste.
+#[derive(Debug, Clone, PartialEq)]
+pub enum PastePayload {
+    Text(String),
+    Image(PathBuf),
+    Files(Vec<PathBuf>),
+}
+
+/// Decides the clipboard contents for `item` under `options`.
+///
+/// Images and files keep their format unless `plain_text` is set, in which
+/// case their paths are pasted as text. `trim` and `single_line` only apply
+/// to text.
+pub fn payload(item: &ClipboardItem, options: &PasteOptions) -> PastePayload {
+    match item.type_ {
+        ClipboardType::Image if !options.plain_text => PastePayload::Image(PathBuf::from(&item.content)),
+        ClipboardType::File if !options.plain_text => PastePayload::Files(file_paths(&item.content)),
+        _ => PastePayload::Text(shape_text(&item.content, options)),
+    }
+}
+
+/// Applies the text options: line breaks collapse first, then the result is trimmed.
+pub fn shape_text(text: &str, options: &PasteOptions) -> String {
+    let text = if options.single_line {
+        text.lines()
+            .map(str::trim)
+            .filter(|line| !line.is_empty())
+            .collect::<Vec<_>>()
+            .join(" ")
+    } else {
+        text.to_string()
+    };
+
+    if options.trim {
+        text.trim().to_string()
+    } else {
+        text
+    }
+}
+
+/// File items store one path per line.
+fn file_paths(content: &str) -> Vec<PathBuf> {
+    content
+        .lines()
+        .map(str::trim)
+        .filter(|line| !line.is_empty())
+        .map(PathBuf::from)
+        .collect()
+}
+
+/// The system clipboard, as far as pasting needs it.
+pub trait SystemClipboard: Send {
+    fn get_text(&mut self) -> Result<String>;
+    fn get_html(&mut self) -> Result<String>;
+    fn get_image(&mut self) -> Result<ImageData<'static>>;
+    fn get_files(&mut self) -> Result<Vec<PathBuf>>;
+    fn set_text(&mut self, text: &str) -> Result<()>;
+    /// HTML with a plain text alternative for apps that don't take HTML.
+    fn set_html(&mut self, html: &str, text: &str) -> Result<()>;
+    fn set_image(&mut self, image: ImageData<'static>) -> Result<()>;
+    fn set_files(&mut self, paths: &[PathBuf]) -> Result<()>;
+    fn clear(&mut self) -> Result<()>;
+}
+
+/// The clipboard through arboard. Like [`EnigoInjector`], it is opened on
+/// first use and then kept, so a manager can be built without a display.
+#[derive(Default)]
+pub struct ArboardClipboard {
+    clipboard: Option<Clipboard>,
+}
+
+impl ArboardClipboard {
+    fn clipboard(&mut self) -> Result<&mut Clipboard> {
+        if self.clipboard.is_none() {
+            self.clipboard = Some(Clipboard::new().context("Failed to open clipboard")?);
+        }
+        Ok(self.clipboard.as_mut().unwrap())
+    }
+}
+
+impl SystemClipboard for ArboardClipboard {
+    fn get_text(&mut self) -> Result<String> {
+        Ok(self.clipboard()?.get_text()?)
+    }
+
+    fn get_html(&mut self) -> Result<String> {
+        Ok(self.clipboard()?.get().html()?)
+    }
+
+    fn get_image(&mut self) -> Result<ImageData<'static>> {
+        Ok(self.clipboard()?.get_image()?)
+    }
+
+    fn get_files(&mut self) -> Result<Vec<PathBuf>> {
+        Ok(self.clipboard()?.get().file_list()?)
+    }
+
+    fn set_text(&mut self, text: &str) -> Result<()> {
+        Ok(self.clipboard()?.set_text(text)?)
+    }
+
+    fn set_html(&mut self, html: &str, text: &str) -> Result<()> {
+        Ok(self.clipboard()?.set_html(html, Some(text))?)
+    }
+
+    fn set_image(&mut self, image: ImageData<'static>) -> Result<()> {
+        Ok(self.clipboard()?.set_image(image)?)
+    }
+
+    fn set_files(&mut self, paths: &[PathBuf]) -> Result<()> {
+        Ok(self.clipboard()?.set().file_list(paths)?)
+    }
+
+    fn clear(&mut self) -> Result<()> {
+        Ok(self.clipboard()?.clear()?)
+    }
+}
+
+/// Puts `payload` on the clipboard, marking it as picaclip's own write.
+pub fn write_payload(clipboard: &mut dyn SystemClipboard, payload: &PastePayload, self_writes: &SelfWrites) -> Result<()> {
+    match payload {
+        PastePayload::Text(text) => write_text(clipboard, text, self_writes),
+        PastePayload::Image(path) => {
+            let img = image::open(path)
+                .with_context(|| format!("Failed to load image from {}", path.display()))?
+                .to_rgba8();
+            let img_data = ImageData {
+                width: img.width() as usize,
+                height: img.height() as usize,
+                bytes: Cow::Owned(img.into_vec()),
+            };
+            self_writes.mark_image(&img_data.bytes);
+            clipboard.set_image(img_data).context("Failed to set clipboard image")
+        }
+        PastePayload::Files(paths) => {
+            if clipboard.set_files(paths).is_ok() {
+                return Ok(());
+            }
+            // Not every platform accepts file lists; fall back to the paths as text
+            write_text(clipboard, &payload_text(payload), self_writes)
+        }
+    }
+}
+
+fn write_text(clipboard: &mut dyn SystemClipboard, text: &str, self_writes: &SelfWrites) -> Result<()> {
+    self_writes.mark_text(text);
+    clipboard.set_text(text).context("Failed to set clipboard text")
+}
+
+/// The clipboard as it was before a paste, so it can be put back afterwards.
+#[derive(Debug, Clone, Default)]
+pub struct ClipboardSnapshot {
+    text: Option<String>,
+    html: Option<String>,
+    image: Option<ImageData<'static>>,
+    files: Option<Vec<PathBuf>>,
+}
+
+impl ClipboardSnapshot {
+    /// Reads every format arboard understands; missing formats stay `None`.
+    pub fn capture(clipboard: &mut dyn SystemClipboard) -> Self {
+        Self {
+            text: clipboard.get_text().ok(),
+            html: clipboard.get_html().ok(),
+            image: clipboard.get_image().ok(),
+            files: clipboard.get_files().ok().filter(|files| !files.is_empty()),
+        }
+    }
+
+    /// Writes the snapshot back. arboard sets one format per write, so the
+    /// richest one is restored: files, then image, then HTML with its text
+    /// alternative, then plain text. An empty snapshot clears the clipboard.
+    pub fn restore(&self, clipboard: &mut dyn SystemClipboard, self_writes: &SelfWrites) -> Result<()> {
+        if let Some(files) = &self.files {
+            if let Some(text) = &self.text {
+                self_writes.mark_text(text);
+            }
+            if clipboard.set_files(files).is_ok() {
+                return Ok(());
+            }
+        }
+        if let Some(image) = &self.image {
+            self_writes.mark_image(&image.bytes);
+            return clipboard.set_image(image.clone()).context("Failed to restore clipboard image");
+        }
+        if let Some(text) = &self.text {
+            self_writes.mark_text(text);
+            return match &self.html {
+                Some(html) => clipboard.set_html(html, text),
+                None => clipboard.set_text(text),
+            }
+            .context("Failed to restore clipboard text");
+        }
+        clipboard.clear().context("Failed to clear clipboard")
+    }
+}
+
+/// The focused application's name, once it has been looked up.
+pub type ActiveApp = Pin<Box<dyn Future<Output = Option<String>> + Send>>;
+
+/// Synthesizes input in the focused application.
+pub trait Injector {
+    /// Name of the focused application (window class on X11), if it can be
+    /// found. Resolves without blocking the caller's thread.
+    fn active_app(&mut self) -> ActiveApp;
+    /// Presses `keys` in order and releases them in reverse.
+    fn key_chord(&mut self, keys: &[Key]) -> Result<()>;
+    fn type_text(&mut self, text: &str) -> Result<()>;
+}
+
+/// Injects through enigo. The connection is opened on first use, so a
+/// manager can be built where no display is available.
+#[derive(Default)]
+pub struct EnigoInjector {
+    enigo: Option<Enigo>,
+}
+
+impl EnigoInjector {
+    fn enigo(&mut self) -> Result<&mut Enigo> {
+        if self.enigo.is_none() {
+            self.enigo = Some(Enigo::new(&Settings::default()).context("Failed to init enigo")?);
+        }
+        Ok(self.enigo.as_mut().unwrap())
+    }
+}
+
+impl Injector for EnigoInjector {
+    #[cfg(all(unix, not(target_os = "macos")))]
+    fn active_app(&mut self) -> ActiveApp {
+        Box::pin(async {
+            let output = Command::new("xdotool")
+                .args(["getactivewindow", "getwindowclassname"])
+                .kill_on_drop(true)
+                .output();
+            let output = tokio::time::timeout(ACTIVE_APP_TIMEOUT, output).await.ok()?.ok()?;
+            let name = String::from_utf8_lossy(&output.stdout).trim().to_string();
+            (output.status.success() && !name.is_empty()).then_some(name)
+        })
+    }
+
+    #[cfg(not(all(unix, not(target_os = "macos"))))]
+    fn active_app(&mut self) -> ActiveApp {
+        Box::pin(std::future::ready(None))
+    }
+
+    fn key_chord(&mut self, keys: &[Key]) -> Result<()> {
+        let enigo = self.enigo()?;
+        let (last, modifiers) = keys.split_last().context("Empty key chord")?;
+        for key in modifiers {
+            enigo.key(*key, Direction::Press)?;
+        }
+        let result = enigo.key(*last, Direction::Click);
+        // Release modifiers even if the click failed, so none stay stuck
+        for key in modifiers.iter().rev() {
+            let _ = enigo.key(*key, Direction::Release);
+        }
+        Ok(result?)
+    }
+
+    fn type_text(&mut self, text: &str) -> Result<()> {
+        Ok(self.enigo()?.text(text)?)
+    }
+}
+
+/// Parses a key name from the config: a modifier, a named key or a single character.
+pub fn parse_key(name: &str) -> Result<Key> {
+    Ok(match name.to_lowercase().as_str() {
+        "ctrl" | "control" => Key::Control,
+        "shift" => Key::Shift,
+        "alt" => Key::Alt,
+        "meta" | "super" | "cmd" | "command" => Key::Meta,
+        #[cfg(not(target_os = "macos"))]
+        "insert" => Key::Insert,
+        "return" | "enter" => Key::Return,
+        "tab" => Key::Tab,
+        other => {
+            let mut chars = other.chars();
+            match (chars.next(), chars.next()) {
+                (Some(c), None) => Key::Unicode(c),
+                _ => bail!("Unknown key '{}'", name),
+            }
+        }
+    })
+}
+
+/// Delivers a paste whose payload is already on the clipboard.
+pub async fn inject(injector: &mut dyn Injector, strategy: &PasteStrategy, payload: &PastePayload) -> Result<()> {
+    match strategy {
+        PasteStrategy::KeyChord { keys } => {
+            let keys = keys.iter().map(|k| parse_key(k)).collect::<Result<Vec<_>>>()?;
+            injector.key_chord(&keys)
+        }
+        #[cfg(not(target_os = "macos"))]
+        PasteStrategy::ShiftInsert => injector.key_chord(&[Key::Shift, Key::Insert]),
+        #[cfg(target_os = "macos")]
+        PasteStrategy::ShiftInsert => bail!("Shift+Insert is not available on macOS"),
+        PasteStrategy::TypeText => match payload {
+            PastePayload::Text(text) => injector.type_text(text),
+            _ => Box::pin(inject(injector, &PasteStrategy::default(), payload)).await,
+        },
+        PasteStrategy::ClipboardOnly => Ok(()),
+        PasteStrategy::Command { program, args } => {
+            run_command(program, args, &payload_text(payload), COMMAND_TIMEOUT).await
+        }
+    }
+}
+
+/// Whether `inject` pastes what is on the clipboard, so the payload has to
+/// get there first. Typed text and paste commands carry it themselves.
+pub fn uses_clipboard(strategy: &PasteStrategy, payload: &PastePayload) -> bool {
+    match strategy {
+        PasteStrategy::TypeText => !matches!(payload, PastePayload::Text(_)),
+        PasteStrategy::Command { .. } => false,
+        _ => true,
+    }
+}
+
+/// The payload as text, with paths standing in for images and files.
+fn payload_text(payload: &PastePayload) -> String {
+    match payload {
+        PastePayload::Text(text) => text.clone(),
+        PastePayload::Image(path) => path.to_string_lossy().into_owned(),
+        PastePayload::Files(paths) => paths
+            .iter()
+            .map(|p| p.to_string_lossy())
+            .collect::<Vec<_>>()
+            .join("\n"),
+    }
+}
+
+/// Runs a paste command with `text` on stdin, killing it if it takes longer than `timeout`.
+async fn run_command(program: &str, args: &[String], text: &str, timeout: Duration) -> Result<()> {
+    let mut child = Command::new(program)
+        .args(args)
+        .stdin(Stdio::piped())
+        .kill_on_drop(true)
+        .spawn()
+        .with_context(|| format!("Failed to run paste command '{}'", program))?;
+    let run = async {
+        if let Some(mut stdin) = child.stdin.take() {
+            stdin.write_all(text.as_bytes()).await?;
+        }
+        // stdin is closed here, so the command sees the end of the text
+        child.wait().await
+    };
+    let status = tokio::time::timeout(timeout, run)
+        .await
+        .map_err(|_| anyhow!("Paste command '{}' took longer than {:?}", program, timeout))??;
+    if !status.success() {
+        return Err(anyhow!("Paste command '{}' exited with {}", program, status));
+    }
+    Ok(())
+}
+
+/// What a [`FakeInjector`] was asked to do.
+#[cfg(test)]
+#[derive(Debug, Clone, PartialEq)]
+pub enum Injected {
+    Chord(Vec<Key>),
+    Text(String),
+}
+
+/// Records injections instead of sending them, for tests.
+#[cfg(test)]
+#[derive(Default, Clone)]
+pub struct FakeInjector {
+    pub app: Option<String>,
+    pub injected: std::sync::Arc<std::sync::Mutex<Vec<Injected>>>,
+}
+
+#[cfg(test)]
+impl Injector for FakeInjector {
+    fn active_app(&mut self) -> ActiveApp {
+        Box::pin(std::future::ready(self.app.clone()))
+    }
+
+    fn key_chord(&mut self, keys: &[Key]) -> Result<()> {
+        self.injected.lock().unwrap().push(Injected::Chord(keys.to_vec()));
+        Ok(())
+    }
+
+    fn type_text(&mut self, text: &str) -> Result<()> {
+        self.injected.lock().unwrap().push(Injected::Text(text.to_string()));
+        Ok(())
+    }
+}
+
+/// What a [`FakeClipboard`] holds.
+#[cfg(test)]
+#[derive(Debug, Clone, PartialEq)]
+pub enum FakeContents {
+    Text(String),
+    Html(String, String),
+    Image(Vec<u8>),
+    Files(Vec<PathBuf>),
+}
+
+/// An in-memory clipboard for tests, so they never touch the user's.
+#[cfg(test)]
+#[derive(Default, Clone)]
+pub struct FakeClipboard {
+    pub contents: std::sync::Arc<std::sync::Mutex<Option<FakeContents>>>,
+}
+
+#[cfg(test)]
+impl FakeClipboard {
+    fn get(&self) -> Option<FakeContents> {
+        self.contents.lock().unwrap().clone()
+    }
+
+    fn set(&self, contents: FakeContents) -> Result<()> {
+        *self.contents.lock().unwrap() = Some(contents);
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+impl SystemClipboard for FakeClipboard {
+    fn get_text(&mut self) -> Result<String> {
+        match self.get() {
+            Some(FakeContents::Text(text) | FakeContents::Html(_, text)) => Ok(text),
+            _ => bail!("no text on the clipboard"),
+        }
+    }
+
+    fn get_html(&mut self) -> Result<String> {
+        match self.get() {
+            Some(FakeContents::Html(html, _)) => Ok(html),
+            _ => bail!("no HTML on the clipboard"),
+        }
+    }
+
+    fn get_image(&mut self) -> Result<ImageData<'static>> {
+        match self.get() {
+            Some(FakeContents::Image(bytes)) => Ok(ImageData {
+                width: 1,
+                height: bytes.len() / 4,
+                bytes: Cow::Owned(bytes),
+            }),
+            _ => bail!("no image on the clipboard"),
+        }
+    }
+
+    fn get_files(&mut self) -> Result<Vec<PathBuf>> {
+        match self.get() {
+            Some(FakeContents::Files(paths)) => Ok(paths),
+            _ => bail!("no files on the clipboard"),
+        }
+    }
+
+    fn set_text(&mut self, text: &str) -> Result<()> {
+        self.set(FakeContents::Text(text.to_string()))
+    }
+
+    fn set_html(&mut self, html: &str, text: &str) -> Result<()> {
+        self.set(FakeContents::Html(html.to_string(), text.to_string()))
+    }
+
+    fn set_image(&mut self, image: ImageData<'static>) -> Result<()> {
+        self.set(FakeContents::Image(image.bytes.into_owned()))
+    }
+
+    fn set_files(&mut self, paths: &[PathBuf]) -> Result<()> {
+        self.set(FakeContents::Files(paths.to_vec()))
+    }
+
+    fn clear(&mut self) -> Result<()> {
+        *self.contents.lock().unwrap() = None;
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn item(type_: ClipboardType, content: &str) -> ClipboardItem {
+        ClipboardItem {
+            id: Some(1),
+            type_,
+            content: content.to_string(),
+            content_hash: String::new(),
+            source_app: None,
+            created_at: 0,
+            is_pinned: false,
+            edited_at: None,
+            pin_order: None,
+            tags: Vec::new(),
+        }
+    }
+
+    #[test]
+    fn test_text_options() {
+        let text = item(ClipboardType::Text, "  first line\n\n  second line  \n");
+
+        assert_eq!(
+            payload(&text, &PasteOptions::default()),
+            PastePayload::Text("  first line\n\n  second line  \n".to_string())
+        );
+        let trimmed = PasteOptions { trim: true, ..PasteOptions::default() };
+        assert_eq!(
+            payload(&text, &trimmed),
+            PastePayload::Text("first line\n\n  second line".to_string())
+        );
+        let single = PasteOptions { single_line: true, ..PasteOptions::default() };
+        assert_eq!(
+            payload(&text, &single),
+            PastePayload::Text("first line second line".to_string())
+        );
+    }
+
+    #[test]
+    fn test_plain_text_for_images_and_files() {
+        let image = item(ClipboardType::Image, "/cache/img_1.png");
+        assert_eq!(
+            payload(&image, &PasteOptions::default()),
+            PastePayload::Image(PathBuf::from("/cache/img_1.png"))
+        );
+        assert_eq!(
+            payload(&image, &PasteOptions::plain()),
+            PastePayload::Text("/cache/img_1.png".to_string())
+        );
+
+        let files = item(ClipboardType::File, "/a.txt\n/b.txt");
+        assert_eq!(
+            payload(&files, &PasteOptions::default()),
+            PastePayload::Files(vec![PathBuf::from("/a.txt"), PathBuf::from("/b.txt")])
+        );
+        let options = PasteOptions { plain_text: true, single_line: true, trim: false };
+        assert_eq!(payload(&files, &options), PastePayload::Text("/a.txt /b.txt".to_string()));
+    }
+
+    #[tokio::test]
+    async fn test_inject_strategies() {
+        let mut fake = FakeInjector::default();
+        let text = PastePayload::Text("hello".to_string());
+        let image = PastePayload::Image(PathBuf::from("/cache/img_1.png"));
+        let chord = PasteStrategy::KeyChord {
+            keys: vec!["Ctrl".to_string(), "shift".to_string(), "v".to_string()],
+        };
+
+        inject(&mut fake, &chord, &text).await.unwrap();
+        inject(&mut fake, &PasteStrategy::TypeText, &text).await.unwrap();
+        inject(&mut fake, &PasteStrategy::ClipboardOnly, &text).await.unwrap();
+        // Images can't be typed, so they use the default chord
+        inject(&mut fake, &PasteStrategy::TypeText, &image).await.unwrap();
+
+        let modifier = if cfg!(target_os = "macos") { Key::Meta } else { Key::Control };
+        assert_eq!(
+            *fake.injected.lock().unwrap(),
+            vec![
+                Injected::Chord(vec![Key::Control, Key::Shift, Key::Unicode('v')]),
+                Injected::Text("hello".to_string()),
+                Injected::Chord(vec![modifier, Key::Unicode('v')]),
+            ]
+        );
+
+        let bad = PasteStrategy::KeyChord {
+            keys: vec!["ctrl".to_string(), "hyper".to_string()],
+        };
+        assert!(inject(&mut fake, &bad, &text).await.is_err());
+
+        assert!(uses_clipboard(&chord, &text));
+        assert!(!uses_clipboard(&PasteStrategy::TypeText, &text));
+        assert!(uses_clipboard(&PasteStrategy::TypeText, &image));
+    }
+
+    #[cfg(unix)]
+    #[tokio::test]
+    async fn test_command_strategy_gets_text_on_stdin() {
+        let out = std::env::temp_dir().join(format!("picaclip_paste_cmd_{}", std::process::id()));
+        let strategy = PasteStrategy::Command {
+            program: "sh".to_string(),
+            args: vec!["-c".to_string(), format!("cat > '{}'", out.display())],
+        };
+        inject(&mut FakeInjector::default(), &strategy, &PastePayload::Text("piped".to_string()))
+            .await
+            .unwrap();
+        assert_eq!(std::fs::read_to_string(&out).unwrap(), "piped");
+        let _ = std::fs::remove_file(&out);
+
+        let failing = PasteStrategy::Command {
+            program: "sh".to_string(),
+            args: vec!["-c".to_string(), "exit 3".to_string()],
+        };
+        assert!(inject(&mut FakeInjector::default(), &failing, &PastePayload::Text(String::new()))
+            .await
+            .is_err());
+
+        // A hung command is killed instead of holding up the paste
+        let hung = ["-c".to_string(), "sleep 10".to_string()];
+        let error = run_command("sh", &hung, "", Duration::from_millis(100)).await.unwrap_err();
+        assert_eq!(error.to_string(), "Paste command 'sh' took longer than 100ms");
+    }
+}
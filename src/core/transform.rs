@@ -0,0 +1,362 @@
+use anyhow::{bail, Context, Result};
+use base64::{engine::general_purpose, Engine as _};
+use serde::{Deserialize, Serialize};
+
+/// A text rewrite applied before pasting or saving. Transforms compose left to
+/// right through [`apply_all`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Transform {
+    Upper,
+    Lower,
+    /// Capitalizes every word.
+    Title,
+    /// `some_identifier`, line by line.
+    Snake,
+    /// `someIdentifier`, line by line.
+    Camel,
+    /// Strips whitespace from both ends of every line.
+    TrimLines,
+    SortLines,
+    /// Drops repeated lines, keeping the first occurrence.
+    DedupeLines,
+    JsonPretty,
+    JsonMinify,
+    Base64Encode,
+    Base64Decode,
+    UrlEncode,
+    UrlDecode,
+    HtmlEscape,
+    /// Wraps in double quotes, escaping `"` and `\`.
+    Quote,
+    /// Reverses [`Transform::Quote`]; single quotes are accepted too.
+    Unquote,
+}
+
+impl Transform {
+    pub fn apply(&self, text: &str) -> Result<String> {
+        Ok(match self {
+            Transform::Upper => text.to_uppercase(),
+            Transform::Lower => text.to_lowercase(),
+            Transform::Title => title_case(text),
+            Transform::Snake => map_lines(text, |line| words(line).join("_").to_lowercase()),
+            Transform::Camel => map_lines(text, camel_case),
+            Transform::TrimLines => map_lines(text, |line| line.trim().to_string()),
+            Transform::SortLines => {
+                let mut lines: Vec<&str> = text.lines().collect();
+                lines.sort();
+                lines.join("\n")
+            }
+            Transform::DedupeLines => {
+                let mut seen = std::collections::HashSet::new();
+                text.lines().filter(|line| seen.insert(*line)).collect::<Vec<_>>().join("\n")
+            }
+            Transform::JsonPretty => reformat_json(text, true)?,
+            Transform::JsonMinify => reformat_json(text, false)?,
+            Transform::Base64Encode => general_purpose::STANDARD.encode(text),
+            Transform::Base64Decode => {
+                let bytes = general_purpose::STANDARD
+                    .decode(text.trim())
+                    .context("Not valid Base64")?;
+                String::from_utf8(bytes).context("Decoded Base64 is not text")?
+            }
+            Transform::UrlEncode => url_encode(text),
+            Transform::UrlDecode => url_decode(text)?,
+            Transform::HtmlEscape => html_escape(text),
+            Transform::Quote => format!("\"{}\"", text.replace('\\', "\\\\").replace('"', "\\\"")),
+            Transform::Unquote => unquote(text)?,
+        })
+    }
+}
+
+pub fn apply_all(transforms: &[Transform], text: &str) -> Result<String> {
+    transforms
+        .iter()
+        .try_fold(text.to_string(), |text, transform| transform.apply(&text))
+}
+
+fn map_lines(text: &str, f: impl Fn(&str) -> String) -> String {
+    text.lines().map(f).collect::<Vec<_>>().join("\n")
+}
+
+fn capitalize(word: &str) -> String {
+    let mut chars = word.chars();
+    match chars.next() {
+        Some(first) => first.to_uppercase().chain(chars.flat_map(char::to_lowercase)).collect(),
+        None => String::new(),
+    }
+}
+
+fn title_case(text: &str) -> String {
+    let mut out = String::with_capacity(text.len());
+    let mut at_word_start = true;
+    for c in text.chars() {
+        if c.is_alphanumeric() {
+            if at_word_start {
+                out.extend(c.to_uppercase());
+            } else {
+                out.extend(c.to_lowercase());
+            }
+            at_word_start = false;
+        } else {
+            out.push(c);
+            at_word_start = c.is_whitespace();
+        }
+    }
+    out
+}
+
+fn camel_case(line: &str) -> String {
+    words(line)
+        .iter()
+        .enumerate()
+        .map(|(i, word)| if i == 0 { word.to_lowercase() } else { capitalize(word) })
+        .collect()
+}
+
+/// Splits an identifier or phrase into words: at anything that isn't
+/// alphanumeric, at lower-to-upper case changes, and before the last capital
+/// of an acronym (`HTTPServer` -> `HTTP`, `Server`).
+fn words(text: &str) -> Vec<String> {
+    let chars: Vec<char> = text.chars().collect();
+    let mut words = Vec::new();
+    let mut current = String::new();
+    for (i, &c) in chars.iter().enumerate() {
+        if !c.is_alphanumeric() {
+            if !current.is_empty() {
+                words.push(std::mem::take(&mut current));
+            }
+            continue;
+        }
+        if c.is_uppercase() && !current.is_empty() {
+            let prev = chars[i - 1];
+            let next_is_lower = chars.get(i + 1).is_some_and(|n| n.is_lowercase());
+            if prev.is_lowercase() || prev.is_numeric() || (prev.is_uppercase() && next_is_lower) {
+                words.push(std::mem::take(&mut current));
+            }
+        }
+        current.push(c);
+    }
+    if !current.is_empty() {
+        words.push(current);
+    }
+    words
+}
+
+/// Re-indents JSON without parsing it into a map, so key order and number
+/// spelling survive.
+fn reformat_json(text: &str, pretty: bool) -> Result<String> {
+    serde_json::from_str::<serde::de::IgnoredAny>(text).context("Not valid JSON")?;
+
+    let mut out = String::with_capacity(text.len());
+    let mut depth = 0usize;
+    let mut in_string = false;
+    let mut escaped = false;
+    let mut chars = text.chars().peekable();
+
+    let newline = |out: &mut String, depth: usize| {
+        out.push('\n');
+        out.push_str(&"  ".repeat(depth));
+    };
+
+    while let Some(c) = chars.next() {
+        if in_string {
+            out.push(c);
+            if escaped {
+                escaped = false;
+            } else if c == '\\' {
+                escaped = true;
+            } else if c == '"' {
+                in_string = false;
+            }
+            continue;
+        }
+        match c {
+            '"' => {
+                in_string = true;
+                out.push(c);
+            }
+            '{' | '[' => {
+                out.push(c);
+                while chars.peek().is_some_and(|n| n.is_whitespace()) {
+                    chars.next();
+                }
+                if matches!(chars.peek(), Some('}') | Some(']')) {
+                    out.push(chars.next().unwrap());
+                } else {
+                    depth += 1;
+                    if pretty {
+                        newline(&mut out, depth);
+                    }
+                }
+            }
+            '}' | ']' => {
+                depth -= 1;
+                if pretty {
+                    newline(&mut out, depth);
+                }
+                out.push(c);
+            }
+            ',' => {
+                out.push(c);
+                if pretty {
+                    newline(&mut out, depth);
+                }
+            }
+            ':' => out.push_str(if pretty { ": " } else { ":" }),
+            c if c.is_whitespace() => {}
+            c => out.push(c),
+        }
+    }
+    Ok(out)
+}
+
+fn url_encode(text: &str) -> String {
+    let mut out = String::with_capacity(text.len());
+    for byte in text.bytes() {
+        match byte {
+            b'A'..=b'Z' | b'a'..=b'z' | b'0'..=b'9' | b'-' | b'.' | b'_' | b'~' => out.push(byte as char),
+            _ => out.push_str(&format!("%{:02X}", byte)),
+        }
+    }
+    out
+}
+
+fn url_decode(text: &str) -> Result<String> {
+    let bytes = text.as_bytes();
+    let mut out = Vec::with_capacity(bytes.len());
+    let mut i = 0;
+    while i < bytes.len() {
+        if bytes[i] == b'%' {
+            let hex = text.get(i + 1..i + 3).context("Truncated percent escape")?;
+            out.push(u8::from_str_radix(hex, 16).with_context(|| format!("Invalid percent escape %{}", hex))?);
+            i += 3;
+        } else {
+            out.push(bytes[i]);
+            i += 1;
+        }
+    }
+    String::from_utf8(out).context("Decoded URL is not valid UTF-8")
+}
+
+fn html_escape(text: &str) -> String {
+    let mut out = String::with_capacity(text.len());
+    for c in text.chars() {
+        match c {
+            '&' => out.push_str("&amp;"),
+            '<' => out.push_str("&lt;"),
+            '>' => out.push_str("&gt;"),
+            '"' => out.push_str("&quot;"),
+            '\'' => out.push_str("&#39;"),
+            c => out.push(c),
+        }
+    }
+    out
+}
+
+fn unquote(text: &str) -> Result<String> {
+    let quote = match text.chars().next() {
+        Some(q @ ('"' | '\'')) if text.len() >= 2 && text.ends_with(q) => q,
+        _ => bail!("Text is not quoted"),
+    };
+
+    let mut out = String::with_capacity(text.len());
+    let mut chars = text[1..text.len() - 1].chars();
+    while let Some(c) = chars.next() {
+        if c == '\\' {
+            match chars.next() {
+                Some(next) if next == quote || next == '\\' => out.push(next),
+                Some(next) => {
+                    out.push(c);
+                    out.push(next);
+                }
+                None => out.push(c),
+            }
+        } else {
+            out.push(c);
+        }
+    }
+    Ok(out)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn apply(transform: Transform, text: &str) -> String {
+        transform.apply(text).unwrap()
+    }
+
+    #[test]
+    fn test_case_conversions() {
+        assert_eq!(apply(Transform::Upper, "straße"), "STRASSE");
+        assert_eq!(apply(Transform::Lower, "Hello World"), "hello world");
+        assert_eq!(apply(Transform::Title, "hello wORLD, it's-me"), "Hello World, It's-me");
+        assert_eq!(apply(Transform::Snake, "parseHTTPResponse"), "parse_http_response");
+        assert_eq!(apply(Transform::Snake, "Hello world-wide web\nfooBar2Baz"), "hello_world_wide_web\nfoo_bar2_baz");
+        assert_eq!(apply(Transform::Camel, "user_id_value"), "userIdValue");
+        assert_eq!(apply(Transform::Camel, "HTTP server"), "httpServer");
+    }
+
+    #[test]
+    fn test_line_transforms() {
+        assert_eq!(apply(Transform::TrimLines, "  a  \n\tb\n"), "a\nb");
+        assert_eq!(apply(Transform::SortLines, "pear\napple\nfig"), "apple\nfig\npear");
+        assert_eq!(apply(Transform::DedupeLines, "a\nb\na\nc\nb"), "a\nb\nc");
+    }
+
+    #[test]
+    fn test_json() {
+        let json = r#"{ "b": [1, 2.50, {}], "a": "x, y: {z}" }"#;
+        assert_eq!(
+            apply(Transform::JsonPretty, json),
+            "{\n  \"b\": [\n    1,\n    2.50,\n    {}\n  ],\n  \"a\": \"x, y: {z}\"\n}"
+        );
+        assert_eq!(apply(Transform::JsonMinify, json), r#"{"b":[1,2.50,{}],"a":"x, y: {z}"}"#);
+        assert_eq!(apply(Transform::JsonMinify, "[ ]"), "[]");
+        assert!(Transform::JsonPretty.apply("{not json}").is_err());
+    }
+
+    #[test]
+    fn test_base64() {
+        assert_eq!(apply(Transform::Base64Encode, "hello, 世界"), "aGVsbG8sIOS4lueVjA==");
+        assert_eq!(apply(Transform::Base64Decode, " aGVsbG8sIOS4lueVjA==\n"), "hello, 世界");
+        assert!(Transform::Base64Decode.apply("not base64!").is_err());
+        assert!(Transform::Base64Decode.apply("/w==").is_err());
+    }
+
+    #[test]
+    fn test_url() {
+        assert_eq!(apply(Transform::UrlEncode, "a b&c=d/é~"), "a%20b%26c%3Dd%2F%C3%A9~");
+        assert_eq!(apply(Transform::UrlDecode, "a%20b%26c%3Dd%2F%C3%A9~"), "a b&c=d/é~");
+        assert!(Transform::UrlDecode.apply("100%").is_err());
+        assert!(Transform::UrlDecode.apply("%zz").is_err());
+    }
+
+    #[test]
+    fn test_html_escape() {
+        assert_eq!(
+            apply(Transform::HtmlEscape, r#"<a href="x">Tom & Jerry's</a>"#),
+            "&lt;a href=&quot;x&quot;&gt;Tom &amp; Jerry&#39;s&lt;/a&gt;"
+        );
+    }
+
+    #[test]
+    fn test_quote_round_trip() {
+        let text = r#"say "hi" \ bye"#;
+        let quoted = apply(Transform::Quote, text);
+        assert_eq!(quoted, r#""say \"hi\" \\ bye""#);
+        assert_eq!(apply(Transform::Unquote, &quoted), text);
+        assert_eq!(apply(Transform::Unquote, r"'it\'s'"), "it's");
+        assert!(Transform::Unquote.apply("bare").is_err());
+        assert!(Transform::Unquote.apply("\"").is_err());
+    }
+
+    #[test]
+    fn test_apply_all_composes_in_order() {
+        let out = apply_all(&[Transform::TrimLines, Transform::DedupeLines, Transform::Upper], " a\na \nb").unwrap();
+        assert_eq!(out, "A\nB");
+        assert!(apply_all(&[Transform::Base64Decode, Transform::Upper], "%%%").is_err());
+        assert_eq!(apply_all(&[], "same").unwrap(), "same");
+    }
+}
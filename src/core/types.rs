@@ -1,3 +1,5 @@
+use crate::core::merge::{MergeOrder, MergeSeparator};
+use crate::core::transform::Transform;
 use serde::{Deserialize, Serialize};
 
 #[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
@@ -27,9 +29,213 @@ pub struct ClipboardItem {
     pub source_app: Option<String>,
     pub created_at: i64,
     pub is_pinned: bool,
+    /// When the content was last edited by hand; `None` for captured content.
+    #[serde(default)]
+    pub edited_at: Option<i64>,
+    /// Position on the pinned board, smallest first. Unpinned items have none.
+    #[serde(default)]
+    pub pin_order: Option<i64>,
     pub tags: Vec<String>,
 }
 
+/// Keyset position in the history list, which shows the pinned board in its
+/// manual order, then everything else by `(created_at, id)` descending. Pages
+/// fetched after a cursor stay stable when new items arrive, since those sort
+/// before it.
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub struct HistoryCursor {
+    pub is_pinned: bool,
+    /// 0 for unpinned items.
+    pub pin_order: i64,
+    pub created_at: i64,
+    pub id: i64,
+}
+
+impl HistoryCursor {
+    pub fn after(item: &ClipboardItem) -> Option<Self> {
+        Some(Self {
+            is_pinned: item.is_pinned,
+            pin_order: item.pin_order.unwrap_or(0),
+            created_at: item.created_at,
+            id: item.id?,
+        })
+    }
+}
+
+/// Where `move_pinned` puts an item on the pinned board.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum PinMove {
+    Up,
+    Down,
+    /// To this index, counted from the top of the board.
+    To(usize),
+}
+
+/// How an item is written to the clipboard when pasted.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Serialize, Deserialize)]
+pub struct PasteOptions {
+    /// Paste only text: images and files paste their paths. When false the
+    /// item's original format is kept.
+    pub plain_text: bool,
+    /// Strip leading and trailing whitespace.
+    pub trim: bool,
+    /// Collapse line breaks into single spaces.
+    pub single_line: bool,
+}
+
+impl PasteOptions {
+    pub fn plain() -> Self {
+        Self {
+            plain_text: true,
+            ..Self::default()
+        }
+    }
+}
+
+/// One item waiting in the paste queue. The content is a copy, so the entry
+/// survives the history item being deleted.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct QueueEntry {
+    pub id: i64,
+    /// Source history item, if it still exists.
+    pub history_id: Option<i64>,
+    pub type_: ClipboardType,
+    pub content: String,
+}
+
+/// Which end of the queue "paste next" takes from.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum QueueOrder {
+    #[default]
+    Fifo,
+    Lifo,
+}
+
+/// The queue as shown to the UI. `entries` are in stored order; with
+/// `Lifo` the last one is pasted next.
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct QueueState {
+    pub active: bool,
+    pub order: QueueOrder,
+    pub entries: Vec<QueueEntry>,
+}
+
+/// What the manager changed, broadcast to every subscriber (UI, control
+/// socket clients, plugins) so they can patch what they show instead of
+/// reloading it.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum ManagerEvent {
+    /// A new item, one copied again, or one brought back by undo. Lists
+    /// place it by pin state and `created_at`, as the history list does.
+    ItemAdded { item: ClipboardItem },
+    /// The item's content changed in place.
+    ItemUpdated { item: ClipboardItem },
+    /// Items moved to the trash, by a delete, a clear or an edit that
+    /// merged a duplicate.
+    ItemDeleted { ids: Vec<i64> },
+    /// An item was pinned, unpinned or moved on the pinned board.
+    PinChanged { id: i64, pinned: bool, pin_order: Option<i64> },
+    QueueChanged { state: QueueState },
+    /// Capturing was paused or resumed.
+    Paused { paused: bool },
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct HistoryPage {
+    pub items: Vec<ClipboardItem>,
+    /// Cursor for the next page, `None` once the end is reached.
+    pub next: Option<HistoryCursor>,
+}
+
+/// Which history items a bulk clear removes. Pinned items are kept by every
+/// filter except `All`.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ClearFilter {
+    Unpinned,
+    All,
+    /// Items created in `[from, to)`, unix seconds.
+    DateRange { from: i64, to: i64 },
+    SourceApp(String),
+    Type(ClipboardType),
+    /// These history items.
+    Items(Vec<i64>),
+}
+
+/// Outcome of a bulk clear. The cleared rows sit in the trash under `batch`
+/// until undone or purged; image files go with the purge.
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct ClearReport {
+    pub batch: i64,
+    pub items: usize,
+    pub images: usize,
+    /// Ids of the cleared rows.
+    pub ids: Vec<i64>,
+}
+
+/// An item's state from before an in-place edit, enough to undo it.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct HistoryEdit {
+    pub id: i64,
+    pub content: String,
+    pub content_hash: String,
+    pub edited_at: Option<i64>,
+    pub is_pinned: bool,
+    pub paste_count: i64,
+    pub pin_order: Option<i64>,
+    pub copy_count: i64,
+    /// The captured content, if the item had already been edited.
+    pub original_content: Option<String>,
+    /// Trash batch of the item that already held the new content, if any.
+    pub replaced_batch: Option<i64>,
+    /// Id of that item.
+    pub replaced_id: Option<i64>,
+}
+
+/// Usage counters of a history item. Times are unix seconds.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Serialize, Deserialize)]
+pub struct ItemStats {
+    /// First capture; rows from before these columns existed use their
+    /// `created_at` at upgrade time.
+    pub first_seen_at: Option<i64>,
+    pub last_copied_at: Option<i64>,
+    /// Captures, the first one included.
+    pub copy_count: i64,
+    pub paste_count: i64,
+    pub last_pasted_at: Option<i64>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ItemUsage {
+    pub item: ClipboardItem,
+    pub stats: ItemStats,
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct HistoryStats {
+    pub total_items: usize,
+    pub pinned_items: usize,
+    pub copies: i64,
+    pub pastes: i64,
+    /// `(YYYY-MM-DD, items)` in local time, oldest day first.
+    pub items_per_day: Vec<(String, usize)>,
+    /// `(source app, items)`, most items first.
+    pub top_apps: Vec<(String, usize)>,
+    pub storage: StorageStats,
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Serialize, Deserialize)]
+pub struct StorageStats {
+    /// Size of the main database file, free pages included.
+    pub database_bytes: u64,
+    pub image_files: usize,
+    /// Combined size of the image files that still exist.
+    pub image_bytes: u64,
+}
+
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct Category {
     pub id: Option<i64>,
@@ -50,14 +256,124 @@ pub struct Snippet {
     pub updated_at: i64,
 }
 
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Tag {
+    pub id: Option<i64>,
+    pub name: String,
+    pub color: Option<String>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub enum TagTarget {
+    History = 1,
+    Snippet = 2,
+}
+
+/// One entry of the unified search, coming from history, snippets or tags.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub enum SearchHit {
+    History(ClipboardItem),
+    Snippet(Snippet),
+    Tag(Tag),
+}
+
+/// Byte range of a matched term inside some text.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub struct MatchSpan {
+    pub start: usize,
+    pub end: usize,
+}
+
+/// Where a search matched inside a history item or snippet content.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SearchExcerpt {
+    /// Short context window around the best match, with `…` where it was cut.
+    pub text: String,
+    /// Matched terms inside `text`, for bolding.
+    pub matches: Vec<MatchSpan>,
+    /// Matched terms inside the full item content, for jumping to them.
+    pub content_matches: Vec<MatchSpan>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SearchResult {
+    pub hit: SearchHit,
+    /// Higher is better. FTS hits use the negated bm25 rank.
+    pub score: f64,
+    /// Set for history hits and for snippet hits that aren't masked.
+    pub excerpt: Option<SearchExcerpt>,
+}
+
 #[derive(Debug, Clone)]
 pub enum AppCommand {
-    PasteItem(i64),
+    PasteItem(i64, PasteOptions),
+    /// Puts an item on the clipboard without pasting it.
+    CopyItem(i64, PasteOptions),
+    /// Saves text as if it had just been copied, e.g. piped in from a script.
+    AddText(String),
+    PasteSnippet(i64, PasteOptions),
+    CopySnippet(i64, PasteOptions),
+    /// Pastes a text item after running it through the transforms, in order.
+    PasteTransformed(i64, Vec<Transform>),
+    /// Saves the transformed text of an item as a new history item.
+    SaveTransformed(i64, Vec<Transform>),
+    /// Pastes several text items joined into one.
+    PasteMerged {
+        ids: Vec<i64>,
+        separator: MergeSeparator,
+        order: MergeOrder,
+    },
+    /// Saves several text items joined into one as a new history item.
+    MergeItems {
+        ids: Vec<i64>,
+        separator: MergeSeparator,
+        order: MergeOrder,
+    },
     DeleteHistory(i64),
+    /// Moves every history item the filter selects to the trash, undoably.
+    ClearHistory(ClearFilter),
+    /// Clears the unpinned history items that contain every term of the
+    /// query. Loose fuzzy matches the search box also shows are kept.
+    ClearSearch(String),
+    /// Permanently removes everything in the trash, image files included.
+    EmptyTrash,
+    /// Replaces the content of a text item.
+    EditItem(i64, String),
+    /// Puts an edited item back to the content it was captured with.
+    RevertEdit(i64),
     TogglePin(i64),
+    /// Pins or unpins an item; does nothing if it is already in that state.
+    SetPinned(i64, bool),
+    MovePinned(i64, PinMove),
     Search(String),
+    /// Stops saving clipboard changes until resumed.
+    SetPaused(bool),
     AddSnippet(Snippet),
     ToggleQueueMode(bool),
     NextQueueItem,
+    /// Moves a queue entry to the given index.
+    MoveQueueEntry(i64, usize),
+    RemoveQueueEntry(i64),
+    SetQueueOrder(QueueOrder),
+    /// Pastes every queued text entry at once, joined by the separator, and
+    /// leaves queue mode.
+    PasteQueueAll(String),
+    ClearQueue,
+    /// Adds existing history items to the queue, in the given order; a
+    /// missing id queues none of them.
+    EnqueueHistory(Vec<i64>),
+    /// Adds snippets to the queue. Masked snippets and missing ids are refused.
+    EnqueueSnippets(Vec<i64>),
+    /// Adds the history items and snippets on the first page the search box
+    /// would show for the query, so a broad query can't flood the queue.
+    EnqueueSearch(String),
+    /// Adds each non-empty line of a text item as its own entry.
+    EnqueueLines(i64),
+    /// Fetch the next page of whatever the list shows (history or search results).
+    LoadMore,
+    /// Reverts the latest delete, clear, unpin or edit still inside the undo window.
+    Undo,
+    /// Release free database pages in the background.
+    Vacuum,
     Exit,
 }
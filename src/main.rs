@@ -2,34 +2,240 @@ mod core;
 mod utils;
 mod ui;
 
+use crate::core::clipboard::{ClipboardMonitor, SelfWrites};
+#[cfg(unix)]
+use crate::core::async_db::AsyncDatabase;
+use crate::core::config::Config;
 use crate::core::database::Database;
-use crate::core::manager::{Manager, UiHandle};
-use crate::core::types::{AppCommand, ClipboardItem as CoreClipboardItem};
+#[cfg(unix)]
+use crate::core::ipc::IpcServer;
+use crate::core::manager::{CommandRequest, Manager, UiHandle};
+use crate::core::types::{
+    AppCommand, ClearFilter, ClipboardItem as CoreClipboardItem, ManagerEvent, PasteOptions, PinMove, QueueOrder,
+    QueueState, SearchExcerpt, SearchHit, SearchResult,
+};
+#[cfg(unix)]
+use crate::ui::cli::{self, Invocation};
+use crate::ui::headless::HeadlessUi;
+use crate::utils::daemon::{self, PidFile};
 use crate::utils::paths;
-use anyhow::Result;
+use anyhow::{bail, Result};
+use slint::Model;
+use std::cmp::Reverse;
 use std::sync::Arc;
-use tokio::sync::mpsc;
+use tokio::sync::{broadcast, mpsc};
 use tokio::runtime::Runtime;
 
 // Slint module include
 slint::include_modules!();
 
+/// How long a notification stays in the window.
+const NOTIFICATION_DURATION: std::time::Duration = std::time::Duration::from_secs(4);
+
 struct SlintUi {
     window: slint::Weak<AppWindow>,
 }
 
+fn history_row(item: CoreClipboardItem) -> ClipboardItem {
+    ClipboardItem {
+        id: item.id.unwrap_or(0) as i32,
+        kind: 0,
+        type_val: item.type_ as i32,
+        content: item.content.into(),
+        is_pinned: item.is_pinned,
+        source: item.source_app.unwrap_or_default().into(),
+        edited: item.edited_at.is_some(),
+        pin_order: item.pin_order.unwrap_or(0) as i32,
+        created_at: item.created_at as i32,
+        segments: Default::default(),
+    }
+}
+
+/// Splits an excerpt into plain and matched runs for the row to draw, on one line.
+fn match_segments(excerpt: &SearchExcerpt) -> slint::ModelRc<TextSegment> {
+    let text = &excerpt.text;
+    let mut segments = Vec::new();
+    let mut push = |range: std::ops::Range<usize>, bold: bool| {
+        if let Some(run) = text.get(range).filter(|run| !run.is_empty()) {
+            segments.push(TextSegment { text: run.replace(['\n', '\r', '\t'], " ").into(), bold });
+        }
+    };
+    let mut at = 0;
+    for span in &excerpt.matches {
+        let start = span.start.max(at);
+        if start >= span.end {
+            continue;
+        }
+        push(at..start, false);
+        push(start..span.end, true);
+        at = span.end;
+    }
+    push(at..text.len(), false);
+    std::rc::Rc::new(slint::VecModel::from(segments)).into()
+}
+
+fn search_row(result: SearchResult) -> ClipboardItem {
+    // Long items show the window around the match instead of their head
+    let segments = result.excerpt.as_ref().map(match_segments).unwrap_or_default();
+    let excerpt = result.excerpt.map(|excerpt| excerpt.text);
+    match result.hit {
+        SearchHit::History(item) => ClipboardItem {
+            content: excerpt.unwrap_or_else(|| item.content.clone()).into(),
+            segments,
+            ..history_row(item)
+        },
+        SearchHit::Snippet(snippet) => ClipboardItem {
+            id: snippet.id.unwrap_or(0) as i32,
+            kind: 1,
+            type_val: 1,
+            content: match (snippet.is_masked, excerpt) {
+                (true, _) => "******".into(),
+                (false, excerpt) => excerpt.unwrap_or(snippet.content).into(),
+            },
+            is_pinned: false,
+            source: snippet.title.into(),
+            edited: false,
+            pin_order: 0,
+            created_at: 0,
+            segments,
+        },
+        SearchHit::Tag(tag) => ClipboardItem {
+            id: tag.id.unwrap_or(0) as i32,
+            kind: 2,
+            type_val: 1,
+            content: tag.name.into(),
+            is_pinned: false,
+            source: Default::default(),
+            edited: false,
+            pin_order: 0,
+            created_at: 0,
+            segments,
+        },
+    }
+}
+
+/// Runs `f` on the list model if it is a `VecModel`, so pages can be added in place.
+fn with_rows(window: &AppWindow, f: impl FnOnce(&slint::VecModel<ClipboardItem>)) {
+    let model = window.get_history_model();
+    if let Some(rows) = model.as_any().downcast_ref::<slint::VecModel<ClipboardItem>>() {
+        f(rows);
+    }
+}
+
+fn find_history_row(rows: &slint::VecModel<ClipboardItem>, id: i32) -> Option<usize> {
+    (0..rows.row_count()).find(|&i| rows.row_data(i).is_some_and(|r| r.kind == 0 && r.id == id))
+}
+
+/// Where a row goes in the history list: the pinned board in its order, then
+/// the rest newest first, as `Database::get_history_page` sorts them.
+fn history_key(row: &ClipboardItem) -> (bool, i32, Reverse<i32>, Reverse<i32>) {
+    let pin_order = if row.is_pinned { row.pin_order } else { 0 };
+    (!row.is_pinned, pin_order, Reverse(row.created_at), Reverse(row.id))
+}
+
+/// Puts a history row where it sorts, replacing the one with the same id.
+fn place_history_row(rows: &slint::VecModel<ClipboardItem>, row: ClipboardItem) {
+    if let Some(index) = find_history_row(rows, row.id) {
+        rows.remove(index);
+    }
+    let key = history_key(&row);
+    let index = (0..rows.row_count())
+        .find(|&i| rows.row_data(i).is_some_and(|r| r.kind == 0 && history_key(&r) > key))
+        .unwrap_or(rows.row_count());
+    rows.insert(index, row);
+}
+
+/// Applies a change published by the manager to the rows on screen. While
+/// search results are shown, rows already listed are updated in place but
+/// nothing is added or reordered.
+fn apply_event(window: &AppWindow, event: ManagerEvent) {
+    let searching = !window.get_search_query().is_empty();
+    match event {
+        ManagerEvent::ItemAdded { item } => with_rows(window, |rows| {
+            let row = history_row(item);
+            if !searching {
+                place_history_row(rows, row);
+            } else if let Some(index) = find_history_row(rows, row.id) {
+                rows.set_row_data(index, row);
+            }
+        }),
+        ManagerEvent::ItemUpdated { item } => with_rows(window, |rows| {
+            let row = history_row(item);
+            if let Some(index) = find_history_row(rows, row.id) {
+                rows.set_row_data(index, row);
+            }
+        }),
+        ManagerEvent::ItemDeleted { ids } => with_rows(window, |rows| {
+            for id in ids {
+                if let Some(index) = find_history_row(rows, id as i32) {
+                    rows.remove(index);
+                }
+            }
+        }),
+        ManagerEvent::PinChanged { id, pinned, pin_order } => with_rows(window, |rows| {
+            let Some(index) = find_history_row(rows, id as i32) else {
+                return;
+            };
+            let Some(mut row) = rows.row_data(index) else {
+                return;
+            };
+            row.is_pinned = pinned;
+            row.pin_order = pin_order.unwrap_or(0) as i32;
+            if searching {
+                rows.set_row_data(index, row);
+            } else {
+                place_history_row(rows, row);
+            }
+        }),
+        ManagerEvent::QueueChanged { state } => show_queue(window, state),
+        ManagerEvent::Paused { .. } => {}
+    }
+}
+
+fn show_queue(window: &AppWindow, state: QueueState) {
+    let rows: Vec<QueueRow> = state
+        .entries
+        .into_iter()
+        .map(|entry| QueueRow {
+            id: entry.id as i32,
+            type_val: entry.type_ as i32,
+            content: entry.content.into(),
+        })
+        .collect();
+    window.set_queue_model(std::rc::Rc::new(slint::VecModel::from(rows)).into());
+    window.set_queue_mode_enabled(state.active);
+    window.set_queue_lifo(state.order == QueueOrder::Lifo);
+}
+
+/// Feeds manager events to the window until the manager goes away. Events
+/// missed because the window fell behind are made up for by reloading the
+/// current list.
+async fn forward_events(
+    window: slint::Weak<AppWindow>,
+    mut events: broadcast::Receiver<ManagerEvent>,
+    command_tx: mpsc::Sender<CommandRequest>,
+) {
+    loop {
+        match events.recv().await {
+            Ok(event) => {
+                let _ = window.upgrade_in_event_loop(move |window| apply_event(&window, event));
+            }
+            Err(broadcast::error::RecvError::Lagged(missed)) => {
+                log::warn!("Window missed {} manager events, reloading", missed);
+                let tx = command_tx.clone();
+                let _ = window.upgrade_in_event_loop(move |window| {
+                    let _ = tx.try_send(AppCommand::Search(window.get_search_query().into()).into());
+                });
+            }
+            Err(broadcast::error::RecvError::Closed) => break,
+        }
+    }
+}
+
 impl UiHandle for SlintUi {
     fn update_history(&self, items: Vec<CoreClipboardItem>) {
         let _ = self.window.upgrade_in_event_loop(move |window| {
-            let model: Vec<ClipboardItem> = items.into_iter().map(|item| {
-                ClipboardItem {
-                    id: item.id.unwrap_or(0) as i32,
-                    type_val: item.type_ as i32,
-                    content: item.content.into(),
-                    is_pinned: item.is_pinned,
-                    source: item.source_app.unwrap_or_default().into(),
-                }
-            }).collect();
+            let model: Vec<ClipboardItem> = items.into_iter().map(history_row).collect();
 
             // Slint VecModel
             let vec_model = std::rc::Rc::new(slint::VecModel::from(model));
@@ -37,11 +243,62 @@ impl UiHandle for SlintUi {
         });
     }
 
+    fn append_history(&self, items: Vec<CoreClipboardItem>) {
+        let _ = self.window.upgrade_in_event_loop(move |window| {
+            with_rows(&window, |rows| {
+                for item in items {
+                    // A row placed by an event may also come back in a later page
+                    let row = history_row(item);
+                    if find_history_row(rows, row.id).is_none() {
+                        rows.push(row);
+                    }
+                }
+            });
+        });
+    }
+
     fn update_search_results(&self, items: Vec<CoreClipboardItem>) {
         // Reuse update_history for now as they use the same view in MVP
         self.update_history(items);
     }
 
+    fn update_unified_results(&self, results: Vec<SearchResult>) {
+        let _ = self.window.upgrade_in_event_loop(move |window| {
+            let model: Vec<ClipboardItem> = results.into_iter().map(search_row).collect();
+
+            let vec_model = std::rc::Rc::new(slint::VecModel::from(model));
+            window.set_history_model(vec_model.into());
+        });
+    }
+
+    fn append_unified_results(&self, results: Vec<SearchResult>) {
+        let _ = self.window.upgrade_in_event_loop(move |window| {
+            with_rows(&window, |rows| {
+                for result in results {
+                    rows.push(search_row(result));
+                }
+            });
+        });
+    }
+
+    fn show_undo(&self, msg: String, duration: std::time::Duration) {
+        let _ = self.window.upgrade_in_event_loop(move |window| {
+            // A newer undo offer replaces this one; only the latest timer clears the bar
+            let serial = window.get_undo_serial() + 1;
+            window.set_undo_serial(serial);
+            window.set_undo_message(msg.into());
+
+            let weak = window.as_weak();
+            slint::Timer::single_shot(duration, move || {
+                if let Some(window) = weak.upgrade() {
+                    if window.get_undo_serial() == serial {
+                        window.set_undo_message(Default::default());
+                    }
+                }
+            });
+        });
+    }
+
     fn hide_window(&self) {
         let _ = self.window.upgrade_in_event_loop(|window| {
             window.hide().unwrap();
@@ -49,19 +306,154 @@ impl UiHandle for SlintUi {
     }
 
     fn show_notification(&self, msg: String) {
-        println!("Notification: {}", msg);
+        log::warn!("{}", msg);
+        let _ = self.window.upgrade_in_event_loop(move |window| {
+            // Same as the undo bar: only the latest notification's timer clears it
+            let serial = window.get_notification_serial() + 1;
+            window.set_notification_serial(serial);
+            window.set_notification_message(msg.into());
+
+            let weak = window.as_weak();
+            slint::Timer::single_shot(NOTIFICATION_DURATION, move || {
+                if let Some(window) = weak.upgrade() {
+                    if window.get_notification_serial() == serial {
+                        window.set_notification_message(Default::default());
+                    }
+                }
+            });
+        });
+    }
+}
+
+/// Command-line flags.
+#[derive(Default)]
+struct Options {
+    /// Run without the window: monitor and manager only.
+    daemon: bool,
+    /// With `daemon`, stay attached to the terminal instead of detaching.
+    foreground: bool,
+}
+
+impl Options {
+    fn parse(args: impl Iterator<Item = String>) -> Result<Self> {
+        let mut options = Self::default();
+        for arg in args {
+            match arg.as_str() {
+                "--daemon" => options.daemon = true,
+                "--foreground" => options.foreground = true,
+                #[cfg(unix)]
+                _ => bail!("unknown argument: {}\n\n{}", arg, cli::USAGE),
+                #[cfg(not(unix))]
+                _ => bail!("unknown argument: {}", arg),
+            }
+        }
+        Ok(options)
+    }
+}
+
+/// Runs the clipboard monitor, the control socket and the manager until the
+/// manager exits. `subscribe` gets the manager's events before it starts.
+async fn run_core(
+    db: Database,
+    config: Config,
+    ui: Arc<dyn UiHandle>,
+    command_tx: mpsc::Sender<CommandRequest>,
+    command_rx: mpsc::Receiver<CommandRequest>,
+    subscribe: impl FnOnce(broadcast::Receiver<ManagerEvent>),
+) {
+    let (clipboard_tx, clipboard_rx) = mpsc::channel(100);
+
+    // The monitor skips the Manager's own clipboard writes
+    let self_writes = SelfWrites::default();
+    match ClipboardMonitor::new(clipboard_tx, self_writes.clone()) {
+        Ok(monitor) => {
+            tokio::spawn(monitor.run());
+        }
+        // No display on a server: the manager still serves commands
+        Err(e) => log::error!("Clipboard monitor unavailable, nothing will be captured: {:#}", e),
     }
+
+    #[cfg(unix)]
+    let ipc_db = AsyncDatabase::new(db.clone());
+    let manager = Manager::new(db, clipboard_rx, command_rx, ui, config, self_writes);
+    subscribe(manager.events().subscribe());
+
+    #[cfg(unix)]
+    match IpcServer::bind(&paths::get_socket_path(), ipc_db, command_tx, manager.events()) {
+        Ok(server) => {
+            tokio::spawn(server.run());
+        }
+        Err(e) => log::error!("Control socket unavailable: {:#}", e),
+    }
+    #[cfg(not(unix))]
+    drop(command_tx);
+
+    manager.run().await;
+}
+
+/// Headless mode: no window, shut down cleanly on SIGTERM or Ctrl+C.
+fn run_daemon(db: Database, config: Config) -> Result<()> {
+    let (command_tx, command_rx) = mpsc::channel(100);
+    let rt = Runtime::new()?;
+    rt.block_on(async {
+        let shutdown_tx = command_tx.clone();
+        tokio::spawn(async move {
+            wait_for_shutdown_signal().await;
+            log::info!("Shutting down");
+            let _ = shutdown_tx.send(AppCommand::Exit.into()).await;
+        });
+        run_core(db, config, Arc::new(HeadlessUi), command_tx, command_rx, drop).await;
+    });
+    Ok(())
+}
+
+async fn wait_for_shutdown_signal() {
+    #[cfg(unix)]
+    {
+        use tokio::signal::unix::{signal, SignalKind};
+        match signal(SignalKind::terminate()) {
+            Ok(mut terminate) => {
+                tokio::select! {
+                    _ = terminate.recv() => {}
+                    _ = tokio::signal::ctrl_c() => {}
+                }
+                return;
+            }
+            Err(e) => log::error!("Failed to listen for SIGTERM: {}", e),
+        }
+    }
+    let _ = tokio::signal::ctrl_c().await;
 }
 
 fn main() -> Result<()> {
     env_logger::init();
+    let args: Vec<String> = std::env::args().skip(1).collect();
+    // `picaclip <command>` is a client; it never takes the instance lock
+    #[cfg(unix)]
+    if let Some(invocation) = Invocation::parse(&args)? {
+        return cli::run(&invocation);
+    }
+    let options = Options::parse(args.into_iter())?;
+
+    if options.daemon && !options.foreground {
+        let pid = daemon::spawn_background(&paths::get_pid_path(), &paths::get_daemon_log_path())?;
+        println!("picaclip daemon started (pid {})", pid);
+        return Ok(());
+    }
 
-    // 1. Initialize DB
+    // Held until main returns; a second instance stops here
+    let _pidfile = PidFile::acquire(&paths::get_pid_path())?;
+
+    // 1. Load config & initialize DB
+    let config = Config::load();
     let db_path = paths::get_db_path();
-    let db = Database::new(&db_path)?;
+    let db = Database::with_tokenizer(&db_path, config.search.tokenizer)?;
+
+    if options.daemon {
+        return run_daemon(db, config);
+    }
 
     // 2. Create Channels
-    let (clipboard_tx, clipboard_rx) = mpsc::channel(100);
     let (command_tx, command_rx) = mpsc::channel(100);
 
     // 3. Initialize Slint Window
@@ -74,50 +466,118 @@ fn main() -> Result<()> {
     });
 
     // 5. Start Core Manager in background thread (Tokio Runtime)
+    let core_tx = command_tx.clone();
+    let events_tx = command_tx.clone();
     std::thread::spawn(move || {
         let rt = Runtime::new().unwrap();
-        rt.block_on(async {
-            // Start Clipboard Monitor
-            let monitor = crate::core::clipboard::ClipboardMonitor::new(clipboard_tx)
-                .expect("Failed to init clipboard monitor");
-
-            tokio::spawn(monitor.run());
-
-            // Start Manager
-            let manager = Manager::new(db, clipboard_rx, command_rx, ui_handle);
-            manager.run().await;
-        });
+        let subscribe = |events| {
+            tokio::spawn(forward_events(window_handle, events, events_tx));
+        };
+        rt.block_on(run_core(db, config, ui_handle, core_tx, command_rx, subscribe));
     });
 
     // 6. Bind UI Callbacks
     let tx = command_tx.clone();
     main_window.on_search(move |query| {
-        let _ = tx.blocking_send(AppCommand::Search(query.into()));
+        let _ = tx.blocking_send(AppCommand::Search(query.into()).into());
     });
 
     let tx = command_tx.clone();
-    main_window.on_paste_item(move |id| {
-        let _ = tx.blocking_send(AppCommand::PasteItem(id as i64));
+    main_window.on_paste_item(move |id, plain| {
+        let options = if plain { PasteOptions::plain() } else { PasteOptions::default() };
+        let _ = tx.blocking_send(AppCommand::PasteItem(id as i64, options).into());
+    });
+
+    let tx = command_tx.clone();
+    main_window.on_paste_snippet(move |id, plain| {
+        let options = if plain { PasteOptions::plain() } else { PasteOptions::default() };
+        let _ = tx.blocking_send(AppCommand::PasteSnippet(id as i64, options).into());
     });
 
     let tx = command_tx.clone();
     main_window.on_delete_item(move |id| {
-        let _ = tx.blocking_send(AppCommand::DeleteHistory(id as i64));
+        let _ = tx.blocking_send(AppCommand::DeleteHistory(id as i64).into());
     });
 
     let tx = command_tx.clone();
     main_window.on_toggle_pin(move |id| {
-        let _ = tx.blocking_send(AppCommand::TogglePin(id as i64));
+        let _ = tx.blocking_send(AppCommand::TogglePin(id as i64).into());
     });
 
     let tx = command_tx.clone();
     main_window.on_toggle_queue_mode(move |enabled| {
-        let _ = tx.blocking_send(AppCommand::ToggleQueueMode(enabled));
+        let _ = tx.blocking_send(AppCommand::ToggleQueueMode(enabled).into());
     });
 
     let tx = command_tx.clone();
     main_window.on_next_queue_item(move || {
-        let _ = tx.blocking_send(AppCommand::NextQueueItem);
+        let _ = tx.blocking_send(AppCommand::NextQueueItem.into());
+    });
+
+    let tx = command_tx.clone();
+    main_window.on_move_queue_entry(move |id, index| {
+        let _ = tx.blocking_send(AppCommand::MoveQueueEntry(id as i64, index.max(0) as usize).into());
+    });
+
+    let tx = command_tx.clone();
+    main_window.on_remove_queue_entry(move |id| {
+        let _ = tx.blocking_send(AppCommand::RemoveQueueEntry(id as i64).into());
+    });
+
+    let tx = command_tx.clone();
+    main_window.on_set_queue_lifo(move |lifo| {
+        let order = if lifo { QueueOrder::Lifo } else { QueueOrder::Fifo };
+        let _ = tx.blocking_send(AppCommand::SetQueueOrder(order).into());
+    });
+
+    let tx = command_tx.clone();
+    main_window.on_paste_queue_all(move || {
+        let _ = tx.blocking_send(AppCommand::PasteQueueAll("\n".to_string()).into());
+    });
+
+    let tx = command_tx.clone();
+    main_window.on_enqueue_item(move |kind, id| {
+        let cmd = match kind {
+            0 => AppCommand::EnqueueHistory(vec![id as i64]),
+            _ => AppCommand::EnqueueSnippets(vec![id as i64]),
+        };
+        let _ = tx.blocking_send(cmd.into());
+    });
+
+    let tx = command_tx.clone();
+    main_window.on_undo(move || {
+        let _ = tx.blocking_send(AppCommand::Undo.into());
+    });
+
+    let tx = command_tx.clone();
+    main_window.on_clear_history(move |query| {
+        let cmd = if query.is_empty() {
+            AppCommand::ClearHistory(ClearFilter::Unpinned)
+        } else {
+            AppCommand::ClearSearch(query.to_string())
+        };
+        let _ = tx.blocking_send(cmd.into());
+    });
+
+    let tx = command_tx.clone();
+    main_window.on_move_pinned(move |id, up| {
+        let to = if up { PinMove::Up } else { PinMove::Down };
+        let _ = tx.blocking_send(AppCommand::MovePinned(id as i64, to).into());
+    });
+
+    let tx = command_tx.clone();
+    main_window.on_edit_item(move |id, content| {
+        let _ = tx.blocking_send(AppCommand::EditItem(id as i64, content.to_string()).into());
+    });
+
+    let tx = command_tx.clone();
+    main_window.on_revert_edit(move |id| {
+        let _ = tx.blocking_send(AppCommand::RevertEdit(id as i64).into());
+    });
+
+    let tx = command_tx.clone();
+    main_window.on_load_more(move || {
+        let _ = tx.blocking_send(AppCommand::LoadMore.into());
     });
 
     let tx = command_tx.clone();
@@ -0,0 +1,741 @@
+//! `picaclip <command>` for shell scripts.
+//!
+//! Commands go to the running instance over the control socket, so the
+//! window and the history stay in sync. With nothing running, the database
+//! is opened directly; only `copy` and `paste` need a live instance.
+
+use crate::core::clipboard;
+use crate::core::config::Config;
+use crate::core::database::Database;
+use crate::core::ipc::{self, Call, Client};
+use crate::core::types::{
+    ClipboardItem, ClipboardType, HistoryPage, HistoryStats, PasteOptions, SearchHit, SearchResult, Snippet,
+};
+use crate::ui::picker::{self, EntryId};
+use crate::utils::paths;
+use anyhow::{anyhow, bail, Context, Result};
+use std::io::{ErrorKind, Read, Write};
+
+pub const USAGE: &str = "\
+usage: picaclip [--daemon [--foreground]]
+       picaclip <command> [--json] [--limit N] [--type text|image|file]
+       picaclip pick [--null] | rofi -dmenu | picaclip select [--paste]
+
+commands:
+  list              recent history, pinned items first
+  search <query>    history, snippets and tags matching the query
+  get <id>          print the content of a history item
+  copy <id>         put a history item on the clipboard
+  paste <id>        paste a history item into the focused window
+  pin <id>          pin a history item
+  unpin <id>        unpin a history item
+  delete <id>       move a history item to the trash
+  add               save text from stdin to history
+  snippets          list snippets
+  stats             usage totals; --limit sets how many apps are shown
+  pick              history and snippets as `<id><TAB><label>` picker entries,
+                    one per line, or NUL-terminated with --null
+  select [entry]    copy the item of a picker entry, read from stdin unless
+                    given; --paste pastes it instead
+  help              show this message";
+
+/// Longest preview shown for one item in text output, in characters.
+const PREVIEW_CHARS: usize = 72;
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum Command {
+    List,
+    Search(String),
+    Get(i64),
+    Copy(i64),
+    Paste(i64),
+    Pin(i64),
+    Unpin(i64),
+    Delete(i64),
+    Add,
+    Snippets,
+    Stats,
+    Pick,
+    /// The picker's output, when given as an argument rather than on stdin.
+    Select(Option<String>),
+    Help,
+}
+
+/// A parsed command line.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Invocation {
+    pub command: Command,
+    /// JSON instead of text output.
+    pub json: bool,
+    pub limit: Option<usize>,
+    pub type_: Option<ClipboardType>,
+    /// `pick`: end entries with NUL instead of a newline.
+    pub null: bool,
+    /// `select`: paste instead of copy.
+    pub paste: bool,
+}
+
+impl Invocation {
+    /// `None` when no command is named, i.e. the app itself should start.
+    pub fn parse(args: &[String]) -> Result<Option<Self>> {
+        let mut words = Vec::new();
+        let mut json = false;
+        let mut limit = None;
+        let mut type_ = None;
+        let mut null = false;
+        let mut paste = false;
+
+        let mut args = args.iter();
+        while let Some(arg) = args.next() {
+            let (flag, inline) = match arg.split_once('=') {
+                Some((flag, value)) if arg.starts_with("--") => (flag, Some(value.to_string())),
+                _ => (arg.as_str(), None),
+            };
+            let mut value = || {
+                inline
+                    .clone()
+                    .or_else(|| args.next().cloned())
+                    .ok_or_else(|| anyhow!("{} needs a value", flag))
+            };
+            match flag {
+                "--json" => json = true,
+                "--limit" => {
+                    let value = value()?;
+                    limit = Some(value.parse().map_err(|_| anyhow!("invalid limit: {}", value))?);
+                }
+                "--type" => type_ = Some(parse_type(&value()?)?),
+                "--null" => null = true,
+                "--paste" => paste = true,
+                // The app's own flags; with a command they are an error below
+                "--daemon" | "--foreground" => words.push(arg.clone()),
+                _ if flag.starts_with("--") => bail!("unknown option: {}", flag),
+                _ => words.push(arg.clone()),
+            }
+        }
+
+        let Some(name) = words.first() else {
+            return Ok(None);
+        };
+        if name.starts_with("--") {
+            return Ok(None);
+        }
+        let operand = words.get(1).map(String::as_str);
+        let id = || parse_id(operand);
+        let command = match name.as_str() {
+            "list" => Command::List,
+            "search" => Command::Search(words[1..].join(" ")),
+            "get" => Command::Get(id()?),
+            "copy" => Command::Copy(id()?),
+            "paste" => Command::Paste(id()?),
+            "pin" => Command::Pin(id()?),
+            "unpin" => Command::Unpin(id()?),
+            "delete" => Command::Delete(id()?),
+            "add" => Command::Add,
+            "snippets" => Command::Snippets,
+            "stats" => Command::Stats,
+            "pick" => Command::Pick,
+            "select" => Command::Select(operand.map(String::from)),
+            "help" => Command::Help,
+            _ => bail!("unknown command: {}\n\n{}", name, USAGE),
+        };
+        let expected = match command {
+            Command::Search(_) => words.len(),
+            Command::Get(_)
+            | Command::Copy(_)
+            | Command::Paste(_)
+            | Command::Pin(_)
+            | Command::Unpin(_)
+            | Command::Delete(_)
+            | Command::Select(_) => 2,
+            _ => 1,
+        };
+        if let Some(extra) = words.get(expected) {
+            bail!("unexpected argument: {}", extra);
+        }
+        if command == Command::Search(String::new()) {
+            bail!("search needs a query");
+        }
+        if null && command != Command::Pick {
+            bail!("--null only applies to pick");
+        }
+        if paste && !matches!(command, Command::Select(_)) {
+            bail!("--paste only applies to select");
+        }
+
+        Ok(Some(Self { command, json, limit, type_, null, paste }))
+    }
+
+    /// Runs the command against `backend`, writing what it prints to `out`.
+    /// `input` is only read by `add`.
+    pub fn execute(&self, backend: &mut Backend, input: &mut dyn Read, out: &mut dyn Write) -> Result<()> {
+        let limit = self.limit.unwrap_or(ipc::DEFAULT_LIMIT);
+        match &self.command {
+            Command::List => {
+                let items = backend.list(limit, self.type_)?;
+                self.print(out, items.as_slice(), render_items)
+            }
+            Command::Search(query) => {
+                let mut results = backend.search(query, limit)?;
+                if let Some(type_) = self.type_ {
+                    results.retain(|result| matches!(&result.hit, SearchHit::History(item) if item.type_ == type_));
+                }
+                self.print(out, results.as_slice(), render_results)
+            }
+            Command::Get(id) => {
+                let item = backend.get(*id)?;
+                if self.json {
+                    self.print(out, &item, |_| String::new())
+                } else {
+                    // Exactly the stored content, so it can be piped on
+                    out.write_all(item.content.as_bytes())?;
+                    Ok(())
+                }
+            }
+            Command::Copy(id) => backend.copy(*id),
+            Command::Paste(id) => backend.paste(*id),
+            Command::Pin(id) => backend.set_pinned(*id, true),
+            Command::Unpin(id) => backend.set_pinned(*id, false),
+            Command::Delete(id) => backend.delete(*id),
+            Command::Add => {
+                let mut content = String::new();
+                input.read_to_string(&mut content).context("Failed to read stdin")?;
+                let Some(item) = clipboard::captured_text(&content) else {
+                    bail!("nothing to add: stdin is empty");
+                };
+                backend.add(item)
+            }
+            Command::Snippets => {
+                let mut snippets = backend.snippets()?;
+                snippets.truncate(limit);
+                self.print(out, snippets.as_slice(), render_snippets)
+            }
+            Command::Stats => {
+                let top_apps = self.limit.unwrap_or(ipc::DEFAULT_TOP_APPS);
+                let stats = backend.stats(ipc::DEFAULT_STATS_DAYS, top_apps)?;
+                self.print(out, &stats, render_stats)
+            }
+            Command::Pick => {
+                let items = backend.list(limit, self.type_)?;
+                // Snippets are text; leave them out when asking for images or files
+                let snippets = match self.type_ {
+                    None | Some(ClipboardType::Text) => backend.snippets()?,
+                    Some(_) => Vec::new(),
+                };
+                let entries: Vec<String> = items
+                    .iter()
+                    .map(picker::history_entry)
+                    .chain(snippets.iter().map(picker::snippet_entry))
+                    .collect();
+                let end = if self.null { '\0' } else { '\n' };
+                self.print(out, entries.as_slice(), |entries| {
+                    entries.iter().map(|entry| format!("{}{}", entry, end)).collect()
+                })
+            }
+            Command::Select(entry) => {
+                let selection = match entry {
+                    Some(entry) => entry.clone(),
+                    None => {
+                        let mut selection = String::new();
+                        input.read_to_string(&mut selection).context("Failed to read stdin")?;
+                        selection
+                    }
+                };
+                let Some(id) = EntryId::from_selection(&selection)? else {
+                    bail!("nothing selected");
+                };
+                match (id, self.paste) {
+                    (EntryId::History(id), false) => backend.copy(id),
+                    (EntryId::History(id), true) => backend.paste(id),
+                    (EntryId::Snippet(id), false) => backend.copy_snippet(id),
+                    (EntryId::Snippet(id), true) => backend.paste_snippet(id),
+                }
+            }
+            Command::Help => {
+                writeln!(out, "{}", USAGE)?;
+                Ok(())
+            }
+        }
+    }
+
+    fn print<T: serde::Serialize + ?Sized>(
+        &self,
+        out: &mut dyn Write,
+        value: &T,
+        render: impl Fn(&T) -> String,
+    ) -> Result<()> {
+        if self.json {
+            writeln!(out, "{}", serde_json::to_string_pretty(value)?)?;
+        } else {
+            out.write_all(render(value).as_bytes())?;
+        }
+        Ok(())
+    }
+}
+
+/// Runs a command line against the running instance, or the database when
+/// there is none.
+pub fn run(invocation: &Invocation) -> Result<()> {
+    let mut backend = Backend::connect()?;
+    let stdout = std::io::stdout();
+    let mut out = stdout.lock();
+    let result = invocation
+        .execute(&mut backend, &mut std::io::stdin().lock(), &mut out)
+        .and_then(|()| Ok(out.flush()?));
+    match result {
+        // Output cut short by `| head` and the like
+        Err(e) if e.downcast_ref::<std::io::Error>().map(std::io::Error::kind) == Some(ErrorKind::BrokenPipe) => {
+            Ok(())
+        }
+        result => result,
+    }
+}
+
+/// Where commands are carried out.
+pub enum Backend {
+    /// The running instance, over its control socket.
+    Remote(Client),
+    /// The database file, when nothing is running.
+    Local(Database),
+}
+
+impl Backend {
+    pub fn connect() -> Result<Self> {
+        if let Ok(client) = Client::connect(&paths::get_socket_path()) {
+            return Ok(Self::Remote(client));
+        }
+        let config = Config::load();
+        let db = Database::with_tokenizer(&paths::get_db_path(), config.search.tokenizer)?;
+        Ok(Self::Local(db))
+    }
+
+    fn list(&mut self, limit: usize, type_: Option<ClipboardType>) -> Result<Vec<ClipboardItem>> {
+        let page: HistoryPage = match self {
+            Self::Remote(client) => client.call(Call::List { limit: Some(limit), after: None, type_ })?,
+            Self::Local(db) => db.get_history_page_of_type(None, limit, type_)?,
+        };
+        Ok(page.items)
+    }
+
+    fn search(&mut self, query: &str, limit: usize) -> Result<Vec<SearchResult>> {
+        match self {
+            Self::Remote(client) => client.call(Call::Search { query: query.to_string(), limit: Some(limit) }),
+            Self::Local(db) => db.search_all(query, limit),
+        }
+    }
+
+    fn get(&mut self, id: i64) -> Result<ClipboardItem> {
+        match self {
+            Self::Remote(client) => client.call(Call::Get { item: id }),
+            Self::Local(db) => db.get_item_by_id(id)?.ok_or_else(|| not_found(id)),
+        }
+    }
+
+    fn copy(&mut self, id: i64) -> Result<()> {
+        match self {
+            Self::Remote(client) => client.call(Call::Copy { item: id, options: PasteOptions::default() }),
+            Self::Local(_) => Err(not_running("copy")),
+        }
+    }
+
+    fn paste(&mut self, id: i64) -> Result<()> {
+        match self {
+            Self::Remote(client) => client.call(Call::Paste { item: id, options: PasteOptions::default() }),
+            Self::Local(_) => Err(not_running("paste")),
+        }
+    }
+
+    fn copy_snippet(&mut self, id: i64) -> Result<()> {
+        match self {
+            Self::Remote(client) => client.call(Call::CopySnippet { snippet: id, options: PasteOptions::default() }),
+            Self::Local(_) => Err(not_running("copy")),
+        }
+    }
+
+    fn paste_snippet(&mut self, id: i64) -> Result<()> {
+        match self {
+            Self::Remote(client) => client.call(Call::PasteSnippet { snippet: id, options: PasteOptions::default() }),
+            Self::Local(_) => Err(not_running("paste")),
+        }
+    }
+
+    fn set_pinned(&mut self, id: i64, pinned: bool) -> Result<()> {
+        match self {
+            Self::Remote(client) => {
+                client.call::<serde_json::Value>(Call::Pin { item: id, pinned: Some(pinned) })?;
+            }
+            Self::Local(db) => {
+                if db.get_item_by_id(id)?.is_none() {
+                    return Err(not_found(id));
+                }
+                db.set_pinned(id, pinned)?;
+            }
+        }
+        Ok(())
+    }
+
+    fn delete(&mut self, id: i64) -> Result<()> {
+        match self {
+            Self::Remote(client) => client.call(Call::Delete { item: id }),
+            Self::Local(db) => {
+                // Through the trash, like a delete in the window
+                if db.trash_history(&[id])?.1 == 0 {
+                    return Err(not_found(id));
+                }
+                Ok(())
+            }
+        }
+    }
+
+    fn add(&mut self, item: ClipboardItem) -> Result<()> {
+        match self {
+            Self::Remote(client) => client.call(Call::Add { content: item.content }),
+            Self::Local(db) => {
+                db.insert_history(&item)?;
+                Ok(())
+            }
+        }
+    }
+
+    fn snippets(&mut self) -> Result<Vec<Snippet>> {
+        match self {
+            Self::Remote(client) => client.call(Call::Snippets),
+            Self::Local(db) => db.get_all_snippets(),
+        }
+    }
+
+    fn stats(&mut self, days: u32, top_apps: usize) -> Result<HistoryStats> {
+        match self {
+            Self::Remote(client) => client.call(Call::Stats { days: Some(days), top_apps: Some(top_apps) }),
+            Self::Local(db) => db.stats(days, top_apps),
+        }
+    }
+}
+
+/// Worded like the control socket's `not_found` error.
+fn not_found(id: i64) -> anyhow::Error {
+    anyhow!("history item {} not found", id)
+}
+
+fn not_running(command: &str) -> anyhow::Error {
+    anyhow!("{} needs a running picaclip; start one with `picaclip --daemon`", command)
+}
+
+fn parse_id(operand: Option<&str>) -> Result<i64> {
+    let operand = operand.ok_or_else(|| anyhow!("missing item id"))?;
+    operand.parse().map_err(|_| anyhow!("invalid item id: {}", operand))
+}
+
+fn parse_type(name: &str) -> Result<ClipboardType> {
+    match name {
+        "text" => Ok(ClipboardType::Text),
+        "image" => Ok(ClipboardType::Image),
+        "file" => Ok(ClipboardType::File),
+        _ => bail!("unknown type: {} (expected text, image or file)", name),
+    }
+}
+
+fn type_name(type_: ClipboardType) -> &'static str {
+    match type_ {
+        ClipboardType::Text => "text",
+        ClipboardType::Image => "image",
+        ClipboardType::File => "file",
+    }
+}
+
+/// One line per item: id, `*` when pinned, type and a preview.
+pub fn render_items(items: &[ClipboardItem]) -> String {
+    items.iter().map(|item| format!("{}\n", item_line(item))).collect()
+}
+
+fn item_line(item: &ClipboardItem) -> String {
+    format!(
+        "{:>6} {} {:<5}  {}",
+        item.id.unwrap_or_default(),
+        if item.is_pinned { '*' } else { ' ' },
+        type_name(item.type_),
+        preview(item.type_, &item.content)
+    )
+}
+
+/// One line per hit, led by what kind of thing matched.
+pub fn render_results(results: &[SearchResult]) -> String {
+    results
+        .iter()
+        .map(|result| match &result.hit {
+            SearchHit::History(item) => format!("history {}\n", item_line(item)),
+            SearchHit::Snippet(snippet) => format!("snippet {}\n", snippet_line(snippet)),
+            SearchHit::Tag(tag) => format!("tag     {:>6}  #{}\n", tag.id.unwrap_or_default(), tag.name),
+        })
+        .collect()
+}
+
+pub fn render_snippets(snippets: &[Snippet]) -> String {
+    snippets.iter().map(|snippet| format!("{}\n", snippet_line(snippet))).collect()
+}
+
+fn snippet_line(snippet: &Snippet) -> String {
+    let content = if snippet.is_masked {
+        "******".to_string()
+    } else {
+        preview(ClipboardType::Text, &snippet.content)
+    };
+    format!("{:>6}  {}: {}", snippet.id.unwrap_or_default(), snippet.title, content)
+}
+
+pub fn render_stats(stats: &HistoryStats) -> String {
+    let mut out = format!(
+        "items    {} ({} pinned)\ncopies   {}\npastes   {}\nstorage  {} database, {} image(s) in {}\n",
+        stats.total_items,
+        stats.pinned_items,
+        stats.copies,
+        stats.pastes,
+        format_bytes(stats.storage.database_bytes),
+        stats.storage.image_files,
+        format_bytes(stats.storage.image_bytes),
+    );
+    if !stats.items_per_day.is_empty() {
+        out.push_str("\nnew items per day\n");
+        for (day, items) in &stats.items_per_day {
+            out.push_str(&format!("  {}  {}\n", day, items));
+        }
+    }
+    if !stats.top_apps.is_empty() {
+        out.push_str("\ntop apps\n");
+        let width = stats.top_apps.iter().map(|(app, _)| app.chars().count()).max().unwrap_or(0);
+        for (app, items) in &stats.top_apps {
+            out.push_str(&format!("  {:<width$}  {}\n", app, items, width = width));
+        }
+    }
+    out
+}
+
+/// First line of the content, cut to `PREVIEW_CHARS`, noting what was left
+/// out. File items list one path per line.
+fn preview(type_: ClipboardType, content: &str) -> String {
+    let mut lines = content.lines().map(str::trim).filter(|line| !line.is_empty());
+    let first = lines.next().unwrap_or("");
+    let rest = lines.count();
+
+    let mut text: String = first.chars().take(PREVIEW_CHARS).collect();
+    if first.chars().count() > PREVIEW_CHARS {
+        text.push('…');
+    }
+    match (type_, rest) {
+        (_, 0) => {}
+        (ClipboardType::File, rest) => text.push_str(&format!(" (+{} more)", rest)),
+        (_, rest) => text.push_str(&format!(" (+{} line{})", rest, if rest == 1 { "" } else { "s" })),
+    }
+    text
+}
+
+fn format_bytes(bytes: u64) -> String {
+    const UNITS: [&str; 4] = ["KB", "MB", "GB", "TB"];
+    if bytes < 1024 {
+        return format!("{} B", bytes);
+    }
+    let mut value = bytes as f64 / 1024.0;
+    let mut unit = 0;
+    while value >= 1024.0 && unit < UNITS.len() - 1 {
+        value /= 1024.0;
+        unit += 1;
+    }
+    format!("{:.1} {}", value, UNITS[unit])
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::core::types::StorageStats;
+    use std::path::Path;
+
+    /// Compares against `src/ui/snapshots/cli__<name>.snap`. Set
+    /// `UPDATE_SNAPSHOTS=1` to write new or changed snapshots.
+    fn assert_snapshot(name: &str, actual: &str) {
+        let path = Path::new(env!("CARGO_MANIFEST_DIR"))
+            .join("src/ui/snapshots")
+            .join(format!("cli__{}.snap", name));
+        let expected = std::fs::read_to_string(&path).ok();
+        if expected.as_deref() == Some(actual) {
+            return;
+        }
+        if std::env::var_os("UPDATE_SNAPSHOTS").is_some() {
+            std::fs::create_dir_all(path.parent().unwrap()).unwrap();
+            std::fs::write(&path, actual).unwrap();
+            return;
+        }
+        match expected {
+            Some(expected) => assert_eq!(actual, expected, "snapshot {} changed", name),
+            None => panic!("no snapshot {}; rerun with UPDATE_SNAPSHOTS=1\n{}", path.display(), actual),
+        }
+    }
+
+    fn args(line: &str) -> Vec<String> {
+        line.split_whitespace().map(String::from).collect()
+    }
+
+    fn item(content: &str, type_: ClipboardType) -> ClipboardItem {
+        ClipboardItem {
+            id: None,
+            type_,
+            content: content.to_string(),
+            content_hash: format!("hash-{}", content),
+            source_app: Some("terminal".to_string()),
+            created_at: 0,
+            is_pinned: false,
+            edited_at: None,
+            pin_order: None,
+            tags: Vec::new(),
+        }
+    }
+
+    /// A database with a bit of everything, and a backend on it.
+    fn backend() -> Backend {
+        let db = Database::open_in_memory().unwrap();
+        db.insert_history(&item("/home/me/a.txt\n/home/me/b.txt\n/home/me/c.txt", ClipboardType::File)).unwrap();
+        db.insert_history(&item("/data/images/1f3a.png", ClipboardType::Image)).unwrap();
+        db.insert_history(&item("fn main() {\n    println!(\"hi\");\n}\n", ClipboardType::Text)).unwrap();
+        db.insert_history(&item(&"long ".repeat(20), ClipboardType::Text)).unwrap();
+        let pinned = db.insert_history(&item("ssh deploy@example.com", ClipboardType::Text)).unwrap();
+        db.set_pinned(pinned, true).unwrap();
+
+        let category_id = db.get_categories().unwrap()[0].id.unwrap();
+        for (title, content, is_masked) in [("Greeting", "Hello,\nthanks for", false), ("Token", "hunter2", true)] {
+            db.add_snippet(&Snippet {
+                id: None,
+                category_id,
+                title: title.to_string(),
+                content: content.to_string(),
+                is_masked,
+                usage_count: 0,
+                updated_at: 0,
+            })
+            .unwrap();
+        }
+        Backend::Local(db)
+    }
+
+    fn run(backend: &mut Backend, line: &str, input: &str) -> Result<String> {
+        let invocation = Invocation::parse(&args(line))?.expect("a command");
+        let mut out = Vec::new();
+        invocation.execute(backend, &mut input.as_bytes(), &mut out)?;
+        Ok(String::from_utf8(out).unwrap())
+    }
+
+    #[test]
+    fn test_parse() {
+        assert_eq!(Invocation::parse(&[]).unwrap(), None);
+        assert_eq!(Invocation::parse(&args("--daemon --foreground")).unwrap(), None);
+        assert_eq!(
+            Invocation::parse(&args("search --limit=3 ssh deploy --type text --json")).unwrap(),
+            Some(Invocation {
+                command: Command::Search("ssh deploy".to_string()),
+                json: true,
+                limit: Some(3),
+                type_: Some(ClipboardType::Text),
+                null: false,
+                paste: false,
+            })
+        );
+        let error = |line: &str| Invocation::parse(&args(line)).unwrap_err().to_string();
+        assert_eq!(error("get x"), "invalid item id: x");
+        assert_eq!(error("pin"), "missing item id");
+        assert_eq!(error("list extra"), "unexpected argument: extra");
+        assert_eq!(error("list --limit"), "--limit needs a value");
+        assert_eq!(error("list --type video"), "unknown type: video (expected text, image or file)");
+        assert_eq!(error("list --verbose"), "unknown option: --verbose");
+        assert!(error("frobnicate").starts_with("unknown command: frobnicate"));
+        assert_eq!(error("search"), "search needs a query");
+        assert_eq!(error("list --paste"), "--paste only applies to select");
+        assert_eq!(error("select h:1 h:2"), "unexpected argument: h:2");
+    }
+
+    #[test]
+    fn test_list_output() {
+        let mut backend = backend();
+        assert_snapshot("list", &run(&mut backend, "list", "").unwrap());
+        assert_snapshot("list_images", &run(&mut backend, "list --type image", "").unwrap());
+        assert_eq!(run(&mut backend, "list --json --limit 2", "").unwrap().matches("\"content\"").count(), 2);
+    }
+
+    #[test]
+    fn test_json_output() {
+        // Fixed items: those from the database carry the current time
+        let mut pinned = item("ssh deploy@example.com", ClipboardType::Text);
+        pinned.id = Some(5);
+        pinned.is_pinned = true;
+        pinned.pin_order = Some(-1);
+        pinned.created_at = 1_760_000_000;
+        let mut image = item("/data/images/1f3a.png", ClipboardType::Image);
+        image.id = Some(2);
+        image.created_at = 1_759_990_000;
+
+        let invocation = Invocation::parse(&args("list --json")).unwrap().unwrap();
+        let mut out = Vec::new();
+        invocation.print(&mut out, [pinned, image].as_slice(), render_items).unwrap();
+        assert_snapshot("list_json", &String::from_utf8(out).unwrap());
+    }
+
+    #[test]
+    fn test_search_and_snippets_output() {
+        let mut backend = backend();
+        assert_snapshot("search", &run(&mut backend, "search main", "").unwrap());
+        assert_snapshot("snippets", &run(&mut backend, "snippets", "").unwrap());
+    }
+
+    #[test]
+    fn test_pick_output() {
+        let mut backend = backend();
+        assert_snapshot("pick", &run(&mut backend, "pick", "").unwrap());
+
+        let null = run(&mut backend, "pick --null --type image", "").unwrap();
+        assert_eq!(null, "h:2\t[image] 1f3a.png\0");
+    }
+
+    #[test]
+    fn test_select_needs_a_running_instance() {
+        let mut backend = backend();
+        let mut error = |line: &str, input: &str| run(&mut backend, line, input).unwrap_err().to_string();
+        assert_eq!(error("select", "h:3\tfn main() { ↵ }\n"), not_running("copy").to_string());
+        assert_eq!(error("select --paste s:1", ""), not_running("paste").to_string());
+        assert_eq!(error("select", ""), "nothing selected");
+        assert_eq!(error("select", "plain text\n"), "not a picker entry: plain text");
+    }
+
+    #[test]
+    fn test_stats_output() {
+        let stats = HistoryStats {
+            total_items: 120,
+            pinned_items: 4,
+            copies: 310,
+            pastes: 57,
+            items_per_day: vec![("2026-10-16".to_string(), 12), ("2026-10-17".to_string(), 30)],
+            top_apps: vec![("firefox".to_string(), 40), ("alacritty".to_string(), 25)],
+            storage: StorageStats { database_bytes: 2_411_724, image_files: 3, image_bytes: 901 },
+        };
+        assert_snapshot("stats", &render_stats(&stats));
+    }
+
+    #[test]
+    fn test_get_add_pin_delete_without_a_daemon() {
+        let mut backend = backend();
+        assert_eq!(run(&mut backend, "get 3", "").unwrap(), "fn main() {\n    println!(\"hi\");\n}\n");
+        assert_eq!(run(&mut backend, "get 99", "").unwrap_err().to_string(), "history item 99 not found");
+
+        run(&mut backend, "add", "piped\n").unwrap();
+        run(&mut backend, "add", "piped").unwrap();
+        assert!(run(&mut backend, "add", "").is_err());
+        assert!(run(&mut backend, "add", " \n").is_err());
+        run(&mut backend, "pin 3", "").unwrap();
+        run(&mut backend, "delete 4", "").unwrap();
+        assert_eq!(run(&mut backend, "delete 4", "").unwrap_err().to_string(), "history item 4 not found");
+        let list = run(&mut backend, "list --type text", "").unwrap();
+        // Newly pinned goes on top of the board
+        let pinned = "     3 * text   fn main() { (+2 lines)\n     5 * text   ssh deploy@example.com\n";
+        assert!(list.starts_with(pinned), "{}", list);
+        assert_eq!(list.matches("piped").count(), 1);
+        assert!(!list.contains("long long"));
+
+        let error = run(&mut backend, "paste 3", "").unwrap_err().to_string();
+        assert_eq!(error, "paste needs a running picaclip; start one with `picaclip --daemon`");
+    }
+}
@@ -0,0 +1,23 @@
+use crate::core::manager::UiHandle;
+use crate::core::types::{ClipboardItem, SearchResult};
+use std::time::Duration;
+
+/// `UiHandle` for running without a window (`--daemon`). List updates have
+/// nowhere to go and are dropped; notifications go to the log.
+#[derive(Default)]
+pub struct HeadlessUi;
+
+impl UiHandle for HeadlessUi {
+    fn update_history(&self, _items: Vec<ClipboardItem>) {}
+    fn append_history(&self, _items: Vec<ClipboardItem>) {}
+    fn update_search_results(&self, _items: Vec<ClipboardItem>) {}
+    fn update_unified_results(&self, _results: Vec<SearchResult>) {}
+    fn append_unified_results(&self, _results: Vec<SearchResult>) {}
+    fn show_undo(&self, msg: String, _window: Duration) {
+        log::info!("{}", msg);
+    }
+    fn hide_window(&self) {}
+    fn show_notification(&self, msg: String) {
+        log::info!("{}", msg);
+    }
+}
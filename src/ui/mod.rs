@@ -1 +1,5 @@
 pub mod window;
+pub mod headless;
+#[cfg(unix)]
+pub mod cli;
+pub mod picker;
@@ -0,0 +1,177 @@
+//! Entries for dmenu-style pickers such as rofi, fzf, dmenu and wofi.
+//!
+//! Each entry is `<id>\t<label>`, with `h:<id>` for history items and
+//! `s:<id>` for snippets. Labels are a single line without tabs, so
+//! whatever line the picker hands back leads to the original item through
+//! its id, however the label was shortened.
+
+use crate::core::types::{ClipboardItem, ClipboardType, Snippet};
+use anyhow::{anyhow, Result};
+use std::fmt;
+use std::path::Path;
+
+/// Longest label, in characters, before it is cut with `…`.
+const LABEL_CHARS: usize = 160;
+/// Marks where line breaks were in multi-line content.
+const LINE_BREAK: &str = " ↵ ";
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum EntryId {
+    History(i64),
+    Snippet(i64),
+}
+
+impl fmt::Display for EntryId {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::History(id) => write!(f, "h:{}", id),
+            Self::Snippet(id) => write!(f, "s:{}", id),
+        }
+    }
+}
+
+impl EntryId {
+    /// Reads the entry a picker printed back. Only the first entry counts,
+    /// whether entries end in newlines or NULs; `None` when nothing was
+    /// chosen.
+    pub fn from_selection(selection: &str) -> Result<Option<Self>> {
+        let Some(entry) = selection.split(['\n', '\0']).map(str::trim).find(|entry| !entry.is_empty()) else {
+            return Ok(None);
+        };
+        let id = entry.split('\t').next().unwrap_or(entry).trim();
+        let not_an_entry = || anyhow!("not a picker entry: {}", id);
+        let (kind, number) = id.split_once(':').ok_or_else(not_an_entry)?;
+        let number = number.parse().map_err(|_| not_an_entry())?;
+        match kind {
+            "h" => Ok(Some(Self::History(number))),
+            "s" => Ok(Some(Self::Snippet(number))),
+            _ => Err(not_an_entry()),
+        }
+    }
+}
+
+pub fn history_entry(item: &ClipboardItem) -> String {
+    let pin = if item.is_pinned { "* " } else { "" };
+    format!(
+        "{}\t{}{}",
+        EntryId::History(item.id.unwrap_or_default()),
+        pin,
+        label(item.type_, &item.content)
+    )
+}
+
+pub fn snippet_entry(snippet: &Snippet) -> String {
+    let content = if snippet.is_masked {
+        "******".to_string()
+    } else {
+        label(ClipboardType::Text, &snippet.content)
+    };
+    format!(
+        "{}\t[{}] {}",
+        EntryId::Snippet(snippet.id.unwrap_or_default()),
+        one_line(&snippet.title),
+        content
+    )
+}
+
+/// One-line summary of some content. Images show their file name and file
+/// items their paths, since neither can be shown in a text picker.
+pub fn label(type_: ClipboardType, content: &str) -> String {
+    match type_ {
+        ClipboardType::Text => {
+            let text = content
+                .lines()
+                .map(one_line)
+                .filter(|line| !line.is_empty())
+                .collect::<Vec<_>>()
+                .join(LINE_BREAK);
+            if text.is_empty() {
+                "(blank)".to_string()
+            } else {
+                shorten(&text)
+            }
+        }
+        ClipboardType::Image => {
+            let name = Path::new(content.trim()).file_name().map(|name| name.to_string_lossy());
+            format!("[image] {}", one_line(&name.unwrap_or_default()))
+        }
+        ClipboardType::File => {
+            let paths: Vec<String> = content.lines().map(one_line).filter(|path| !path.is_empty()).collect();
+            let kind = match paths.len() {
+                1 => "[file]".to_string(),
+                n => format!("[{} files]", n),
+            };
+            shorten(&format!("{} {}", kind, paths.join(", ")))
+        }
+    }
+}
+
+/// Trims a line and drops what would break the entry format: tabs become
+/// spaces, other control characters are removed.
+fn one_line(line: &str) -> String {
+    line.trim()
+        .chars()
+        .filter_map(|c| match c {
+            '\t' => Some(' '),
+            c if c.is_control() => None,
+            c => Some(c),
+        })
+        .collect()
+}
+
+fn shorten(text: &str) -> String {
+    if text.chars().count() <= LABEL_CHARS {
+        return text.to_string();
+    }
+    let mut short: String = text.chars().take(LABEL_CHARS).collect();
+    short.push('…');
+    short
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_labels() {
+        let code = "  fn main() {\n\n\tprintln!();\r\n}\n";
+        assert_eq!(label(ClipboardType::Text, code), "fn main() { ↵ println!(); ↵ }");
+        assert_eq!(label(ClipboardType::Text, "a\tb\u{7}c"), "a bc");
+        assert_eq!(label(ClipboardType::Text, " \n \n"), "(blank)");
+        let long = label(ClipboardType::Text, &"x".repeat(500));
+        assert_eq!(long.chars().count(), LABEL_CHARS + 1);
+        assert!(long.ends_with('…'));
+
+        assert_eq!(label(ClipboardType::Image, "/data/images/1f3a.png"), "[image] 1f3a.png");
+        assert_eq!(label(ClipboardType::File, "/home/me/a.txt\n"), "[file] /home/me/a.txt");
+        assert_eq!(label(ClipboardType::File, "/a\n/b\n/c"), "[3 files] /a, /b, /c");
+    }
+
+    #[test]
+    fn test_selection_round_trip() {
+        let item = ClipboardItem {
+            id: Some(12),
+            type_: ClipboardType::Text,
+            content: "two\nlines".to_string(),
+            content_hash: String::new(),
+            source_app: None,
+            created_at: 0,
+            is_pinned: true,
+            edited_at: None,
+            pin_order: Some(0),
+            tags: Vec::new(),
+        };
+        let entry = history_entry(&item);
+        assert_eq!(entry, "h:12\t* two ↵ lines");
+        assert_eq!(EntryId::from_selection(&format!("{}\n", entry)).unwrap(), Some(EntryId::History(12)));
+
+        // fzf --read0 / rofi -sep '\0' output, a bare id, and a cancelled picker
+        assert_eq!(EntryId::from_selection("s:3\t[Greeting] Hello\0").unwrap(), Some(EntryId::Snippet(3)));
+        assert_eq!(EntryId::from_selection("h:7").unwrap(), Some(EntryId::History(7)));
+        assert_eq!(EntryId::from_selection("\n").unwrap(), None);
+
+        assert_eq!(EntryId::from_selection("hello").unwrap_err().to_string(), "not a picker entry: hello");
+        assert!(EntryId::from_selection("x:1\tlabel").is_err());
+        assert!(EntryId::from_selection("h:one").is_err());
+    }
+}
@@ -0,0 +1,153 @@
+use anyhow::{bail, Context, Result};
+use std::fs::{self, File, OpenOptions, TryLockError};
+use std::io::{Read, Seek, SeekFrom, Write};
+use std::path::Path;
+use std::process::{Child, Command, Stdio};
+use std::time::{Duration, Instant};
+
+/// How long `spawn_background` waits for the daemon to take the pidfile.
+const STARTUP_TIMEOUT: Duration = Duration::from_secs(10);
+const STARTUP_POLL: Duration = Duration::from_millis(50);
+
+/// Pidfile that doubles as the single-instance lock. The file stays locked
+/// for as long as this value lives, and is emptied when it is dropped.
+/// The lock is released by the OS if the process dies without cleaning up,
+/// so a stale file from a crash doesn't block the next start.
+///
+/// The file itself is never removed: unlinking it while another instance
+/// waits on the old inode would let two instances lock different files.
+pub struct PidFile {
+    file: File,
+}
+
+impl PidFile {
+    /// Takes the lock and records our pid, or fails naming the running instance.
+    pub fn acquire(path: &Path) -> Result<Self> {
+        let mut file = OpenOptions::new()
+            .read(true)
+            .write(true)
+            .create(true)
+            .truncate(false)
+            .open(path)
+            .with_context(|| format!("Failed to open pidfile {}", path.display()))?;
+
+        match file.try_lock() {
+            Ok(()) => {}
+            Err(TryLockError::WouldBlock) => {
+                let mut pid = String::new();
+                let _ = file.read_to_string(&mut pid);
+                // Empty while the other instance is between locking and writing
+                match pid.trim() {
+                    "" => bail!("picaclip is already running"),
+                    pid => bail!("picaclip is already running (pid {})", pid),
+                }
+            }
+            Err(TryLockError::Error(e)) => {
+                return Err(e).with_context(|| format!("Failed to lock pidfile {}", path.display()))
+            }
+        }
+
+        file.set_len(0)?;
+        file.seek(SeekFrom::Start(0))?;
+        writeln!(file, "{}", std::process::id())?;
+        file.flush()?;
+
+        Ok(Self { file })
+    }
+}
+
+impl Drop for PidFile {
+    fn drop(&mut self) {
+        // Emptied while still locked, so a new instance never sees our pid
+        let _ = self.file.set_len(0);
+        let _ = self.file.unlock();
+    }
+}
+
+/// Starts this executable again as a detached `--daemon --foreground`
+/// process and returns its pid once it holds the pidfile at `pid_path`.
+/// The child gets its own process group, so closing the terminal doesn't
+/// take it down, and its log (stderr) is appended to `log_path`.
+pub fn spawn_background(pid_path: &Path, log_path: &Path) -> Result<u32> {
+    let exe = std::env::current_exe().context("Failed to locate the picaclip executable")?;
+    let log = OpenOptions::new()
+        .create(true)
+        .append(true)
+        .open(log_path)
+        .with_context(|| format!("Failed to open daemon log {}", log_path.display()))?;
+    let mut command = Command::new(exe);
+    command
+        .args(["--daemon", "--foreground"])
+        .stdin(Stdio::null())
+        .stdout(Stdio::null())
+        .stderr(log);
+    #[cfg(unix)]
+    {
+        use std::os::unix::process::CommandExt;
+        command.process_group(0);
+    }
+    let mut child = command.spawn().context("Failed to start the daemon")?;
+    wait_until_ready(&mut child, pid_path, STARTUP_TIMEOUT)
+        .with_context(|| format!("The daemon did not start; see {}", log_path.display()))?;
+    Ok(child.id())
+}
+
+/// Waits until `child` has written its pid to the pidfile, which it only
+/// does once it holds the single-instance lock.
+fn wait_until_ready(child: &mut Child, pid_path: &Path, timeout: Duration) -> Result<()> {
+    let pid = child.id().to_string();
+    let deadline = Instant::now() + timeout;
+    loop {
+        if let Some(status) = child.try_wait()? {
+            bail!("it exited during startup ({})", status);
+        }
+        if fs::read_to_string(pid_path).is_ok_and(|written| written.trim() == pid) {
+            return Ok(());
+        }
+        if Instant::now() >= deadline {
+            bail!("it did not take the pidfile within {:?}", timeout);
+        }
+        std::thread::sleep(STARTUP_POLL);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_pidfile_is_exclusive() {
+        let path = std::env::temp_dir().join(format!("picaclip_test_{}.pid", std::process::id()));
+        let first = PidFile::acquire(&path).unwrap();
+        assert_eq!(fs::read_to_string(&path).unwrap().trim(), std::process::id().to_string());
+
+        let err = PidFile::acquire(&path).err().unwrap();
+        assert_eq!(
+            err.to_string(),
+            format!("picaclip is already running (pid {})", std::process::id())
+        );
+
+        drop(first);
+        assert_eq!(fs::read_to_string(&path).unwrap(), "");
+        let again = PidFile::acquire(&path).unwrap();
+        drop(again);
+        fs::remove_file(&path).unwrap();
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_wait_until_ready() {
+        let path = std::env::temp_dir().join(format!("picaclip_ready_{}.pid", std::process::id()));
+        let script = format!("sleep 0.1; echo $$ > '{}'; sleep 5", path.display());
+        let mut child = Command::new("sh").args(["-c", &script]).spawn().unwrap();
+        wait_until_ready(&mut child, &path, Duration::from_secs(5)).unwrap();
+        child.kill().unwrap();
+        child.wait().unwrap();
+        fs::remove_file(&path).unwrap();
+
+        // A child that gives up, e.g. because another instance holds the lock
+        let mut child = Command::new("sh").args(["-c", "exit 1"]).spawn().unwrap();
+        let err = wait_until_ready(&mut child, &path, Duration::from_secs(5)).unwrap_err();
+        assert!(err.to_string().starts_with("it exited during startup"), "{}", err);
+    }
+}
@@ -1,3 +1,4 @@
 pub mod crypto;
 pub mod text;
 pub mod paths;
+pub mod daemon;
@@ -24,3 +24,22 @@ pub fn get_image_cache_dir() -> PathBuf {
     }
     dir
 }
+
+pub fn get_config_path() -> PathBuf {
+    get_data_dir().join("config.json")
+}
+
+/// Pidfile of the running instance, also used as its single-instance lock.
+pub fn get_pid_path() -> PathBuf {
+    get_data_dir().join("picaclip.pid")
+}
+
+/// Control socket of the running instance (see `core::ipc`).
+pub fn get_socket_path() -> PathBuf {
+    get_data_dir().join("picaclip.sock")
+}
+
+/// Log of the background daemon, which has no terminal to write to.
+pub fn get_daemon_log_path() -> PathBuf {
+    get_data_dir().join("daemon.log")
+}